@@ -1,6 +1,13 @@
 use crate::arch::kernel::pci::get_filesystem_driver;
-use crate::syscalls::fs::{FileError, FilePerms, PosixFile, PosixFileSystem, SeekWhence};
+use crate::drivers::virtio::depr::virtio_fs::VirtioFsDriver;
+use crate::synch::spinlock::SpinlockIrqSave;
+use crate::syscalls::fs::{
+	DirectoryEntry, FileAttr, FileError, FilePerms, FileType, PosixFile, PosixFileSystem,
+	SeekWhence, S_IFLNK, S_IFMT,
+};
 use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::{fmt, u32, u8};
 
@@ -11,22 +18,53 @@ use core::{fmt, u32, u8};
 const FUSE_ROOT_ID: u64 = 1;
 const MAX_READ_LEN: usize = 1024 * 64;
 const MAX_WRITE_LEN: usize = 1024 * 64;
+/// Size of the buffer, which receives the directory entries of one FUSE_READDIR
+const READDIR_LEN: u32 = 4096;
+/// Maximum number of symbolic links, which are followed by `stat`
+const MAX_SYMLINK_DEPTH: usize = 40;
+/// Requests the attributes of the file handle in FUSE_GETATTR
+const FUSE_GETATTR_FH: u32 = 1;
+/// Synchronizes only the data and not the metadata in FUSE_FSYNC
+const FUSE_FSYNC_FDATASYNC: u32 = 1;
+/// Valid fields of FUSE_SETATTR
+const FATTR_SIZE: u32 = 1 << 3;
+const FATTR_FH: u32 = 1 << 6;
 
 pub trait FuseInterface {
 	fn send_command<S, T>(&mut self, cmd: Cmd<S>, rsp: Option<Rsp<T>>) -> Option<Rsp<T>>
 	where
 		S: FuseIn + core::fmt::Debug,
 		T: FuseOut + core::fmt::Debug;
+
+	/// Like [`send_command`](Self::send_command), but the payload of the
+	/// reply is written directly into `buf` instead of the extra buffer of `rsp`.
+	fn send_command_into<S, T>(
+		&mut self,
+		cmd: Cmd<S>,
+		rsp: Rsp<T>,
+		buf: &mut [u8],
+	) -> Option<Rsp<T>>
+	where
+		S: FuseIn + core::fmt::Debug,
+		T: FuseOut + core::fmt::Debug;
 }
 
-pub struct Fuse;
+/// A FUSE session with the virtio-fs device, whose index among the
+/// filesystem drivers is `device`
+#[derive(Clone, Copy, Debug)]
+pub struct Fuse {
+	device: usize,
+}
 
 impl PosixFileSystem for Fuse {
 	fn open(&self, path: &str, perms: FilePerms) -> Result<Box<dyn PosixFile + Send>, FileError> {
+		check_path_len(path)?;
 		let mut file = FuseFile {
+			fs: *self,
 			fuse_nid: None,
 			fuse_fh: None,
 			offset: 0,
+			direct_io: perms.directio,
 		};
 		// 1.FUSE_INIT to create session
 		// Already done
@@ -34,31 +72,29 @@ impl PosixFileSystem for Fuse {
 		// Differentiate between opening and creating new file, since fuse does not support O_CREAT on open.
 		if !perms.creat {
 			// 2.FUSE_LOOKUP(FUSE_ROOT_ID, “foo”) -> nodeid
-			file.fuse_nid = self.lookup(path);
-
-			if file.fuse_nid == None {
-				warn!("Fuse lookup seems to have failed!");
-				return Err(FileError::ENOENT);
-			}
+			let nid = self.lookup(path)?;
+			file.fuse_nid = Some(nid);
 
 			// 3.FUSE_OPEN(nodeid, O_RDONLY) -> fh
-			let (cmd, rsp) = create_open(file.fuse_nid.unwrap(), perms.raw);
-			let rsp = get_filesystem_driver()
-				.ok_or(FileError::ENOSYS)?
+			let (cmd, rsp) = create_open(nid, perms.raw);
+			let rsp = self
+				.driver()?
 				.lock()
 				.send_command(cmd, Some(rsp))
-				.unwrap();
+				.ok_or(FileError::EIO)?;
 			trace!("Open answer {:?}", rsp);
+			check_error(&rsp.header)?;
 			file.fuse_fh = Some(rsp.rsp.fh);
 		} else {
 			// Create file (opens implicitly, returns results from both lookup and open calls)
 			let (cmd, rsp) = create_create(path, perms.raw, perms.mode);
-			let rsp = get_filesystem_driver()
-				.ok_or(FileError::ENOSYS)?
+			let rsp = self
+				.driver()?
 				.lock()
 				.send_command(cmd, Some(rsp))
-				.unwrap();
+				.ok_or(FileError::EIO)?;
 			trace!("Create answer {:?}", rsp);
+			check_error(&rsp.header)?;
 
 			file.fuse_nid = Some(rsp.rsp.entry.nodeid);
 			file.fuse_fh = Some(rsp.rsp.open.fh);
@@ -68,94 +104,275 @@ impl PosixFileSystem for Fuse {
 	}
 
 	fn unlink(&self, path: &str) -> core::result::Result<(), FileError> {
+		check_path_len(path)?;
 		let (cmd, rsp) = create_unlink(path);
-		let rsp = get_filesystem_driver()
-			.ok_or(FileError::ENOSYS)?
+		let rsp = self
+			.driver()?
 			.lock()
-			.send_command(cmd, Some(rsp));
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
 		trace!("unlink answer {:?}", rsp);
 
-		Ok(())
+		check_error(&rsp.header)
+	}
+
+	fn mkdir(&self, path: &str, mode: u32) -> Result<(), FileError> {
+		check_path_len(path)?;
+		let (cmd, rsp) = create_mkdir(path, mode);
+		let rsp = self
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+		trace!("mkdir answer {:?}", rsp);
+
+		check_error(&rsp.header)
+	}
+
+	fn rmdir(&self, path: &str) -> Result<(), FileError> {
+		check_path_len(path)?;
+		let (cmd, rsp) = create_rmdir(path);
+		let rsp = self
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+		trace!("rmdir answer {:?}", rsp);
+
+		check_error(&rsp.header)
+	}
+
+	fn opendir(&self, path: &str) -> Result<Box<dyn PosixFile + Send>, FileError> {
+		let nid = self.lookup_nid(path)?;
+
+		let (cmd, rsp) = create_opendir(nid);
+		let rsp = self
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+		trace!("opendir answer {:?}", rsp);
+		check_error(&rsp.header)?;
+
+		Ok(Box::new(FuseDir {
+			fs: *self,
+			fuse_nid: nid,
+			fuse_fh: rsp.rsp.fh,
+			offset: 0,
+			entries: VecDeque::new(),
+		}))
+	}
+
+	fn rename(&self, old_path: &str, new_path: &str) -> Result<(), FileError> {
+		let (cmd, rsp) = create_rename(old_path, new_path);
+		let rsp = self
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+		trace!("rename answer {:?}", rsp);
+
+		check_error(&rsp.header)
+	}
+
+	fn lstat(&self, path: &str) -> Result<FileAttr, FileError> {
+		let nid = self.lookup_nid(path)?;
+		self.getattr(nid, None)
+	}
+
+	fn stat(&self, path: &str) -> Result<FileAttr, FileError> {
+		let mut path = path.to_string();
+
+		for _ in 0..MAX_SYMLINK_DEPTH {
+			let nid = self.lookup_nid(&path)?;
+			let attr = self.getattr(nid, None)?;
+			if attr.st_mode & S_IFMT != S_IFLNK {
+				return Ok(attr);
+			}
+
+			let target = self.readlink(nid)?;
+			if target.starts_with('/') {
+				// absolute targets refer to the file system of the host
+				warn!("Unable to follow symbolic link {} to {}", path, target);
+				return Err(FileError::ENOENT);
+			}
+			path = match path.rfind('/') {
+				Some(pos) => format!("{}/{}", &path[..pos], target),
+				None => target,
+			};
+		}
+
+		Err(FileError::ELOOP)
+	}
+}
+
+/// Converts the error of a FUSE reply, which is a negative `errno` value.
+fn check_error(header: &fuse_out_header) -> Result<(), FileError> {
+	match -header.error {
+		0 => Ok(()),
+		error => Err(FileError::from_errno(error)),
 	}
 }
 
 impl Fuse {
-	pub fn new() -> Self {
-		Self {}
+	pub fn new(device: usize) -> Self {
+		Self { device }
 	}
 
-	pub fn send_init(&self) {
+	fn driver(&self) -> Result<&'static SpinlockIrqSave<VirtioFsDriver<'static>>, FileError> {
+		get_filesystem_driver(self.device).ok_or(FileError::ENODEV)
+	}
+
+	pub fn send_init(&self) -> Result<(), FileError> {
 		let (cmd, rsp) = create_init();
-		let rsp = get_filesystem_driver()
-			.unwrap()
+		let rsp = self
+			.driver()?
 			.lock()
-			.send_command(cmd, Some(rsp));
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
 		trace!("fuse init answer: {:?}", rsp);
+
+		check_error(&rsp.header)
 	}
 
-	pub fn lookup(&self, name: &str) -> Option<u64> {
+	pub fn lookup(&self, name: &str) -> Result<u64, FileError> {
+		check_path_len(name)?;
 		let (cmd, rsp) = create_lookup(name);
-		let rsp = get_filesystem_driver()
-			.unwrap()
+		let rsp = self
+			.driver()?
 			.lock()
-			.send_command(cmd, Some(rsp));
-		Some(rsp.unwrap().rsp.nodeid)
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+		check_error(&rsp.header)?;
+
+		Ok(rsp.rsp.nodeid)
+	}
+
+	/// Returns the node id of a path. The empty path is the root directory.
+	fn lookup_nid(&self, path: &str) -> Result<u64, FileError> {
+		if path.is_empty() {
+			Ok(FUSE_ROOT_ID)
+		} else {
+			self.lookup(path)
+		}
+	}
+
+	/// Queries the attributes of a node or, if `fh` is given, of an opened file.
+	fn getattr(&self, nid: u64, fh: Option<u64>) -> Result<FileAttr, FileError> {
+		let (cmd, rsp) = create_getattr(nid, fh);
+		let rsp = self
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+		trace!("getattr answer {:?}", rsp);
+		check_error(&rsp.header)?;
+
+		Ok(FileAttr::from(&rsp.rsp.attr))
+	}
+
+	/// Sends FUSE_FSYNC or FUSE_FSYNCDIR. As by Linux, a server, which does not
+	/// implement the request, is assumed to need no synchronization.
+	fn fsync(&self, nid: u64, fh: u64, datasync: bool, dir: bool) -> Result<(), FileError> {
+		let (cmd, rsp) = create_fsync(nid, fh, datasync, dir);
+		let rsp = self
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+
+		match check_error(&rsp.header) {
+			Err(FileError::ENOSYS) => Ok(()),
+			result => result,
+		}
 	}
-}
 
-impl Default for Fuse {
-	fn default() -> Self {
-		Self::new()
+	fn readlink(&self, nid: u64) -> Result<String, FileError> {
+		let (cmd, rsp) = create_readlink(nid, MAX_PATH_LEN as u32);
+		let rsp = self
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+		check_error(&rsp.header)?;
+
+		let len =
+			(rsp.header.len as usize).saturating_sub(::core::mem::size_of::<fuse_out_header>());
+		let buf = rsp.extra_buffer.as_deref().unwrap_or(&[]);
+		Ok(String::from_utf8_lossy(&buf[..len.min(buf.len())]).into_owned())
 	}
 }
 
 struct FuseFile {
+	fs: Fuse,
 	fuse_nid: Option<u64>,
 	fuse_fh: Option<u64>,
 	offset: usize,
+	/// Opened with `O_DIRECT`, so that the buffers of the requests have to be page-aligned
+	direct_io: bool,
 }
 
 impl PosixFile for FuseFile {
 	fn close(&mut self) -> Result<(), FileError> {
-		let (cmd, rsp) = create_release(self.fuse_nid.unwrap(), self.fuse_fh.unwrap());
-		get_filesystem_driver()
-			.ok_or(FileError::ENOSYS)?
+		let (nid, fh) = self.fuse_nid.zip(self.fuse_fh).ok_or(FileError::EBADF)?;
+		let (cmd, rsp) = create_release(nid, fh);
+		let rsp = self
+			.fs
+			.driver()?
 			.lock()
-			.send_command(cmd, Some(rsp));
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
 
-		Ok(())
+		check_error(&rsp.header)
 	}
 
 	fn read(&mut self, len: u32) -> Result<Vec<u8>, FileError> {
-		let mut len = len;
-		if len as usize > MAX_READ_LEN {
-			debug!("Reading longer than max_read_len: {}", len);
-			len = MAX_READ_LEN as u32;
+		let mut buf = vec![0; (len as usize).min(MAX_READ_LEN)];
+		let len = self.pread(&mut buf, self.offset as u64)?;
+		buf.truncate(len);
+		self.offset += len;
+		Ok(buf)
+	}
+
+	fn write(&mut self, buf: &[u8]) -> Result<u64, FileError> {
+		let len = self.pwrite(buf, self.offset as u64)?;
+		self.offset += len;
+		Ok(len as u64)
+	}
+
+	fn pread(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, FileError> {
+		let (nid, fh) = self.fuse_nid.zip(self.fuse_fh).ok_or(FileError::EBADF)?;
+		let len = buf.len().min(MAX_READ_LEN);
+		if len < buf.len() {
+			debug!("Reading longer than max_read_len: {}", buf.len());
 		}
-		if let Some(fh) = self.fuse_fh {
-			let (cmd, rsp) = create_read(fh, len, self.offset as u64);
-			let rsp = get_filesystem_driver()
-				.ok_or(FileError::ENOSYS)?
-				.lock()
-				.send_command(cmd, Some(rsp));
-			let rsp = rsp.unwrap();
-			let len = rsp.header.len as usize - ::core::mem::size_of::<fuse_out_header>();
-			self.offset += len;
-			// TODO: do this zerocopy
-			let mut vec = rsp.extra_buffer.unwrap();
-			vec.truncate(len);
-			trace!("LEN: {}, VEC: {:?}", len, vec);
-			Ok(vec)
+
+		let (cmd, mut rsp) = create_read(nid, fh, len as u32, offset);
+		let mut driver = self.fs.driver()?.lock();
+		let rsp = if self.direct_io {
+			rsp.extra_buffer = Some(aligned_buffer(len));
+			driver.send_command(cmd, Some(rsp))
 		} else {
-			warn!("File not open, cannot read!");
-			Err(FileError::ENOENT)
+			driver.send_command_into(cmd, rsp, &mut buf[..len])
+		}
+		.ok_or(FileError::EIO)?;
+		drop(driver);
+		check_error(&rsp.header)?;
+
+		let len = (rsp.header.len as usize)
+			.saturating_sub(::core::mem::size_of::<fuse_out_header>())
+			.min(len);
+		if let Some(data) = rsp.extra_buffer {
+			buf[..len].copy_from_slice(&data[..len]);
 		}
+		trace!("Read {} bytes at offset {}", len, offset);
+		Ok(len)
 	}
 
-	fn write(&mut self, buf: &[u8]) -> Result<u64, FileError> {
-		debug!("fuse write!");
+	fn pwrite(&mut self, buf: &[u8], offset: u64) -> Result<usize, FileError> {
+		let (nid, fh) = self.fuse_nid.zip(self.fuse_fh).ok_or(FileError::EBADF)?;
 		let mut len = buf.len();
-		if len as usize > MAX_WRITE_LEN {
+		if len > MAX_WRITE_LEN {
 			debug!(
 				"Writing longer than max_write_len: {} > {}",
 				buf.len(),
@@ -163,36 +380,201 @@ impl PosixFile for FuseFile {
 			);
 			len = MAX_WRITE_LEN;
 		}
-		if let Some(fh) = self.fuse_fh {
-			let (cmd, rsp) = create_write(fh, &buf[..len], self.offset as u64);
-			let rsp = get_filesystem_driver()
-				.ok_or(FileError::ENOSYS)?
-				.lock()
-				.send_command(cmd, Some(rsp));
-			trace!("write response: {:?}", rsp);
-			let rsp = rsp.unwrap();
-
-			let len = rsp.rsp.size as usize;
-			self.offset += len;
-			debug!("Written {} bytes", len);
-			Ok(len as u64)
-		} else {
-			warn!("File not open, cannot read!");
-			Err(FileError::ENOENT)
-		}
+
+		let (cmd, rsp) = create_write(nid, fh, &buf[..len], offset);
+		let rsp = self
+			.fs
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+		trace!("write response: {:?}", rsp);
+		check_error(&rsp.header)?;
+
+		let len = rsp.rsp.size as usize;
+		debug!("Written {} bytes at offset {}", len, offset);
+		Ok(len)
 	}
 
 	fn lseek(&mut self, offset: isize, whence: SeekWhence) -> Result<usize, FileError> {
 		debug!("fuse lseek");
 
-		match whence {
-			SeekWhence::Set => self.offset = offset as usize,
-			SeekWhence::Cur => self.offset = (self.offset as isize + offset) as usize,
-			SeekWhence::End => unimplemented!("Can't seek from end yet!"),
-		}
+		let base = match whence {
+			SeekWhence::Set => 0,
+			SeekWhence::Cur => self.offset as isize,
+			SeekWhence::End => self.fstat()?.st_size as isize,
+		};
+		let offset = base.checked_add(offset).ok_or(FileError::EOVERFLOW)?;
+		self.offset = offset.try_into().map_err(|_| FileError::EINVAL)?;
 
 		Ok(self.offset)
 	}
+
+	fn fstat(&mut self) -> Result<FileAttr, FileError> {
+		let nid = self.fuse_nid.ok_or(FileError::EBADF)?;
+		self.fs.getattr(nid, self.fuse_fh)
+	}
+
+	fn flush(&mut self) -> Result<(), FileError> {
+		let (nid, fh) = self.fuse_nid.zip(self.fuse_fh).ok_or(FileError::EBADF)?;
+		let (cmd, rsp) = create_flush(nid, fh);
+		let rsp = self
+			.fs
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+
+		match check_error(&rsp.header) {
+			Err(FileError::ENOSYS) => Ok(()),
+			result => result,
+		}
+	}
+
+	fn fsync(&mut self, datasync: bool) -> Result<(), FileError> {
+		let (nid, fh) = self.fuse_nid.zip(self.fuse_fh).ok_or(FileError::EBADF)?;
+		self.fs.fsync(nid, fh, datasync, false)
+	}
+
+	fn ftruncate(&mut self, size: u64) -> Result<(), FileError> {
+		let (nid, fh) = self.fuse_nid.zip(self.fuse_fh).ok_or(FileError::EBADF)?;
+		let (cmd, rsp) = create_truncate(nid, fh, size);
+		let rsp = self
+			.fs
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+		trace!("setattr answer {:?}", rsp);
+
+		check_error(&rsp.header)
+	}
+
+	fn fallocate(&mut self, mode: u32, offset: u64, len: u64) -> Result<(), FileError> {
+		let (nid, fh) = self.fuse_nid.zip(self.fuse_fh).ok_or(FileError::EBADF)?;
+		let (cmd, rsp) = create_fallocate(nid, fh, mode, offset, len);
+		let rsp = self
+			.fs
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+
+		match check_error(&rsp.header) {
+			Err(FileError::ENOSYS) => Err(FileError::EOPNOTSUPP),
+			result => result,
+		}
+	}
+}
+
+/// An opened directory, whose entries are fetched on demand by FUSE_READDIR
+struct FuseDir {
+	fs: Fuse,
+	fuse_nid: u64,
+	fuse_fh: u64,
+	/// Offset of the next entry, which is not yet fetched
+	offset: u64,
+	/// Fetched entries, which are not yet returned
+	entries: VecDeque<DirectoryEntry>,
+}
+
+impl PosixFile for FuseDir {
+	fn close(&mut self) -> Result<(), FileError> {
+		let (cmd, rsp) = create_releasedir(self.fuse_nid, self.fuse_fh);
+		let rsp = self
+			.fs
+			.driver()?
+			.lock()
+			.send_command(cmd, Some(rsp))
+			.ok_or(FileError::EIO)?;
+
+		check_error(&rsp.header)
+	}
+
+	fn read(&mut self, _len: u32) -> Result<Vec<u8>, FileError> {
+		Err(FileError::EISDIR)
+	}
+
+	fn write(&mut self, _buf: &[u8]) -> Result<u64, FileError> {
+		Err(FileError::EBADF)
+	}
+
+	fn lseek(&mut self, _offset: isize, _whence: SeekWhence) -> Result<usize, FileError> {
+		Err(FileError::EINVAL)
+	}
+
+	fn readdir(&mut self) -> Result<Option<DirectoryEntry>, FileError> {
+		if self.entries.is_empty() {
+			let (cmd, rsp) = create_readdir(self.fuse_nid, self.fuse_fh, READDIR_LEN, self.offset);
+			let rsp = self
+				.fs
+				.driver()?
+				.lock()
+				.send_command(cmd, Some(rsp))
+				.ok_or(FileError::EIO)?;
+			check_error(&rsp.header)?;
+
+			let len =
+				(rsp.header.len as usize).saturating_sub(::core::mem::size_of::<fuse_out_header>());
+			let buf = rsp.extra_buffer.as_deref().unwrap_or(&[]);
+			for (offset, entry) in parse_dirents(&buf[..len.min(buf.len())]) {
+				self.offset = offset;
+				self.entries.push_back(entry);
+			}
+		}
+
+		Ok(self.entries.pop_front())
+	}
+
+	fn fstat(&mut self) -> Result<FileAttr, FileError> {
+		self.fs.getattr(self.fuse_nid, None)
+	}
+
+	fn fsync(&mut self, datasync: bool) -> Result<(), FileError> {
+		self.fs.fsync(self.fuse_nid, self.fuse_fh, datasync, true)
+	}
+}
+
+/// Size of the fixed part of a `fuse_dirent`, which is followed by the name
+const FUSE_NAME_OFFSET: usize = 24;
+
+/// Decodes the `fuse_dirent` records of a FUSE_READDIR reply. Each entry is
+/// returned with the offset, at which the following entry starts.
+fn parse_dirents(buf: &[u8]) -> Vec<(u64, DirectoryEntry)> {
+	let read_u64 = |pos: usize| u64::from_ne_bytes(buf[pos..pos + 8].try_into().unwrap());
+	let read_u32 = |pos: usize| u32::from_ne_bytes(buf[pos..pos + 4].try_into().unwrap());
+
+	let mut entries = Vec::new();
+	let mut pos = 0;
+	while pos + FUSE_NAME_OFFSET <= buf.len() {
+		let ino = read_u64(pos);
+		let offset = read_u64(pos + 8);
+		let namelen = read_u32(pos + 16) as usize;
+		let typ = read_u32(pos + 20);
+		let name = match buf.get(pos + FUSE_NAME_OFFSET..pos + FUSE_NAME_OFFSET + namelen) {
+			Some(name) => String::from_utf8_lossy(name).into_owned(),
+			None => break,
+		};
+
+		entries.push((
+			offset,
+			DirectoryEntry {
+				inode: ino,
+				file_type: match typ {
+					4 => FileType::Directory,
+					8 => FileType::File,
+					10 => FileType::Symlink,
+					_ => FileType::Unknown,
+				},
+				name,
+			},
+		));
+
+		// entries are aligned to 8 bytes
+		pos += (FUSE_NAME_OFFSET + namelen + 7) & !7;
+	}
+
+	entries
 }
 
 #[repr(C)]
@@ -448,8 +830,17 @@ unsafe impl FuseIn for fuse_read_in {}
 pub struct fuse_read_out {}
 unsafe impl FuseOut for fuse_read_out {}
 
-pub fn create_read(nid: u64, size: u32, offset: u64) -> (Cmd<fuse_read_in>, Rsp<fuse_read_out>) {
+/// Creates a FUSE_READ request without a buffer for the payload of the reply,
+/// which is either passed to [`FuseInterface::send_command_into`] or has to
+/// be added as extra buffer of the reply.
+pub fn create_read(
+	nid: u64,
+	fh: u64,
+	size: u32,
+	offset: u64,
+) -> (Cmd<fuse_read_in>, Rsp<fuse_read_out>) {
 	let cmd = fuse_read_in {
+		fh,
 		offset,
 		size,
 		..Default::default()
@@ -458,21 +849,6 @@ pub fn create_read(nid: u64, size: u32, offset: u64) -> (Cmd<fuse_read_in>, Rsp<
 	cmdhdr.nodeid = nid;
 	let rsp = Default::default();
 	let rsphdr = Default::default();
-	// direct-io requires aligned memory.
-	// ugly hack from https://stackoverflow.com/questions/60180121/how-do-i-allocate-a-vecu8-that-is-aligned-to-the-size-of-the-cache-line
-	let mut aligned: Vec<AlignToPage> =
-		Vec::with_capacity(size as usize / ::core::mem::size_of::<AlignToPage>() + 1);
-	let ptr = aligned.as_mut_ptr();
-	let cap_units = aligned.capacity();
-	::core::mem::forget(aligned);
-	let readbuf = unsafe {
-		Vec::from_raw_parts(
-			ptr as *mut u8,
-			size as usize,
-			cap_units * ::core::mem::size_of::<AlignToPage>(),
-		)
-	};
-	// let readbuf = vec![0; size as usize];
 	(
 		Cmd {
 			cmd,
@@ -482,7 +858,7 @@ pub fn create_read(nid: u64, size: u32, offset: u64) -> (Cmd<fuse_read_in>, Rsp<
 		Rsp {
 			rsp,
 			header: rsphdr,
-			extra_buffer: Some(readbuf),
+			extra_buffer: None,
 		},
 	)
 }
@@ -510,15 +886,37 @@ unsafe impl FuseOut for fuse_write_out {}
 
 #[repr(C, align(4096))]
 struct AlignToPage([u8; 4096]);
+
+/// Returns a zeroed, page-aligned buffer of `len` bytes, as required by direct-io.
+fn aligned_buffer(len: usize) -> Vec<u8> {
+	// ugly hack from https://stackoverflow.com/questions/60180121/how-do-i-allocate-a-vecu8-that-is-aligned-to-the-size-of-the-cache-line
+	let mut aligned: Vec<AlignToPage> =
+		Vec::with_capacity(len / ::core::mem::size_of::<AlignToPage>() + 1);
+	let ptr = aligned.as_mut_ptr();
+	let cap_units = aligned.capacity();
+	::core::mem::forget(aligned);
+	let mut buf = unsafe {
+		Vec::from_raw_parts(
+			ptr as *mut u8,
+			0,
+			cap_units * ::core::mem::size_of::<AlignToPage>(),
+		)
+	};
+	buf.resize(len, 0);
+	buf
+}
+
 // TODO: do write zerocopy? currently does buf.to_vec()
 // problem: i cannot create owned type, since this would deallocate memory on drop. But memory belongs to userspace!
 //          Using references, i have to be careful of lifetimes!
 pub fn create_write(
 	nid: u64,
+	fh: u64,
 	buf: &[u8],
 	offset: u64,
 ) -> (Cmd<fuse_write_in>, Rsp<fuse_write_out>) {
 	let cmd = fuse_write_in {
+		fh,
 		offset,
 		size: buf.len() as u32,
 		..Default::default()
@@ -528,22 +926,8 @@ pub fn create_write(
 	let rsp = Default::default();
 	let rsphdr = Default::default();
 
-	//direct-io requires aligned memory.
-	// ugly hack from https://stackoverflow.com/questions/60180121/how-do-i-allocate-a-vecu8-that-is-aligned-to-the-size-of-the-cache-line
-	let mut aligned: Vec<AlignToPage> =
-		Vec::with_capacity(buf.len() / ::core::mem::size_of::<AlignToPage>() + 1);
-	let ptr = aligned.as_mut_ptr();
-	let cap_units = aligned.capacity();
-	::core::mem::forget(aligned);
-	let mut writebuf = unsafe {
-		Vec::from_raw_parts(
-			ptr as *mut u8,
-			buf.len(),
-			cap_units * ::core::mem::size_of::<AlignToPage>(),
-		)
-	};
-	writebuf.clone_from_slice(buf);
-	// let writebuf = buf.to_vec();
+	let mut writebuf = aligned_buffer(buf.len());
+	writebuf.copy_from_slice(buf);
 	(
 		Cmd {
 			cmd,
@@ -634,14 +1018,204 @@ pub fn create_release(nid: u64, fh: u64) -> (Cmd<fuse_release_in>, Rsp<fuse_rele
 	)
 }
 
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_flush_in {
+	pub fh: u64,
+	pub unused: u32,
+	pub padding: u32,
+	pub lock_owner: u64,
+}
+unsafe impl FuseIn for fuse_flush_in {}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_flush_out {}
+unsafe impl FuseOut for fuse_flush_out {}
+
+pub fn create_flush(nid: u64, fh: u64) -> (Cmd<fuse_flush_in>, Rsp<fuse_flush_out>) {
+	let cmd = fuse_flush_in {
+		fh,
+		..Default::default()
+	};
+	let mut cmdhdr = create_in_header::<fuse_flush_in>(Opcode::FUSE_FLUSH);
+	cmdhdr.nodeid = nid;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_fsync_in {
+	pub fh: u64,
+	pub fsync_flags: u32,
+	pub padding: u32,
+}
+unsafe impl FuseIn for fuse_fsync_in {}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_fsync_out {}
+unsafe impl FuseOut for fuse_fsync_out {}
+
+/// Creates a FUSE_FSYNC request or, for directories, a FUSE_FSYNCDIR request.
+pub fn create_fsync(
+	nid: u64,
+	fh: u64,
+	datasync: bool,
+	dir: bool,
+) -> (Cmd<fuse_fsync_in>, Rsp<fuse_fsync_out>) {
+	let cmd = fuse_fsync_in {
+		fh,
+		fsync_flags: if datasync { FUSE_FSYNC_FDATASYNC } else { 0 },
+		..Default::default()
+	};
+	let opcode = if dir {
+		Opcode::FUSE_FSYNCDIR
+	} else {
+		Opcode::FUSE_FSYNC
+	};
+	let mut cmdhdr = create_in_header::<fuse_fsync_in>(opcode);
+	cmdhdr.nodeid = nid;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_setattr_in {
+	pub valid: u32,
+	pub padding: u32,
+	pub fh: u64,
+	pub size: u64,
+	pub lock_owner: u64,
+	pub atime: u64,
+	pub mtime: u64,
+	pub ctime: u64,
+	pub atimensec: u32,
+	pub mtimensec: u32,
+	pub ctimensec: u32,
+	pub mode: u32,
+	pub unused4: u32,
+	pub uid: u32,
+	pub gid: u32,
+	pub unused5: u32,
+}
+unsafe impl FuseIn for fuse_setattr_in {}
+
+/// Creates a FUSE_SETATTR request, which sets the size of the opened file `fh`.
+pub fn create_truncate(nid: u64, fh: u64, size: u64) -> (Cmd<fuse_setattr_in>, Rsp<fuse_attr_out>) {
+	let cmd = fuse_setattr_in {
+		valid: FATTR_SIZE | FATTR_FH,
+		fh,
+		size,
+		..Default::default()
+	};
+	let mut cmdhdr = create_in_header::<fuse_setattr_in>(Opcode::FUSE_SETATTR);
+	cmdhdr.nodeid = nid;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_fallocate_in {
+	pub fh: u64,
+	pub offset: u64,
+	pub length: u64,
+	pub mode: u32,
+	pub padding: u32,
+}
+unsafe impl FuseIn for fuse_fallocate_in {}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_fallocate_out {}
+unsafe impl FuseOut for fuse_fallocate_out {}
+
+pub fn create_fallocate(
+	nid: u64,
+	fh: u64,
+	mode: u32,
+	offset: u64,
+	length: u64,
+) -> (Cmd<fuse_fallocate_in>, Rsp<fuse_fallocate_out>) {
+	let cmd = fuse_fallocate_in {
+		fh,
+		offset,
+		length,
+		mode,
+		..Default::default()
+	};
+	let mut cmdhdr = create_in_header::<fuse_fallocate_in>(Opcode::FUSE_FALLOCATE);
+	cmdhdr.nodeid = nid;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
 fn str_into_u8buf(s: &str, u8buf: &mut [u8]) {
-	// TODO: fix this hacky conversion..
-	for (i, c) in s.chars().enumerate() {
-		u8buf[i] = c as u8;
-		if i > u8buf.len() {
-			warn!("FUSE: Name too long!");
-			break;
-		}
+	// keep the terminating null byte
+	let len = s.len().min(u8buf.len() - 1);
+	if len < s.len() {
+		warn!("FUSE: Name too long!");
+	}
+	u8buf[..len].copy_from_slice(&s.as_bytes()[..len]);
+}
+
+/// Paths are sent with a terminating null byte in buffers of `MAX_PATH_LEN` bytes.
+fn check_path_len(path: &str) -> Result<(), FileError> {
+	if path.len() < MAX_PATH_LEN {
+		Ok(())
+	} else {
+		Err(FileError::ENAMETOOLONG)
 	}
 }
 
@@ -707,6 +1281,29 @@ pub struct fuse_attr {
 	pub padding: u32,
 }
 
+impl From<&fuse_attr> for FileAttr {
+	fn from(attr: &fuse_attr) -> Self {
+		Self {
+			st_ino: attr.ino,
+			st_nlink: attr.nlink.into(),
+			st_mode: attr.mode,
+			st_uid: attr.uid,
+			st_gid: attr.gid,
+			st_rdev: attr.rdev.into(),
+			st_size: attr.size as i64,
+			st_blksize: attr.blksize.into(),
+			st_blocks: attr.blocks as i64,
+			st_atime: attr.atime as i64,
+			st_atime_nsec: attr.atimensec.into(),
+			st_mtime: attr.mtime as i64,
+			st_mtime_nsec: attr.mtimensec.into(),
+			st_ctime: attr.ctime as i64,
+			st_ctime_nsec: attr.ctimensec.into(),
+			..Default::default()
+		}
+	}
+}
+
 #[repr(C)]
 pub struct fuse_unlink_in {
 	pub name: [u8; MAX_PATH_LEN],
@@ -818,3 +1415,284 @@ pub fn create_create(
 		},
 	)
 }
+
+#[repr(C)]
+pub struct fuse_mkdir_in {
+	pub mode: u32,
+	pub umask: u32,
+	pub name: [u8; MAX_PATH_LEN],
+}
+unsafe impl FuseIn for fuse_mkdir_in {}
+
+impl fmt::Debug for fuse_mkdir_in {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"fuse_mkdir_in {{ mode: {}, umask: {}, name: {:?} ...}}",
+			self.mode,
+			self.umask,
+			&self.name[..10]
+		)
+	}
+}
+
+pub fn create_mkdir(path: &str, mode: u32) -> (Cmd<fuse_mkdir_in>, Rsp<fuse_entry_out>) {
+	let cmd = fuse_mkdir_in {
+		mode,
+		umask: 0,
+		name: str_to_path(path),
+	};
+	let mut cmdhdr = create_in_header::<fuse_mkdir_in>(Opcode::FUSE_MKDIR);
+	cmdhdr.nodeid = FUSE_ROOT_ID;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
+#[repr(C)]
+pub struct fuse_rmdir_in {
+	pub name: [u8; MAX_PATH_LEN],
+}
+unsafe impl FuseIn for fuse_rmdir_in {}
+
+impl From<&str> for fuse_rmdir_in {
+	fn from(name: &str) -> Self {
+		Self {
+			name: str_to_path(name),
+		}
+	}
+}
+
+impl fmt::Debug for fuse_rmdir_in {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "fuse_rmdir_in {{ {:?} }}", &self.name[..])
+	}
+}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_rmdir_out {}
+unsafe impl FuseOut for fuse_rmdir_out {}
+
+pub fn create_rmdir(name: &str) -> (Cmd<fuse_rmdir_in>, Rsp<fuse_rmdir_out>) {
+	let cmd = name.into();
+	let mut cmdhdr = create_in_header::<fuse_rmdir_in>(Opcode::FUSE_RMDIR);
+	cmdhdr.nodeid = FUSE_ROOT_ID;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_rename_in {
+	pub newdir: u64,
+}
+unsafe impl FuseIn for fuse_rename_in {}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_rename_out {}
+unsafe impl FuseOut for fuse_rename_out {}
+
+/// Both names follow `fuse_rename_in` as NUL-terminated strings.
+pub fn create_rename(
+	old_path: &str,
+	new_path: &str,
+) -> (Cmd<fuse_rename_in>, Rsp<fuse_rename_out>) {
+	let cmd = fuse_rename_in {
+		newdir: FUSE_ROOT_ID,
+	};
+	let mut cmdhdr = create_in_header::<fuse_rename_in>(Opcode::FUSE_RENAME);
+	cmdhdr.nodeid = FUSE_ROOT_ID;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+
+	let mut names = Vec::with_capacity(old_path.len() + new_path.len() + 2);
+	names.extend_from_slice(old_path.as_bytes());
+	names.push(0);
+	names.extend_from_slice(new_path.as_bytes());
+	names.push(0);
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: Some(names),
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
+pub fn create_opendir(nid: u64) -> (Cmd<fuse_open_in>, Rsp<fuse_open_out>) {
+	let cmd = Default::default();
+	let mut cmdhdr = create_in_header::<fuse_open_in>(Opcode::FUSE_OPENDIR);
+	cmdhdr.nodeid = nid;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
+/// The reply contains `fuse_dirent` records in its extra buffer.
+pub fn create_readdir(
+	nid: u64,
+	fh: u64,
+	size: u32,
+	offset: u64,
+) -> (Cmd<fuse_read_in>, Rsp<fuse_read_out>) {
+	let cmd = fuse_read_in {
+		fh,
+		offset,
+		size,
+		..Default::default()
+	};
+	let mut cmdhdr = create_in_header::<fuse_read_in>(Opcode::FUSE_READDIR);
+	cmdhdr.nodeid = nid;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: Some(vec![0; size as usize]),
+		},
+	)
+}
+
+pub fn create_releasedir(nid: u64, fh: u64) -> (Cmd<fuse_release_in>, Rsp<fuse_release_out>) {
+	let cmd = fuse_release_in {
+		fh,
+		..Default::default()
+	};
+	let mut cmdhdr = create_in_header::<fuse_release_in>(Opcode::FUSE_RELEASEDIR);
+	cmdhdr.nodeid = nid;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_getattr_in {
+	pub getattr_flags: u32,
+	pub dummy: u32,
+	pub fh: u64,
+}
+unsafe impl FuseIn for fuse_getattr_in {}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_attr_out {
+	pub attr_valid: u64,
+	pub attr_valid_nsec: u32,
+	pub dummy: u32,
+	pub attr: fuse_attr,
+}
+unsafe impl FuseOut for fuse_attr_out {}
+
+pub fn create_getattr(nid: u64, fh: Option<u64>) -> (Cmd<fuse_getattr_in>, Rsp<fuse_attr_out>) {
+	let cmd = fuse_getattr_in {
+		getattr_flags: if fh.is_some() { FUSE_GETATTR_FH } else { 0 },
+		fh: fh.unwrap_or(0),
+		..Default::default()
+	};
+	let mut cmdhdr = create_in_header::<fuse_getattr_in>(Opcode::FUSE_GETATTR);
+	cmdhdr.nodeid = nid;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: None,
+		},
+	)
+}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_readlink_in {}
+unsafe impl FuseIn for fuse_readlink_in {}
+
+#[repr(C)]
+#[derive(Default, Debug)]
+pub struct fuse_readlink_out {}
+unsafe impl FuseOut for fuse_readlink_out {}
+
+/// The reply contains the target of the link in its extra buffer.
+pub fn create_readlink(nid: u64, size: u32) -> (Cmd<fuse_readlink_in>, Rsp<fuse_readlink_out>) {
+	let cmd = Default::default();
+	let mut cmdhdr = create_in_header::<fuse_readlink_in>(Opcode::FUSE_READLINK);
+	cmdhdr.nodeid = nid;
+	let rsp = Default::default();
+	let rsphdr = Default::default();
+	(
+		Cmd {
+			cmd,
+			header: cmdhdr,
+			extra_buffer: None,
+		},
+		Rsp {
+			rsp,
+			header: rsphdr,
+			extra_buffer: Some(vec![0; size as usize]),
+		},
+	)
+}
@@ -118,8 +118,18 @@ pub fn register_driver(drv: MmioDriver) {
 	}
 }
 
-pub fn get_network_driver() -> Option<&'static SpinlockIrqSave<dyn NetworkInterface>> {
-	unsafe { MMIO_DRIVERS.iter().find_map(|drv| drv.get_network_driver()) }
+/// Returns the drivers of all network interfaces in the order of their discovery.
+pub fn get_network_drivers() -> impl Iterator<Item = &'static SpinlockIrqSave<dyn NetworkInterface>>
+{
+	unsafe {
+		MMIO_DRIVERS
+			.iter()
+			.filter_map(|drv| drv.get_network_driver())
+	}
+}
+
+pub fn get_network_driver(index: usize) -> Option<&'static SpinlockIrqSave<dyn NetworkInterface>> {
+	get_network_drivers().nth(index)
 }
 
 pub fn init_drivers() {
@@ -146,18 +146,36 @@ pub fn register_driver(drv: PciDriver<'static>) {
 	}
 }
 
-pub fn get_network_driver() -> Option<&'static SpinlockIrqSave<dyn NetworkInterface>> {
-	unsafe { PCI_DRIVERS.iter().find_map(|drv| drv.get_network_driver()) }
+/// Returns the drivers of all network interfaces in the order of their discovery.
+pub fn get_network_drivers() -> impl Iterator<Item = &'static SpinlockIrqSave<dyn NetworkInterface>>
+{
+	unsafe {
+		PCI_DRIVERS
+			.iter()
+			.filter_map(|drv| drv.get_network_driver())
+	}
 }
 
-pub fn get_filesystem_driver() -> Option<&'static SpinlockIrqSave<VirtioFsDriver<'static>>> {
+pub fn get_network_driver(index: usize) -> Option<&'static SpinlockIrqSave<dyn NetworkInterface>> {
+	get_network_drivers().nth(index)
+}
+
+/// Returns the drivers of all virtio-fs devices in the order of their discovery.
+pub fn get_filesystem_drivers(
+) -> impl Iterator<Item = &'static SpinlockIrqSave<VirtioFsDriver<'static>>> {
 	unsafe {
 		PCI_DRIVERS
 			.iter()
-			.find_map(|drv| drv.get_filesystem_driver())
+			.filter_map(|drv| drv.get_filesystem_driver())
 	}
 }
 
+pub fn get_filesystem_driver(
+	index: usize,
+) -> Option<&'static SpinlockIrqSave<VirtioFsDriver<'static>>> {
+	get_filesystem_drivers().nth(index)
+}
+
 /// Reads all bar registers of specified device and returns vector of PciBar's containing addresses and sizes.
 fn parse_bars(bus: u8, device: u8, vendor_id: u16, device_id: u16) -> Vec<PciBar> {
 	let mut bar_idxs = 0..6;
@@ -398,7 +416,8 @@ impl fmt::Display for PciAdapter {
 			.unwrap_or(("Unknown Vendor", "Unknown Device"));
 
 		#[cfg(not(feature = "pci-ids"))]
-		let (class_name, vendor_name, device_name) = ("Unknown Class", "Unknown Vendor", "Unknown Device");
+		let (class_name, vendor_name, device_name) =
+			("Unknown Class", "Unknown Vendor", "Unknown Device");
 
 		// Output detailed readable information about this device.
 		write!(
@@ -472,8 +491,6 @@ pub fn init() {
 }
 
 pub fn init_drivers() {
-	let mut nic_available = false;
-
 	// virtio: 4.1.2 PCI Device Discovery
 	irqsave(|| {
 		for adapter in unsafe {
@@ -487,27 +504,23 @@ pub fn init_drivers() {
 			);
 
 			if let Ok(VirtioDriver::Network(drv)) = pci_virtio::init_device(adapter) {
-				nic_available = true;
 				register_driver(PciDriver::VirtioNet(SpinlockIrqSave::new(drv)))
 			}
 		}
 
-		// do we already found a network interface?
-		if !nic_available {
-			// Searching for Realtek RTL8139, which is supported by Qemu
-			for adapter in unsafe {
-				PCI_ADAPTERS.iter().filter(|x| {
-					x.vendor_id == 0x10ec && x.device_id >= 0x8138 && x.device_id <= 0x8139
-				})
-			} {
-				info!(
-					"Found Realtek network device with device id {:#x}",
-					adapter.device_id
-				);
+		// Searching for Realtek RTL8139, which is supported by Qemu
+		for adapter in unsafe {
+			PCI_ADAPTERS
+				.iter()
+				.filter(|x| x.vendor_id == 0x10ec && x.device_id >= 0x8138 && x.device_id <= 0x8139)
+		} {
+			info!(
+				"Found Realtek network device with device id {:#x}",
+				adapter.device_id
+			);
 
-				if let Ok(drv) = rtl8139::init_device(adapter) {
-					register_driver(PciDriver::RTL8139Net(SpinlockIrqSave::new(drv)))
-				}
+			if let Ok(drv) = rtl8139::init_device(adapter) {
+				register_driver(PciDriver::RTL8139Net(SpinlockIrqSave::new(drv)))
 			}
 		}
 	});
@@ -9,10 +9,29 @@ pub(crate) const VIRTIO_MAX_QUEUE_SIZE: u16 = 2048;
 #[cfg(not(feature = "pci"))]
 pub(crate) const VIRTIO_MAX_QUEUE_SIZE: u16 = 1024;
 
+/// Default length of a timeslice in microseconds
+pub(crate) const DEFAULT_TIMESLICE: u64 = 10_000;
+
 /// Default keep alive interval in milliseconds
 #[cfg(feature = "tcp")]
 pub(crate) const DEFAULT_KEEP_ALIVE_INTERVAL: u64 = 75000;
 
+/// Default size of the receive and send buffer of a TCP socket
+#[cfg(feature = "tcp")]
+pub(crate) const DEFAULT_TCP_BUFFER_SIZE: usize = 65535;
+
+/// Minimum size of the receive and send buffer of a TCP socket
+#[cfg(feature = "tcp")]
+pub(crate) const MIN_TCP_BUFFER_SIZE: usize = 1024;
+
+/// Maximum size of the receive and send buffer of a TCP socket
+#[cfg(feature = "tcp")]
+pub(crate) const MAX_TCP_BUFFER_SIZE: usize = 4_194_304;
+
+/// Maximum number of pending connections of a TCP listener
+#[cfg(feature = "tcp")]
+pub(crate) const MAX_LISTEN_BACKLOG: usize = 32;
+
 pub(crate) const HW_DESTRUCTIVE_INTERFERENCE_SIZE: usize = {
 	use core::ptr;
 	use crossbeam_utils::CachePadded;
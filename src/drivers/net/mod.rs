@@ -46,21 +46,15 @@ pub extern "x86-interrupt" fn network_irqhandler(_stack_frame: ExceptionStackFra
 	apic::eoi();
 
 	#[cfg(feature = "pci")]
-	let has_packet = match pci::get_network_driver() {
-		Some(driver) => driver.lock().handle_interrupt(),
-		_ => {
-			debug!("Unable to handle interrupt!");
-			false
-		}
-	};
+	let drivers = pci::get_network_drivers();
 	#[cfg(not(feature = "pci"))]
-	let has_packet = match mmio::get_network_driver() {
-		Some(driver) => driver.lock().handle_interrupt(),
-		_ => {
-			debug!("Unable to handle interrupt!");
-			false
-		}
-	};
+	let drivers = mmio::get_network_drivers();
+
+	// the interrupt line may be shared by several interfaces
+	let mut has_packet = false;
+	for driver in drivers {
+		has_packet |= driver.lock().handle_interrupt();
+	}
 
 	if has_packet {
 		// handle incoming packets
@@ -1,5 +1,6 @@
 use crate::arch::x86_64::kernel::fuse::{self, FuseInterface};
 use crate::arch::x86_64::kernel::pci;
+use crate::arch::x86_64::mm::paging::{BasePageSize, PageSize};
 use crate::drivers::virtio::depr::virtio::{
 	self, consts::*, virtio_pci_common_cfg, VirtioNotification, Virtq,
 };
@@ -163,6 +164,20 @@ impl VirtioFsDriver<'_> {
 	}
 }
 
+/// Splits `buf` at page boundaries, because every descriptor has to be
+/// physically contiguous, while consecutive pages of `buf` may not be.
+fn split_at_pages(mut buf: &mut [u8]) -> Vec<&mut [u8]> {
+	let mut chunks = Vec::new();
+	while !buf.is_empty() {
+		let page_offset = buf.as_ptr() as usize % BasePageSize::SIZE;
+		let len = (BasePageSize::SIZE - page_offset).min(buf.len());
+		let (chunk, rest) = core::mem::take(&mut buf).split_at_mut(len);
+		chunks.push(chunk);
+		buf = rest;
+	}
+	chunks
+}
+
 impl FuseInterface for VirtioFsDriver<'_> {
 	fn send_command<S, T>(
 		&mut self,
@@ -186,6 +201,25 @@ impl FuseInterface for VirtioFsDriver<'_> {
 		None
 	}
 
+	fn send_command_into<S, T>(
+		&mut self,
+		cmd: fuse::Cmd<S>,
+		mut rsp: fuse::Rsp<T>,
+		buf: &mut [u8],
+	) -> Option<fuse::Rsp<T>>
+	where
+		S: fuse::FuseIn + core::fmt::Debug,
+		T: fuse::FuseOut + core::fmt::Debug,
+	{
+		trace!("Sending Fuse Command: {:?}", cmd);
+		let vqueues = self.vqueues.as_mut()?;
+		let mut rsp_bufs = rsp.to_u8buf_mut();
+		rsp_bufs.extend(split_at_pages(buf));
+		vqueues[1].send_blocking(&cmd.to_u8buf(), Some(&rsp_bufs));
+		trace!("Got Fuse Reply: {:?}", rsp);
+		Some(rsp)
+	}
+
 	/* TODO: make TEST out of this!
 
 	pub fn send_hello(&mut self) {
@@ -311,18 +345,29 @@ pub fn create_virtiofs_driver(adapter: &pci::PciAdapter) -> Option<VirtioFsDrive
 	Some(drv)
 }
 
-pub fn init_fs() {
-	let drv = pci::get_filesystem_driver().expect("Unable to get access to the device driver");
+/// Starts a FUSE session with the virtio-fs device `index` and mounts it at
+/// `/<tag>`, where `tag` is the name, which is given by the device configuration.
+pub fn init_fs(index: usize) {
+	let drv = match pci::get_filesystem_driver(index) {
+		Some(drv) => drv,
+		None => {
+			warn!("Unable to get access to the device driver");
+			return;
+		}
+	};
 
-	// Instantiate global fuse object
-	let fuse = fuse::Fuse::new();
+	let fuse = fuse::Fuse::new(index);
 
 	// send FUSE_INIT to create session
-	fuse.send_init();
+	if let Err(err) = fuse.send_init() {
+		warn!("Unable to initialize the FUSE session: {:?}", err);
+		return;
+	}
 
 	let mut fs = fs::FILESYSTEM.lock();
 	let tag = drv.lock().device_cfg.tag.as_str();
 	info!("Mounting virtio-fs at /{}", tag);
-	fs.mount(tag, Box::new(fuse))
-		.expect("Mount failed. Duplicate tag?");
+	if fs.mount(tag, Box::new(fuse)).is_err() {
+		warn!("Unable to mount virtio-fs at /{}. Duplicate tag?", tag);
+	}
 }
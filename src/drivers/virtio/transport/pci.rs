@@ -1260,11 +1260,12 @@ pub fn init_device(adapter: &PciAdapter) -> Result<VirtioDriver, DriverError> {
 			// TODO: proper error handling on driver creation fail
 			match virtio_fs::create_virtiofs_driver(adapter) {
 				Some(virt_fs_drv) => {
+					// every device gets its own FUSE session and mount point
+					let index = kernel_pci::get_filesystem_drivers().count();
 					kernel_pci::register_driver(PciDriver::VirtioFs(SpinlockIrqSave::new(
 						virt_fs_drv,
 					)));
-					// initialize file system
-					virtio_fs::init_fs();
+					virtio_fs::init_fs(index);
 					Ok(VirtioDriver::FileSystem)
 				}
 				None => Err(DriverError::InitVirtioDevFail(VirtioError::Unknown)),
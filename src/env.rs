@@ -21,6 +21,13 @@ struct Cli {
 	#[allow(dead_code)]
 	image_path: Option<String>,
 	freq: Option<u16>,
+	timeslice: Option<u64>,
+	ip: Option<String>,
+	mask: Option<String>,
+	gateway: Option<String>,
+	dns_servers: Vec<String>,
+	ip6: Option<String>,
+	gateway6: Option<String>,
 	env_vars: Vec<String>,
 	args: Vec<String>,
 }
@@ -41,6 +48,13 @@ impl Default for Cli {
 	fn default() -> Self {
 		let mut image_path = None;
 		let mut freq = None;
+		let mut timeslice = None;
+		let mut ip = None;
+		let mut mask = None;
+		let mut gateway = None;
+		let mut dns_servers = Vec::new();
+		let mut ip6 = None;
+		let mut gateway6 = None;
 		let mut env_vars = Vec::new();
 		let mut args = Vec::new();
 
@@ -59,17 +73,34 @@ impl Default for Cli {
 					let s = expect_arg(words.next(), word.as_str());
 					freq = Some(s.parse().unwrap());
 				}
+				"-timeslice" => {
+					let s = expect_arg(words.next(), word.as_str());
+					timeslice = Some(s.parse().unwrap());
+				}
 				"-ip" => {
-					let ip = expect_arg(words.next(), word.as_str());
-					env_vars.push(format!("HERMIT_IP={ip}"));
+					let value = expect_arg(words.next(), word.as_str());
+					env_vars.push(format!("HERMIT_IP={value}"));
+					ip = Some(value);
 				}
 				"-mask" => {
-					let mask = expect_arg(words.next(), word.as_str());
-					env_vars.push(format!("HERMIT_MASK={mask}"));
+					let value = expect_arg(words.next(), word.as_str());
+					env_vars.push(format!("HERMIT_MASK={value}"));
+					mask = Some(value);
 				}
 				"-gateway" => {
-					let gateway = expect_arg(words.next(), word.as_str());
-					env_vars.push(format!("HERMIT_GATEWAY={gateway}"));
+					let value = expect_arg(words.next(), word.as_str());
+					env_vars.push(format!("HERMIT_GATEWAY={value}"));
+					gateway = Some(value);
+				}
+				"-ip6" => {
+					ip6 = Some(expect_arg(words.next(), word.as_str()));
+				}
+				"-gateway6" => {
+					gateway6 = Some(expect_arg(words.next(), word.as_str()));
+				}
+				"-dns" => {
+					let dns = expect_arg(words.next(), word.as_str());
+					dns_servers.push(dns);
 				}
 				"--" => args.extend(&mut words),
 				_ if image_path.is_none() => image_path = Some(word),
@@ -84,6 +115,13 @@ impl Default for Cli {
 		Self {
 			image_path,
 			freq,
+			timeslice,
+			ip,
+			mask,
+			gateway,
+			dns_servers,
+			ip6,
+			gateway6,
 			env_vars,
 			args,
 		}
@@ -95,6 +133,49 @@ pub fn freq() -> Option<u16> {
 	CLI.get().unwrap().freq
 }
 
+/// Length of a timeslice in microseconds if given through the -timeslice command-line parameter.
+///
+/// A value of 0 disables the preemption of tasks with the same priority.
+pub fn timeslice() -> Option<u64> {
+	CLI.get().unwrap().timeslice
+}
+
+/// IPv4 address given through the -ip command-line parameter.
+#[cfg(feature = "tcp")]
+pub fn ip() -> Option<&'static str> {
+	CLI.get().unwrap().ip.as_deref()
+}
+
+/// IPv4 netmask given through the -mask command-line parameter.
+#[cfg(feature = "tcp")]
+pub fn mask() -> Option<&'static str> {
+	CLI.get().unwrap().mask.as_deref()
+}
+
+/// IPv4 gateway given through the -gateway command-line parameter.
+#[cfg(feature = "tcp")]
+pub fn gateway() -> Option<&'static str> {
+	CLI.get().unwrap().gateway.as_deref()
+}
+
+/// Nameservers given through the -dns command-line parameter.
+#[cfg(feature = "tcp")]
+pub fn dns_servers() -> &'static [String] {
+	CLI.get().unwrap().dns_servers.as_slice()
+}
+
+/// IPv6 address and prefix length given through the -ip6 command-line parameter.
+#[cfg(feature = "tcp")]
+pub fn ip6() -> Option<&'static str> {
+	CLI.get().unwrap().ip6.as_deref()
+}
+
+/// IPv6 gateway given through the -gateway6 command-line parameter.
+#[cfg(feature = "tcp")]
+pub fn gateway6() -> Option<&'static str> {
+	CLI.get().unwrap().gateway6.as_deref()
+}
+
 pub fn vars() -> &'static [String] {
 	CLI.get().unwrap().env_vars.as_slice()
 }
@@ -1,21 +1,17 @@
 use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
 use core::slice;
-#[cfg(not(feature = "dhcpv4"))]
-use core::str::FromStr;
 
 use smoltcp::iface::{InterfaceBuilder, NeighborCache, Routes};
 #[cfg(feature = "trace")]
 use smoltcp::phy::Tracer;
 use smoltcp::phy::{self, Device, DeviceCapabilities};
-#[cfg(feature = "dhcpv4")]
-use smoltcp::socket::Dhcpv4Socket;
 use smoltcp::time::Instant;
-#[cfg(not(feature = "dhcpv4"))]
-use smoltcp::wire::IpAddress;
 use smoltcp::wire::{EthernetAddress, HardwareAddress, IpCidr, Ipv4Address};
 
-use crate::net::{NetworkInterface, NetworkState};
+use crate::env;
+use crate::net::{ipv6, Ipv4Config, NetworkInterface};
 use crate::syscalls::SYS;
 
 /// Data type to determine the mac address
@@ -23,130 +19,137 @@ use crate::syscalls::SYS;
 #[repr(C)]
 pub(crate) struct HermitNet {
 	pub mtu: u16,
+	/// Index of the network driver
+	pub index: usize,
 }
 
 impl HermitNet {
-	pub(crate) const fn new(mtu: u16) -> Self {
-		Self { mtu }
+	pub(crate) const fn new(mtu: u16, index: usize) -> Self {
+		Self { mtu, index }
+	}
+}
+
+/// Returns the static IPv4 configuration of the command-line parameters -ip, -mask
+/// and -gateway. Without DHCP support, the values of HERMIT_IP, HERMIT_MASK and
+/// HERMIT_GATEWAY at compile time and finally the defaults of QEMU's user network
+/// are used instead. The configuration applies only to the first interface.
+fn static_ipv4_config(index: usize) -> Option<Ipv4Config> {
+	if index != 0 {
+		return None;
+	}
+
+	let ip = env::ip();
+	#[cfg(not(feature = "dhcpv4"))]
+	let ip = ip.or(option_env!("HERMIT_IP")).or(Some("10.0.5.3"));
+	let ip = ip?;
+	let mask = env::mask()
+		.or(option_env!("HERMIT_MASK"))
+		.unwrap_or("255.255.255.0");
+	let gateway = env::gateway().or(option_env!("HERMIT_GATEWAY"));
+	#[cfg(not(feature = "dhcpv4"))]
+	let gateway = gateway.or(Some("10.0.5.1"));
+
+	match Ipv4Config::parse(ip, mask, gateway) {
+		Ok(config) => Some(config),
+		Err(_) => {
+			warn!("Ignore invalid IPv4 configuration {}/{}", ip, mask);
+			None
+		}
 	}
 }
 
 impl NetworkInterface<HermitNet> {
-	#[cfg(feature = "dhcpv4")]
-	pub(crate) fn create() -> NetworkState {
-		let mtu = match unsafe { SYS.get_mtu() } {
+	/// Creates the network interface of the driver with the given index.
+	pub(crate) fn create(index: usize) -> Option<Box<Self>> {
+		let mtu = match unsafe { SYS.get_mtu(index) } {
 			Ok(mtu) => mtu,
 			Err(_) => {
-				return NetworkState::InitializationFailed;
+				return None;
 			}
 		};
-		let device = HermitNet::new(mtu);
+		let device = HermitNet::new(mtu, index);
 		#[cfg(feature = "trace")]
 		let device = Tracer::new(device, |_timestamp, printer| {
 			trace!("{}", printer);
 		});
 
-		let mac: [u8; 6] = match unsafe { SYS.get_mac_address() } {
+		let mac: [u8; 6] = match unsafe { SYS.get_mac_address(index) } {
 			Ok(mac) => mac,
 			Err(_) => {
-				return NetworkState::InitializationFailed;
+				return None;
 			}
 		};
 
 		let neighbor_cache = NeighborCache::new(BTreeMap::new());
 		let ethernet_addr = EthernetAddress([mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]]);
 		let hardware_addr = HardwareAddress::Ethernet(ethernet_addr);
-		let ip_addrs = [IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0)];
-		let routes = Routes::new(BTreeMap::new());
+		let interface_id = ipv6::interface_identifier(&ethernet_addr);
+		let link_local = ipv6::link_local_address(&interface_id);
+		let (address6, gateway6) = ipv6::static_config(index);
+		let ip_addrs = [
+			IpCidr::new(Ipv4Address::UNSPECIFIED.into(), 0),
+			IpCidr::Ipv6(address6.unwrap_or(link_local)),
+			IpCidr::Ipv6(link_local),
+		];
+		let mut routes = Routes::new(BTreeMap::new());
+		if let Some(gateway6) = gateway6 {
+			routes.add_default_ipv6_route(gateway6).unwrap();
+		}
 
-		info!("MAC address {}", hardware_addr);
+		info!("Interface {}: MAC address {}", index, hardware_addr);
+		info!("Link-local address {}", link_local);
+		if let Some(address6) = address6 {
+			info!("Configure network interface with address {}", address6);
+		}
+		if let Some(gateway6) = gateway6 {
+			info!("Configure gateway with address {}", gateway6);
+		}
 		info!("MTU: {} bytes", mtu);
 
-		let dhcp = Dhcpv4Socket::new();
-
-		let mut iface = InterfaceBuilder::new(device, vec![])
+		let iface = InterfaceBuilder::new(device, vec![])
 			.hardware_addr(hardware_addr)
 			.neighbor_cache(neighbor_cache)
 			.ip_addrs(ip_addrs)
 			.routes(routes)
 			.finalize();
 
-		let dhcp_handle = iface.add_socket(dhcp);
-
-		NetworkState::Initialized(Box::new(Self { iface, dhcp_handle }))
-	}
-
-	#[cfg(not(feature = "dhcpv4"))]
-	pub(crate) fn create() -> NetworkState {
-		let mtu = match unsafe { SYS.get_mtu() } {
-			Ok(mtu) => mtu,
-			Err(_) => {
-				return NetworkState::InitializationFailed;
-			}
-		};
-		let device = HermitNet::new(mtu);
-		#[cfg(feature = "trace")]
-		let device = Tracer::new(device, |_timestamp, printer| {
-			trace!("{}", printer);
+		let mut nic = Box::new(Self {
+			iface,
+			#[cfg(feature = "dhcpv4")]
+			dhcp_handle: None,
+			ipv4_gateway: None,
+			ipv6_gateway: gateway6,
+			udp_peers: BTreeMap::new(),
+			tcp_options: BTreeMap::new(),
+			closing: Vec::new(),
+			dns_servers: Vec::new(),
+			listeners: BTreeMap::new(),
+			mac: ethernet_addr,
+			interface_id,
+			autoconf: None,
 		});
 
-		let mac: [u8; 6] = match unsafe { SYS.get_mac_address() } {
-			Ok(mac) => mac,
-			Err(_) => {
-				return NetworkState::InitializationFailed;
+		match static_ipv4_config(index) {
+			Some(config) => {
+				info!(
+					"Configure network interface with address {}",
+					config.address
+				);
+				if let Some(gateway) = config.gateway {
+					info!("Configure gateway with address {}", gateway);
+				}
+				nic.set_ipv4_config(config);
 			}
-		};
-
-		let myip =
-			Ipv4Address::from_str(core::option_env!("HERMIT_IP").unwrap_or("10.0.5.3")).unwrap();
-		let mygw = Ipv4Address::from_str(core::option_env!("HERMIT_GATEWAY").unwrap_or("10.0.5.1"))
-			.unwrap();
-		let mymask =
-			Ipv4Address::from_str(core::option_env!("HERMIT_MASK").unwrap_or("255.255.255.0"))
-				.unwrap();
-
-		// calculate the netmask length
-		// => count the number of contiguous 1 bits,
-		// starting at the most significant bit in the first octet
-		let mut prefix_len = (!mymask.as_bytes()[0]).trailing_zeros();
-		if prefix_len == 8 {
-			prefix_len += (!mymask.as_bytes()[1]).trailing_zeros();
-		}
-		if prefix_len == 16 {
-			prefix_len += (!mymask.as_bytes()[2]).trailing_zeros();
+			#[cfg(feature = "dhcpv4")]
+			None => nic.set_dhcp(true).unwrap(),
+			#[cfg(not(feature = "dhcpv4"))]
+			None => {}
 		}
-		if prefix_len == 24 {
-			prefix_len += (!mymask.as_bytes()[3]).trailing_zeros();
+		if address6.is_none() {
+			nic.start_autoconfiguration();
 		}
 
-		let neighbor_cache = NeighborCache::new(BTreeMap::new());
-		let ethernet_addr = EthernetAddress([mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]]);
-		let hardware_addr = HardwareAddress::Ethernet(ethernet_addr);
-		let ip_addrs = [IpCidr::new(
-			IpAddress::v4(
-				myip.as_bytes()[0],
-				myip.as_bytes()[1],
-				myip.as_bytes()[2],
-				myip.as_bytes()[3],
-			),
-			prefix_len.try_into().unwrap(),
-		)];
-		let mut routes = Routes::new(BTreeMap::new());
-		routes.add_default_ipv4_route(mygw).unwrap();
-
-		info!("MAC address {}", hardware_addr);
-		info!("Configure network interface with address {}", ip_addrs[0]);
-		info!("Configure gateway with address {}", mygw);
-		info!("MTU: {} bytes", mtu);
-
-		let iface = InterfaceBuilder::new(device, vec![])
-			.hardware_addr(hardware_addr)
-			.neighbor_cache(neighbor_cache)
-			.ip_addrs(ip_addrs)
-			.routes(routes)
-			.finalize();
-
-		NetworkState::Initialized(Box::new(Self { iface }))
+		Some(nic)
 	}
 }
 
@@ -161,15 +164,18 @@ impl<'a> Device<'a> for HermitNet {
 	}
 
 	fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
-		match unsafe { SYS.receive_rx_buffer() } {
-			Ok((buffer, handle)) => Some((RxToken::new(buffer, handle), TxToken::new())),
+		match unsafe { SYS.receive_rx_buffer(self.index) } {
+			Ok((buffer, handle)) => Some((
+				RxToken::new(buffer, handle, self.index),
+				TxToken::new(self.index),
+			)),
 			_ => None,
 		}
 	}
 
 	fn transmit(&'a mut self) -> Option<Self::TxToken> {
 		trace!("create TxToken to transfer data");
-		Some(TxToken::new())
+		Some(TxToken::new(self.index))
 	}
 }
 
@@ -177,11 +183,16 @@ impl<'a> Device<'a> for HermitNet {
 pub(crate) struct RxToken {
 	buffer: &'static mut [u8],
 	handle: usize,
+	index: usize,
 }
 
 impl RxToken {
-	pub(crate) fn new(buffer: &'static mut [u8], handle: usize) -> Self {
-		Self { buffer, handle }
+	pub(crate) fn new(buffer: &'static mut [u8], handle: usize, index: usize) -> Self {
+		Self {
+			buffer,
+			handle,
+			index,
+		}
 	}
 }
 
@@ -192,7 +203,7 @@ impl phy::RxToken for RxToken {
 		F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
 	{
 		let result = f(self.buffer);
-		if unsafe { SYS.rx_buffer_consumed(self.handle).is_ok() } {
+		if unsafe { SYS.rx_buffer_consumed(self.index, self.handle).is_ok() } {
 			result
 		} else {
 			Err(smoltcp::Error::Exhausted)
@@ -201,11 +212,13 @@ impl phy::RxToken for RxToken {
 }
 
 #[doc(hidden)]
-pub(crate) struct TxToken;
+pub(crate) struct TxToken {
+	index: usize,
+}
 
 impl TxToken {
-	pub(crate) fn new() -> Self {
-		Self {}
+	pub(crate) fn new(index: usize) -> Self {
+		Self { index }
 	}
 }
 
@@ -215,13 +228,13 @@ impl phy::TxToken for TxToken {
 		F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
 	{
 		let (tx_buffer, handle) = unsafe {
-			SYS.get_tx_buffer(len)
+			SYS.get_tx_buffer(self.index, len)
 				.map_err(|_| smoltcp::Error::Exhausted)?
 		};
 		let tx_slice: &'static mut [u8] = unsafe { slice::from_raw_parts_mut(tx_buffer, len) };
 		match f(tx_slice) {
 			Ok(result) => {
-				if unsafe { SYS.send_tx_buffer(handle, len).is_ok() } {
+				if unsafe { SYS.send_tx_buffer(self.index, handle, len).is_ok() } {
 					Ok(result)
 				} else {
 					Err(smoltcp::Error::Exhausted)
@@ -229,7 +242,7 @@ impl phy::TxToken for TxToken {
 			}
 			Err(e) => {
 				unsafe {
-					let _ = SYS.free_tx_buffer(handle);
+					let _ = SYS.free_tx_buffer(self.index, handle);
 				}
 				Err(e)
 			}
@@ -0,0 +1,358 @@
+//! A stub resolver, which sends A and AAAA queries over UDP to the
+//! nameservers given by the `-dns` command-line option or by DHCP.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use smoltcp::time::{Duration, Instant};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address, Ipv6Address};
+
+use crate::arch;
+use crate::env;
+use crate::errno::*;
+use crate::net::executor::block_on;
+use crate::net::{now, AsyncUdpSocket, NIC};
+use crate::synch::spinlock::Spinlock;
+
+const DNS_PORT: u16 = 53;
+/// Maximum size of a DNS message over UDP without EDNS
+const MAX_MESSAGE_SIZE: usize = 512;
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+const RCODE_NAME_ERROR: u8 = 3;
+/// Number of attempts per nameserver, before the next one is asked
+const RETRIES: usize = 3;
+/// Time in milliseconds to wait for the answers of one attempt
+const QUERY_TIMEOUT: u64 = 1000;
+/// Upper bound in seconds for caching a resolved name
+const MAX_CACHE_TTL: u32 = 300;
+/// Maximum number of cached names
+const MAX_CACHE_ENTRIES: usize = 64;
+
+struct CacheEntry {
+	addresses: Vec<IpAddress>,
+	expires: Instant,
+}
+
+static CACHE: Spinlock<BTreeMap<String, CacheEntry>> = Spinlock::new(BTreeMap::new());
+
+/// Decoded answer of a nameserver
+struct Response {
+	id: u16,
+	rcode: u8,
+	addresses: Vec<IpAddress>,
+	ttl: u32,
+}
+
+/// Resolves `name` into its IPv4 and IPv6 addresses.
+///
+/// Returns `ENOENT`, if the name does not exist or has no addresses and
+/// `ETIMEDOUT`, if no nameserver answered.
+pub(crate) fn resolve(name: &str) -> Result<Vec<IpAddress>, i32> {
+	if let Ok(address) = IpAddress::from_str(name) {
+		return Ok(vec![address]);
+	}
+
+	let name = name.trim_end_matches('.').to_ascii_lowercase();
+	if let Some(addresses) = lookup_cache(&name) {
+		return Ok(addresses);
+	}
+
+	// An invalid name is rejected before asking any nameserver.
+	encode_query(0, &name, TYPE_A)?;
+
+	let servers = nameservers();
+	if servers.is_empty() {
+		warn!("Unable to resolve {}: no nameserver is configured", name);
+		return Err(EADDRNOTAVAIL);
+	}
+
+	let (addresses, ttl) = query_nameservers(&name, &servers)?;
+
+	insert_cache(
+		name,
+		CacheEntry {
+			addresses: addresses.clone(),
+			expires: now() + Duration::from_secs(ttl.min(MAX_CACHE_TTL).into()),
+		},
+	);
+
+	Ok(addresses)
+}
+
+/// Inserts a resolved name into the cache. Expired entries are dropped and,
+/// if the cache is still full, the entry, which expires next, is evicted.
+fn insert_cache(name: String, entry: CacheEntry) {
+	let mut cache = CACHE.lock();
+	let current = now();
+	cache.retain(|_, entry| entry.expires > current);
+
+	if cache.len() >= MAX_CACHE_ENTRIES && !cache.contains_key(&name) {
+		let next_expiring = cache
+			.iter()
+			.min_by_key(|(_, entry)| entry.expires)
+			.map(|(name, _)| name.clone());
+		if let Some(next_expiring) = next_expiring {
+			cache.remove(&next_expiring);
+		}
+	}
+
+	cache.insert(name, entry);
+}
+
+fn lookup_cache(name: &str) -> Option<Vec<IpAddress>> {
+	let mut cache = CACHE.lock();
+	let entry = cache.get(name)?;
+	if entry.expires > now() {
+		Some(entry.addresses.clone())
+	} else {
+		cache.remove(name);
+		None
+	}
+}
+
+/// Returns the nameservers of the command line, followed by the ones of DHCP.
+fn nameservers() -> Vec<IpAddress> {
+	let mut servers: Vec<IpAddress> = env::dns_servers()
+		.iter()
+		.filter_map(|server| IpAddress::from_str(server).ok())
+		.collect();
+
+	for nic in NIC.lock().nics_mut().iter() {
+		servers.extend(nic.dns_servers.iter().copied());
+	}
+
+	servers
+}
+
+fn query_id() -> u16 {
+	arch::processor::generate_random_number32()
+		.unwrap_or_else(|| arch::processor::get_timer_ticks() as u32) as u16
+}
+
+/// Asks the nameservers one after another. Only the nonexistence of the
+/// name ends the lookup, other failures of a nameserver are skipped.
+fn query_nameservers(name: &str, servers: &[IpAddress]) -> Result<(Vec<IpAddress>, u32), i32> {
+	for server in servers {
+		match query_server(name, *server) {
+			Ok(Some(answer)) => return Ok(answer),
+			Ok(None) => {}
+			Err(ENOENT) => return Err(ENOENT),
+			Err(errno) => debug!("Unable to query nameserver {}: errno {}", server, errno),
+		}
+	}
+
+	Err(ETIMEDOUT)
+}
+
+/// Opens a socket on the interface, which reaches the nameserver `server`,
+/// and asks the nameserver for `name`.
+fn query_server(name: &str, server: IpAddress) -> Result<Option<(Vec<IpAddress>, u32)>, i32> {
+	let interface = NIC.lock().route(server)?;
+	let socket = AsyncUdpSocket::new(interface)?;
+	let result = socket
+		.bind(&[], 0)
+		.and_then(|_| query_nameserver(&socket, name, server));
+	let _ = socket.close();
+
+	result
+}
+
+/// Returns `None`, if the nameserver does not answer or fails to
+/// resolve the name, so that the next nameserver should be asked.
+fn query_nameserver(
+	socket: &AsyncUdpSocket,
+	name: &str,
+	server: IpAddress,
+) -> Result<Option<(Vec<IpAddress>, u32)>, i32> {
+	let queries = [TYPE_A, TYPE_AAAA];
+	let mut buffer = [0u8; MAX_MESSAGE_SIZE];
+	let endpoint = IpEndpoint::new(server, DNS_PORT);
+
+	for _ in 0..RETRIES {
+		let id = query_id();
+		for (i, qtype) in queries.iter().enumerate() {
+			let query = encode_query(id.wrapping_add(i as u16), name, *qtype)?;
+			block_on(socket.send_to_endpoint(&query, endpoint), None).map_err(|_| ETIMEDOUT)??;
+		}
+
+		let deadline = now() + Duration::from_millis(QUERY_TIMEOUT);
+		let mut answered = [false; 2];
+		let mut addresses = Vec::new();
+		let mut ttl = u32::MAX;
+		let mut name_error = false;
+
+		while !answered.iter().all(|answered| *answered) {
+			let current = now();
+			if current >= deadline {
+				break;
+			}
+
+			let (len, source) =
+				match block_on(socket.recv_from(&mut buffer), Some(deadline - current)) {
+					Ok(result) => result?,
+					// timeout
+					Err(()) => break,
+				};
+			if source != endpoint {
+				continue;
+			}
+
+			let response = match parse_response(&buffer[..len]) {
+				Some(response) => response,
+				None => continue,
+			};
+			let i = response.id.wrapping_sub(id) as usize;
+			if i >= answered.len() || answered[i] {
+				continue;
+			}
+			answered[i] = true;
+
+			match response.rcode {
+				0 => {
+					ttl = ttl.min(response.ttl);
+					addresses.extend(response.addresses);
+				}
+				RCODE_NAME_ERROR => name_error = true,
+				rcode => {
+					debug!("Nameserver {} failed with rcode {}", server, rcode);
+					return Ok(None);
+				}
+			}
+		}
+
+		if !addresses.is_empty() {
+			return Ok(Some((addresses, ttl)));
+		} else if name_error || answered.iter().all(|answered| *answered) {
+			return Err(ENOENT);
+		}
+	}
+
+	Ok(None)
+}
+
+fn encode_query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>, i32> {
+	if name.is_empty() || name.len() > 253 {
+		return Err(EINVAL);
+	}
+
+	let mut packet = Vec::with_capacity(name.len() + 18);
+	packet.extend_from_slice(&id.to_be_bytes());
+	// standard query, recursion desired
+	packet.extend_from_slice(&0x0100u16.to_be_bytes());
+	// one question, no answer, authority or additional records
+	packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]);
+	for label in name.split('.') {
+		if label.is_empty() || label.len() > 63 {
+			return Err(EINVAL);
+		}
+		packet.push(label.len() as u8);
+		packet.extend_from_slice(label.as_bytes());
+	}
+	packet.push(0);
+	packet.extend_from_slice(&qtype.to_be_bytes());
+	packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+	Ok(packet)
+}
+
+fn read_u16(packet: &[u8], pos: usize) -> Option<u16> {
+	Some(u16::from_be_bytes(
+		packet.get(pos..pos + 2)?.try_into().ok()?,
+	))
+}
+
+fn read_u32(packet: &[u8], pos: usize) -> Option<u32> {
+	Some(u32::from_be_bytes(
+		packet.get(pos..pos + 4)?.try_into().ok()?,
+	))
+}
+
+/// Returns the position behind the (possibly compressed) name at `pos`.
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+	loop {
+		let len = usize::from(*packet.get(pos)?);
+		if len == 0 {
+			return Some(pos + 1);
+		} else if len & 0xc0 == 0xc0 {
+			// a pointer always terminates the name
+			return Some(pos + 2);
+		}
+		pos += len + 1;
+	}
+}
+
+fn parse_response(packet: &[u8]) -> Option<Response> {
+	let id = read_u16(packet, 0)?;
+	let flags = read_u16(packet, 2)?;
+	if flags & 0x8000 == 0 {
+		// not a response
+		return None;
+	}
+	let qdcount = read_u16(packet, 4)?;
+	let ancount = read_u16(packet, 6)?;
+
+	let mut pos = 12;
+	for _ in 0..qdcount {
+		pos = skip_name(packet, pos)? + 4;
+	}
+
+	let mut addresses = Vec::new();
+	let mut ttl = u32::MAX;
+	for _ in 0..ancount {
+		pos = skip_name(packet, pos)?;
+		let rtype = read_u16(packet, pos)?;
+		let class = read_u16(packet, pos + 2)?;
+		let rttl = read_u32(packet, pos + 4)?;
+		let rdlength = usize::from(read_u16(packet, pos + 8)?);
+		pos += 10;
+		let rdata = packet.get(pos..pos + rdlength)?;
+		pos += rdlength;
+
+		if class != CLASS_IN {
+			continue;
+		}
+		match (rtype, rdlength) {
+			(TYPE_A, 4) => addresses.push(IpAddress::Ipv4(Ipv4Address::from_bytes(rdata))),
+			(TYPE_AAAA, 16) => addresses.push(IpAddress::Ipv6(Ipv6Address::from_bytes(rdata))),
+			// e.g. CNAME records, which precede the addresses
+			_ => continue,
+		}
+		ttl = ttl.min(rttl);
+	}
+
+	Some(Response {
+		id,
+		rcode: (flags & 0xf) as u8,
+		addresses,
+		ttl,
+	})
+}
+
+#[cfg(not(target_os = "none"))]
+#[test]
+fn encode_and_parse() {
+	let query = encode_query(0x1234, "example.com", TYPE_A).unwrap();
+	assert_eq!(&query[12..25], b"\x07example\x03com\x00");
+	assert!(encode_query(0, "invalid..name", TYPE_A).is_err());
+
+	// answer the query with a CNAME and an A record
+	let mut response = query.clone();
+	response[2] = 0x81;
+	response[3] = 0x80;
+	response[7] = 2;
+	response.extend_from_slice(b"\xc0\x0c\x00\x05\x00\x01\x00\x00\x00\x3c\x00\x02\xc0\x0c");
+	response.extend_from_slice(b"\xc0\x0c\x00\x01\x00\x01\x00\x00\x00\x1e\x00\x04\x5d\xb8\xd8\x22");
+
+	let response = parse_response(&response).unwrap();
+	assert_eq!(response.id, 0x1234);
+	assert_eq!(response.rcode, 0);
+	assert_eq!(response.ttl, 30);
+	assert_eq!(
+		response.addresses,
+		[IpAddress::v4(93, 184, 216, 34)].as_slice()
+	);
+}
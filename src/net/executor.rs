@@ -4,6 +4,7 @@ use alloc::vec::Vec;
 use async_task::{Runnable, Task};
 use core::sync::atomic::Ordering;
 use core::{
+	cmp,
 	future::Future,
 	sync::atomic::AtomicBool,
 	task::{Context, Poll},
@@ -19,11 +20,7 @@ static QUEUE: Spinlock<Vec<Runnable>> = Spinlock::new(Vec::new());
 
 #[inline]
 fn network_delay(timestamp: Instant) -> Option<Duration> {
-	crate::net::NIC
-		.lock()
-		.as_nic_mut()
-		.unwrap()
-		.poll_delay(timestamp)
+	crate::net::NIC.lock().poll_delay(timestamp)
 }
 
 fn run_executor_once() {
@@ -97,7 +94,10 @@ where
 	// Polling mode => no NIC interrupts => NIC thread should not run
 	set_polling_mode(true);
 
-	let start = crate::net::now();
+	// The deadline is given in timer ticks, so that it is able to limit
+	// the wakeup time of the blocked task.
+	let deadline =
+		timeout.map(|duration| crate::arch::processor::get_timer_ticks() + duration.total_micros());
 	let task_notify = Arc::new(TaskNotify::new());
 	let waker = task_notify.clone().into();
 	let mut cx = Context::from_waker(&waker);
@@ -118,8 +118,8 @@ where
 			return Ok(t);
 		}
 
-		if let Some(duration) = timeout {
-			if crate::net::now() >= start + duration {
+		if let Some(deadline) = deadline {
+			if crate::arch::processor::get_timer_ticks() >= deadline {
 				if let Some(delay) = network_delay(crate::net::now()).map(|d| d.total_micros()) {
 					let wakeup_time = crate::arch::processor::get_timer_ticks() + delay;
 					core_scheduler().add_network_timer(wakeup_time);
@@ -137,7 +137,14 @@ where
 			let unparked = task_notify.unparked.swap(false, Ordering::AcqRel);
 			if !unparked {
 				let core_scheduler = core_scheduler();
-				let wakeup_time = delay.map(|us| crate::arch::processor::get_timer_ticks() + us);
+				// Wake up for the next network poll, but not after the deadline.
+				let wakeup_time = match (
+					delay.map(|us| crate::arch::processor::get_timer_ticks() + us),
+					deadline,
+				) {
+					(Some(wakeup_time), Some(deadline)) => Some(cmp::min(wakeup_time, deadline)),
+					(wakeup_time, deadline) => wakeup_time.or(deadline),
+				};
 				core_scheduler.block_current_task(wakeup_time);
 				// allow interrupts => NIC thread is able to run
 				set_polling_mode(false);
@@ -161,7 +168,7 @@ fn set_polling_mode(value: bool) {
 
 		if *guard == 1 {
 			#[cfg(feature = "pci")]
-			if let Some(driver) = crate::arch::kernel::pci::get_network_driver() {
+			for driver in crate::arch::kernel::pci::get_network_drivers() {
 				driver.lock().set_polling_mode(value)
 			}
 		}
@@ -170,7 +177,7 @@ fn set_polling_mode(value: bool) {
 
 		if *guard == 0 {
 			#[cfg(feature = "pci")]
-			if let Some(driver) = crate::arch::kernel::pci::get_network_driver() {
+			for driver in crate::arch::kernel::pci::get_network_drivers() {
 				driver.lock().set_polling_mode(value)
 			}
 		}
@@ -0,0 +1,285 @@
+//! IPv6 configuration of the network interface: the link-local address,
+//! static addresses and stateless address autoconfiguration (RFC 4862).
+
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+use smoltcp::wire::{EthernetAddress, Ipv6Address, Ipv6Cidr};
+
+use crate::env;
+
+/// Position of the preferred IPv6 address in the address list of the interface.
+///
+/// smoltcp uses the first address of a protocol version as source address.
+/// Therefore, this slot holds the link-local address, until a global address is known.
+pub(crate) const ADDRESS_SLOT: usize = 1;
+/// Position of the link-local address. After its preferred lifetime, the
+/// autoconfigured address moves to this slot, so that it is valid for the
+/// existing connections, but isn't used as source of new ones.
+pub(crate) const LINK_LOCAL_SLOT: usize = 2;
+/// Maximum number of router solicitations (RFC 4861, section 10)
+pub(crate) const MAX_RTR_SOLICITATIONS: u8 = 3;
+/// Time in seconds between two router solicitations (RFC 4861, section 10)
+pub(crate) const RTR_SOLICITATION_INTERVAL: u64 = 4;
+/// Lifetime, which never ends
+pub(crate) const INFINITE_LIFETIME: u32 = u32::MAX;
+/// Prefix length of addresses, which are derived from the interface identifier
+const AUTOCONF_PREFIX_LEN: u8 = 64;
+const IPV6_HEADER_LEN: usize = 40;
+const NEXT_HEADER_ICMPV6: u8 = 58;
+/// Hop limit of all neighbor discovery messages
+const NDISC_HOP_LIMIT: u8 = 255;
+const ICMPV6_ROUTER_SOLICIT: u8 = 133;
+const ICMPV6_ROUTER_ADVERT: u8 = 134;
+const NDISC_OPTION_SOURCE_LLADDR: u8 = 1;
+const NDISC_OPTION_PREFIX_INFO: u8 = 3;
+/// Flag of the prefix information, which permits autoconfiguration
+const PREFIX_FLAG_AUTONOMOUS: u8 = 0x40;
+
+/// Content of a router advertisement, which is used for autoconfiguration
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct RouterAdvertisement {
+	/// Link-local address of the router, if it offers to be the default gateway
+	pub router: Option<Ipv6Address>,
+	/// Prefixes, which permit autoconfiguration
+	pub prefixes: Vec<PrefixInformation>,
+}
+
+/// Prefix of a router advertisement with its lifetimes in seconds
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct PrefixInformation {
+	pub prefix: Ipv6Cidr,
+	pub valid_lifetime: u32,
+	pub preferred_lifetime: u32,
+}
+
+/// Returns the modified EUI-64 interface identifier of `mac` (RFC 4291, appendix A).
+pub(crate) fn interface_identifier(mac: &EthernetAddress) -> [u8; 8] {
+	let mac = mac.as_bytes();
+	[
+		mac[0] ^ 0x02,
+		mac[1],
+		mac[2],
+		0xff,
+		0xfe,
+		mac[3],
+		mac[4],
+		mac[5],
+	]
+}
+
+/// Combines the first 64 bits of `prefix` with the interface identifier.
+pub(crate) fn autoconfigured_address(prefix: &Ipv6Cidr, id: &[u8; 8]) -> Ipv6Cidr {
+	let mut bytes = [0u8; 16];
+	bytes[..8].copy_from_slice(&prefix.address().as_bytes()[..8]);
+	bytes[8..].copy_from_slice(id);
+	Ipv6Cidr::new(Ipv6Address::from_bytes(&bytes), AUTOCONF_PREFIX_LEN)
+}
+
+pub(crate) fn link_local_address(id: &[u8; 8]) -> Ipv6Cidr {
+	let prefix = Ipv6Cidr::new(Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 64);
+	autoconfigured_address(&prefix, id)
+}
+
+/// Returns the static address and gateway, which are given by the command-line
+/// parameters -ip6 and -gateway6 or at compile time by HERMIT_IP6 and HERMIT_GATEWAY6.
+/// The configuration applies only to the first interface.
+pub(crate) fn static_config(index: usize) -> (Option<Ipv6Cidr>, Option<Ipv6Address>) {
+	if index != 0 {
+		return (None, None);
+	}
+
+	let address = env::ip6()
+		.or(option_env!("HERMIT_IP6"))
+		.and_then(|address| {
+			let cidr = Ipv6Cidr::from_str(address).ok();
+			if cidr.is_none() {
+				warn!("Ignore invalid IPv6 address {}", address);
+			}
+			cidr
+		});
+	let gateway = env::gateway6()
+		.or(option_env!("HERMIT_GATEWAY6"))
+		.and_then(|gateway| {
+			let address = Ipv6Address::from_str(gateway).ok();
+			if address.is_none() {
+				warn!("Ignore invalid IPv6 gateway {}", gateway);
+			}
+			address
+		});
+
+	(address, gateway)
+}
+
+fn checksum(source: &Ipv6Address, destination: &Ipv6Address, message: &[u8]) -> u16 {
+	let mut sum = 0u32;
+	let mut add = |data: &[u8]| {
+		for chunk in data.chunks(2) {
+			let high = u32::from(chunk[0]) << 8;
+			sum += high | chunk.get(1).copied().map(u32::from).unwrap_or(0);
+		}
+	};
+
+	// pseudo header
+	add(source.as_bytes());
+	add(destination.as_bytes());
+	add(&(message.len() as u32).to_be_bytes());
+	add(&[0, 0, 0, NEXT_HEADER_ICMPV6]);
+	add(message);
+
+	while sum >> 16 != 0 {
+		sum = (sum & 0xffff) + (sum >> 16);
+	}
+	!(sum as u16)
+}
+
+/// Builds a router solicitation including its IPv6 header, which is
+/// expected by a raw socket.
+pub(crate) fn router_solicitation(source: &Ipv6Address, mac: &EthernetAddress) -> Vec<u8> {
+	// all-routers multicast address
+	let destination = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+
+	let mut message = [0u8; 16];
+	message[0] = ICMPV6_ROUTER_SOLICIT;
+	message[8] = NDISC_OPTION_SOURCE_LLADDR;
+	// length in units of 8 bytes
+	message[9] = 1;
+	message[10..].copy_from_slice(mac.as_bytes());
+	let checksum = checksum(source, &destination, &message);
+	message[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+	let mut packet = Vec::with_capacity(IPV6_HEADER_LEN + message.len());
+	packet.extend_from_slice(&[0x60, 0, 0, 0]);
+	packet.extend_from_slice(&(message.len() as u16).to_be_bytes());
+	packet.push(NEXT_HEADER_ICMPV6);
+	packet.push(NDISC_HOP_LIMIT);
+	packet.extend_from_slice(source.as_bytes());
+	packet.extend_from_slice(destination.as_bytes());
+	packet.extend_from_slice(&message);
+	packet
+}
+
+/// Parses an IPv6 packet, as it is received by a raw socket, and
+/// returns `None`, if it is not a valid router advertisement.
+pub(crate) fn parse_router_advertisement(packet: &[u8]) -> Option<RouterAdvertisement> {
+	let header = packet.get(..IPV6_HEADER_LEN)?;
+	if header[0] >> 4 != 6 || header[6] != NEXT_HEADER_ICMPV6 || header[7] != NDISC_HOP_LIMIT {
+		return None;
+	}
+	let payload_len = usize::from(u16::from_be_bytes([header[4], header[5]]));
+	let source = Ipv6Address::from_bytes(&header[8..24]);
+	let destination = Ipv6Address::from_bytes(&header[24..40]);
+	if !source.is_link_local() {
+		return None;
+	}
+
+	let message = packet.get(IPV6_HEADER_LEN..IPV6_HEADER_LEN + payload_len)?;
+	if message.len() < 16
+		|| message[0] != ICMPV6_ROUTER_ADVERT
+		|| message[1] != 0
+		|| checksum(&source, &destination, message) != 0
+	{
+		return None;
+	}
+	let router_lifetime = u16::from_be_bytes([message[6], message[7]]);
+
+	let mut prefixes = Vec::new();
+	let mut options = &message[16..];
+	while options.len() >= 2 {
+		let len = usize::from(options[1]) * 8;
+		if len == 0 || len > options.len() {
+			return None;
+		}
+		let (option, rest) = options.split_at(len);
+		options = rest;
+
+		if option[0] != NDISC_OPTION_PREFIX_INFO || len != 32 {
+			continue;
+		}
+		let prefix_len = option[2];
+		let flags = option[3];
+		let valid_lifetime = u32::from_be_bytes([option[4], option[5], option[6], option[7]]);
+		let preferred_lifetime = u32::from_be_bytes([option[8], option[9], option[10], option[11]]);
+		// a preferred lifetime beyond the valid lifetime is invalid (RFC 4862, section 5.5.3)
+		if flags & PREFIX_FLAG_AUTONOMOUS != 0
+			&& prefix_len == AUTOCONF_PREFIX_LEN
+			&& valid_lifetime != 0
+			&& preferred_lifetime <= valid_lifetime
+		{
+			let prefix = Ipv6Address::from_bytes(&option[16..32]);
+			prefixes.push(PrefixInformation {
+				prefix: Ipv6Cidr::new(prefix, prefix_len),
+				valid_lifetime,
+				preferred_lifetime,
+			});
+		}
+	}
+
+	Some(RouterAdvertisement {
+		router: (router_lifetime != 0).then(|| source),
+		prefixes,
+	})
+}
+
+#[cfg(not(target_os = "none"))]
+#[test]
+fn router_discovery() {
+	let mac = EthernetAddress([0x52, 0x54, 0x00, 0x12, 0x34, 0x56]);
+	let id = interface_identifier(&mac);
+	let link_local = link_local_address(&id);
+	assert_eq!(
+		link_local.address(),
+		Ipv6Address::new(0xfe80, 0, 0, 0, 0x5054, 0x00ff, 0xfe12, 0x3456)
+	);
+
+	let solicitation = router_solicitation(&link_local.address(), &mac);
+	assert_eq!(solicitation.len(), IPV6_HEADER_LEN + 16);
+	let destination = Ipv6Address::from_bytes(&solicitation[24..40]);
+	assert_eq!(
+		checksum(
+			&link_local.address(),
+			&destination,
+			&solicitation[IPV6_HEADER_LEN..]
+		),
+		0
+	);
+
+	// a router advertisement of fe80::1 with the prefix 2001:db8:1::/64
+	let router = Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1);
+	let all_nodes = Ipv6Address::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+	let mut message = vec![ICMPV6_ROUTER_ADVERT, 0, 0, 0, 64, 0, 0x07, 0x08];
+	message.extend_from_slice(&[0; 8]);
+	message.extend_from_slice(&[NDISC_OPTION_PREFIX_INFO, 4, 64, 0xc0]);
+	message.extend_from_slice(&[0, 0, 0x0e, 0x10, 0, 0, 0x07, 0x08, 0, 0, 0, 0]);
+	message.extend_from_slice(Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 0).as_bytes());
+	let checksum = checksum(&router, &all_nodes, &message);
+	message[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+	let mut packet = vec![
+		0x60,
+		0,
+		0,
+		0,
+		0,
+		message.len() as u8,
+		NEXT_HEADER_ICMPV6,
+		255,
+	];
+	packet.extend_from_slice(router.as_bytes());
+	packet.extend_from_slice(all_nodes.as_bytes());
+	packet.extend_from_slice(&message);
+
+	let advertisement = parse_router_advertisement(&packet).unwrap();
+	assert_eq!(advertisement.router, Some(router));
+	assert_eq!(advertisement.prefixes.len(), 1);
+	assert_eq!(advertisement.prefixes[0].valid_lifetime, 3600);
+	assert_eq!(advertisement.prefixes[0].preferred_lifetime, 1800);
+	assert_eq!(
+		autoconfigured_address(&advertisement.prefixes[0].prefix, &id).address(),
+		Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0x5054, 0x00ff, 0xfe12, 0x3456)
+	);
+
+	// a corrupted checksum
+	packet[IPV6_HEADER_LEN + 2] ^= 0xff;
+	assert!(parse_router_advertisement(&packet).is_none());
+}
@@ -1,10 +1,14 @@
 mod device;
+pub(crate) mod dns;
 pub(crate) mod executor;
+mod ipv6;
 
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
 use core::ops::DerefMut;
 use core::str::FromStr;
-use core::task::Poll;
+use core::task::{Poll, Waker};
 
 use futures_lite::future;
 use smoltcp::iface::{self, SocketHandle};
@@ -13,39 +17,207 @@ use smoltcp::phy::Device;
 use smoltcp::phy::Tracer;
 #[cfg(feature = "dhcpv4")]
 use smoltcp::socket::{Dhcpv4Event, Dhcpv4Socket};
-use smoltcp::socket::{TcpSocket, TcpSocketBuffer, TcpState};
+use smoltcp::socket::{
+	RawPacketMetadata, RawSocket, RawSocketBuffer, Socket, TcpSocket, TcpSocketBuffer, TcpState,
+	UdpPacketMetadata, UdpSocket, UdpSocketBuffer,
+};
 use smoltcp::time::{Duration, Instant};
-use smoltcp::wire::IpAddress;
-#[cfg(feature = "dhcpv4")]
-use smoltcp::wire::{IpCidr, Ipv4Address, Ipv4Cidr};
+use smoltcp::wire::{
+	EthernetAddress, IpAddress, IpCidr, IpEndpoint, IpProtocol, IpVersion, Ipv4Address, Ipv4Cidr,
+	Ipv6Address, Ipv6Cidr,
+};
 use smoltcp::Error;
 
-use core::sync::atomic::{AtomicU16, Ordering};
+use core::sync::atomic::{AtomicU16, AtomicUsize, Ordering};
 
 use crate::arch;
+use crate::errno::*;
 use crate::net::device::HermitNet;
 use crate::net::executor::spawn;
 use crate::synch::spinlock::SpinlockIrqSave;
-use crate::DEFAULT_KEEP_ALIVE_INTERVAL;
+use crate::syscalls::SYS;
+use crate::{
+	DEFAULT_KEEP_ALIVE_INTERVAL, DEFAULT_TCP_BUFFER_SIZE, MAX_LISTEN_BACKLOG, MAX_TCP_BUFFER_SIZE,
+	MIN_TCP_BUFFER_SIZE,
+};
 
 pub(crate) enum NetworkState {
 	Missing,
 	InitializationFailed,
-	Initialized(Box<NetworkInterface<HermitNet>>),
+	/// One interface per network driver, in the order of their discovery
+	Initialized(Vec<Box<NetworkInterface<HermitNet>>>),
 }
 
 impl NetworkState {
-	pub fn as_nic_mut(&mut self) -> Result<&mut NetworkInterface<HermitNet>, &'static str> {
+	/// Returns the interface with the given index.
+	pub(crate) fn nic_mut(
+		&mut self,
+		interface: usize,
+	) -> Result<&mut NetworkInterface<HermitNet>, i32> {
 		match self {
-			NetworkState::Initialized(nic) => Ok(nic),
-			_ => Err("Network is not initialized!"),
+			NetworkState::Initialized(nics) => nics
+				.get_mut(interface)
+				.map(|nic| nic.as_mut())
+				.ok_or(ENODEV),
+			_ => Err(ENETDOWN),
 		}
 	}
+
+	pub(crate) fn nics_mut(&mut self) -> &mut [Box<NetworkInterface<HermitNet>>] {
+		match self {
+			NetworkState::Initialized(nics) => nics,
+			_ => &mut [],
+		}
+	}
+
+	/// Polls all interfaces and returns the time until the next poll is required.
+	pub(crate) fn poll(&mut self, timestamp: Instant) -> Option<Duration> {
+		self.nics_mut()
+			.iter_mut()
+			.filter_map(|nic| {
+				nic.poll_common(timestamp);
+				nic.poll_delay(timestamp)
+			})
+			.min()
+	}
+
+	pub(crate) fn poll_delay(&mut self, timestamp: Instant) -> Option<Duration> {
+		self.nics_mut()
+			.iter_mut()
+			.filter_map(|nic| nic.poll_delay(timestamp))
+			.min()
+	}
+
+	/// Selects the interface for packets to `destination`. An interface, whose
+	/// subnet contains the destination, is preferred over an interface with a
+	/// default gateway. Otherwise, the first interface is used.
+	pub(crate) fn route(&mut self, destination: IpAddress) -> Result<usize, i32> {
+		let nics = match self {
+			NetworkState::Initialized(nics) => nics,
+			_ => return Err(ENETDOWN),
+		};
+
+		Ok(nics
+			.iter()
+			.position(|nic| nic.is_local(&destination))
+			.or_else(|| nics.iter().position(|nic| nic.has_gateway(&destination)))
+			.unwrap_or(0))
+	}
 }
 
 pub(crate) type Handle = SocketHandle;
 
+/// Identifies a socket and the network interface, which owns it. The
+/// interface stays an internal detail of the file descriptor of the socket.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct InterfaceHandle {
+	pub(crate) interface: usize,
+	pub(crate) socket: Handle,
+}
+
+/// Identifies a TCP listener and its pool of listening sockets
+pub(crate) type ListenerHandle = usize;
+
+/// Position of the IPv4 address in the address list of the interface
+const IPV4_SLOT: usize = 0;
+
+/// IPv4 configuration of the network interface
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Ipv4Config {
+	pub address: Ipv4Cidr,
+	pub gateway: Option<Ipv4Address>,
+}
+
+impl Ipv4Config {
+	fn unspecified() -> Self {
+		Self {
+			address: Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0),
+			gateway: None,
+		}
+	}
+
+	/// Parses the textual representation of an address, a netmask and an optional gateway.
+	pub(crate) fn parse(address: &str, netmask: &str, gateway: Option<&str>) -> Result<Self, i32> {
+		let address = Ipv4Address::from_str(address).map_err(|_| EINVAL)?;
+		let netmask = Ipv4Address::from_str(netmask).map_err(|_| EINVAL)?;
+		let address = Ipv4Cidr::from_netmask(address, netmask).map_err(|_| EINVAL)?;
+		let gateway = gateway
+			.map(Ipv4Address::from_str)
+			.transpose()
+			.map_err(|_| EINVAL)?;
+
+		Ok(Self { address, gateway })
+	}
+}
+
+/// Options of a TCP socket, which smoltcp does not store itself
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct SocketOptions {
+	/// Read timeout in milliseconds
+	pub read_timeout: Option<u64>,
+	/// Write timeout in milliseconds
+	pub write_timeout: Option<u64>,
+	/// Return `EAGAIN` instead of blocking
+	pub nonblocking: bool,
+	/// The receiving half of the connection is shut down
+	pub read_shutdown: bool,
+}
+
+/// Configuration of a TCP socket, which has to be known before the socket
+/// connects or listens. Sockets, which are accepted by a listener, inherit
+/// the configuration of the listener.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TcpConfig {
+	/// Size of the receive buffer in bytes, which limits the advertised window
+	pub recv_buffer_size: usize,
+	/// Size of the send buffer in bytes
+	pub send_buffer_size: usize,
+	/// Interval in milliseconds between keep-alive segments of an idle
+	/// connection. `None` disables keep-alive.
+	pub keep_alive: Option<u64>,
+	/// Disables the Nagle algorithm
+	pub nodelay: bool,
+}
+
+impl Default for TcpConfig {
+	fn default() -> Self {
+		Self {
+			recv_buffer_size: DEFAULT_TCP_BUFFER_SIZE,
+			send_buffer_size: DEFAULT_TCP_BUFFER_SIZE,
+			keep_alive: Some(DEFAULT_KEEP_ALIVE_INTERVAL),
+			nodelay: true,
+		}
+	}
+}
+
+impl TcpConfig {
+	/// Checks, if the buffer sizes are supported by the kernel and
+	/// if the keep-alive interval is positive.
+	pub(crate) fn validate(&self) -> Result<(), i32> {
+		let buffer_sizes = MIN_TCP_BUFFER_SIZE..=MAX_TCP_BUFFER_SIZE;
+		if !buffer_sizes.contains(&self.recv_buffer_size)
+			|| !buffer_sizes.contains(&self.send_buffer_size)
+			|| self.keep_alive == Some(0)
+		{
+			return Err(EINVAL);
+		}
+
+		Ok(())
+	}
+}
+
+/// A TCP listener keeps several sockets in the `Listen` state, so that
+/// connections, which arrive between two accepts, are not refused.
+struct Listener {
+	endpoint: IpEndpoint,
+	config: TcpConfig,
+	backlog: Vec<SocketHandle>,
+	/// Number of sockets, which the backlog should contain
+	size: usize,
+}
+
 static LOCAL_ENDPOINT: AtomicU16 = AtomicU16::new(0);
+static NEXT_LISTENER: AtomicUsize = AtomicUsize::new(0);
 pub(crate) static NIC: SpinlockIrqSave<NetworkState> = SpinlockIrqSave::new(NetworkState::Missing);
 
 pub(crate) struct NetworkInterface<T: for<'a> Device<'a>> {
@@ -53,14 +225,97 @@ pub(crate) struct NetworkInterface<T: for<'a> Device<'a>> {
 	pub iface: smoltcp::iface::Interface<'static, Tracer<T>>,
 	#[cfg(not(feature = "trace"))]
 	pub iface: smoltcp::iface::Interface<'static, T>,
+	/// DHCP client, if the IPv4 address is not configured statically
 	#[cfg(feature = "dhcpv4")]
-	dhcp_handle: SocketHandle,
+	dhcp_handle: Option<SocketHandle>,
+	/// Default gateway of the IPv4 network
+	ipv4_gateway: Option<Ipv4Address>,
+	/// Default gateway of the IPv6 network
+	ipv6_gateway: Option<Ipv6Address>,
+	/// Default destinations of connected UDP sockets
+	udp_peers: BTreeMap<SocketHandle, IpEndpoint>,
+	/// Per-socket options of TCP sockets
+	tcp_options: BTreeMap<SocketHandle, SocketOptions>,
+	/// TCP sockets, which are closed by the application. They are removed
+	/// from the interface, when their connection has ended.
+	closing: Vec<SocketHandle>,
+	/// Nameservers, which are announced by DHCP
+	dns_servers: Vec<IpAddress>,
+	/// Open TCP listeners
+	listeners: BTreeMap<ListenerHandle, Listener>,
+	mac: EthernetAddress,
+	/// Interface identifier of the IPv6 addresses, which is derived from the MAC address
+	interface_id: [u8; 8],
+	/// State of the IPv6 autoconfiguration, if the address isn't configured statically
+	autoconf: Option<Autoconfiguration>,
+}
+
+/// State of the stateless address autoconfiguration (RFC 4862)
+struct Autoconfiguration {
+	/// Raw socket, which sends the router solicitations and receives the advertisements
+	handle: SocketHandle,
+	/// Number of router solicitations, which are already sent
+	solicitations: u8,
+	/// Time of the next router solicitation, as long as no router has answered
+	next_solicitation: Option<Instant>,
+	address: Option<AutoconfiguredAddress>,
+}
+
+/// Address, which is derived from the prefix of a router advertisement.
+/// A lifetime of `None` never ends.
+struct AutoconfiguredAddress {
+	cidr: Ipv6Cidr,
+	preferred_until: Option<Instant>,
+	valid_until: Option<Instant>,
 }
 
+impl Autoconfiguration {
+	/// Returns the time of the next solicitation or of the next end of a lifetime.
+	fn next_event(&self, timestamp: Instant) -> Option<Instant> {
+		let lifetimes = self
+			.address
+			.iter()
+			.flat_map(|address| [address.preferred_until, address.valid_until]);
+
+		self.next_solicitation
+			.into_iter()
+			.chain(lifetimes.flatten())
+			.filter(|event| *event > timestamp)
+			.min()
+	}
+}
+
+/// Returns the end of a lifetime in seconds, which starts at `timestamp`.
+fn lifetime_end(timestamp: Instant, lifetime: u32) -> Option<Instant> {
+	(lifetime != ipv6::INFINITE_LIFETIME).then(|| timestamp + Duration::from_secs(lifetime.into()))
+}
+
+/// First port of the dynamic range, from which the local ports are taken (RFC 6335)
+const EPHEMERAL_PORT_START: u16 = 49152;
+
 fn start_endpoint() -> u16 {
-	((unsafe { core::arch::x86_64::_rdtsc() as u64 }) % (u16::MAX as u64))
-		.try_into()
-		.unwrap()
+	let ports = u64::from(u16::MAX - EPHEMERAL_PORT_START) + 1;
+	EPHEMERAL_PORT_START
+		+ u16::try_from((unsafe { core::arch::x86_64::_rdtsc() as u64 }) % ports).unwrap()
+}
+
+/// Returns the next local port of the dynamic range. After the last port,
+/// the range starts again at its first port.
+fn next_local_endpoint() -> u16 {
+	fn successor(port: u16) -> u16 {
+		if (EPHEMERAL_PORT_START..u16::MAX).contains(&port) {
+			port + 1
+		} else {
+			EPHEMERAL_PORT_START
+		}
+	}
+
+	let port = LOCAL_ENDPOINT
+		.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |port| {
+			Some(successor(port))
+		})
+		.unwrap();
+	successor(port)
 }
 
 #[inline]
@@ -71,8 +326,11 @@ pub(crate) fn now() -> Instant {
 
 async fn network_run() {
 	future::poll_fn(|cx| match NIC.lock().deref_mut() {
-		NetworkState::Initialized(nic) => {
-			nic.poll_common(now());
+		NetworkState::Initialized(nics) => {
+			let time = now();
+			for nic in nics.iter_mut() {
+				nic.poll_common(time);
+			}
 
 			// this background task will never stop
 			// => wakeup ourself
@@ -88,13 +346,9 @@ async fn network_run() {
 #[inline]
 pub(crate) fn network_poll() {
 	if let Ok(mut guard) = NIC.try_lock() {
-		if let NetworkState::Initialized(nic) = guard.deref_mut() {
-			let time = now();
-			nic.poll_common(time);
-			if let Some(delay) = nic.poll_delay(time).map(|d| d.total_micros()) {
-				let wakeup_time = crate::arch::processor::get_timer_ticks() + delay;
-				crate::core_scheduler().add_network_timer(wakeup_time);
-			}
+		if let Some(delay) = guard.poll(now()).map(|d| d.total_micros()) {
+			let wakeup_time = crate::arch::processor::get_timer_ticks() + delay;
+			crate::core_scheduler().add_network_timer(wakeup_time);
 		}
 	}
 }
@@ -107,12 +361,18 @@ pub(crate) fn init() {
 
 	let mut guard = NIC.lock();
 
-	*guard = NetworkInterface::<HermitNet>::create();
+	let count = unsafe { SYS.get_network_interface_count() };
+	let nics: Vec<_> = (0..count)
+		.filter_map(NetworkInterface::<HermitNet>::create)
+		.collect();
+	*guard = if !nics.is_empty() {
+		NetworkState::Initialized(nics)
+	} else {
+		NetworkState::InitializationFailed
+	};
 
-	if let NetworkState::Initialized(nic) = guard.deref_mut() {
-		let time = now();
-		nic.poll_common(time);
-		if let Some(delay) = nic.poll_delay(time).map(|d| d.total_micros()) {
+	if matches!(*guard, NetworkState::Initialized(_)) {
+		if let Some(delay) = guard.poll(now()).map(|d| d.total_micros()) {
 			let wakeup_time = crate::arch::processor::get_timer_ticks() + delay;
 			crate::core_scheduler().add_network_timer(wakeup_time);
 		}
@@ -125,85 +385,623 @@ impl<T> NetworkInterface<T>
 where
 	T: for<'a> Device<'a>,
 {
-	pub(crate) fn create_handle(&mut self) -> Result<Handle, ()> {
-		let tcp_rx_buffer = TcpSocketBuffer::new(vec![0; 65535]);
-		let tcp_tx_buffer = TcpSocketBuffer::new(vec![0; 65535]);
+	/// Returns true, if `handle` refers to a socket of this interface.
+	fn has_socket(&self, handle: Handle) -> bool {
+		self.iface.sockets().any(|(socket, _)| socket == handle)
+	}
+
+	/// Creates a TCP socket. An invalid configuration is rejected with `EINVAL`.
+	pub(crate) fn create_handle(&mut self, config: &TcpConfig) -> Result<SocketHandle, i32> {
+		config.validate()?;
+		let tcp_rx_buffer = TcpSocketBuffer::new(vec![0; config.recv_buffer_size]);
+		let tcp_tx_buffer = TcpSocketBuffer::new(vec![0; config.send_buffer_size]);
 		let mut tcp_socket = TcpSocket::new(tcp_rx_buffer, tcp_tx_buffer);
-		tcp_socket.set_nagle_enabled(false);
+		tcp_socket.set_nagle_enabled(!config.nodelay);
+		tcp_socket.set_keep_alive(config.keep_alive.map(Duration::from_millis));
 		let tcp_handle = self.iface.add_socket(tcp_socket);
 
 		Ok(tcp_handle)
 	}
 
-	pub(crate) fn poll_common(&mut self, timestamp: Instant) {
-		while self.iface.poll(timestamp).unwrap_or(true) {
-			// just to make progress
+	/// Aborts a TCP socket and removes it from the interface.
+	pub(crate) fn destroy_handle(&mut self, handle: SocketHandle) {
+		self.tcp_options.remove(&handle);
+		self.iface.get_socket::<TcpSocket<'_>>(handle).abort();
+		self.iface.remove_socket(handle);
+	}
+
+	/// Closes a TCP socket, which the application doesn't use anymore. The
+	/// interface removes the socket, when the connection has ended.
+	fn release_handle(&mut self, handle: SocketHandle) {
+		// The options must not be applied to a later socket with the same handle.
+		self.tcp_options.remove(&handle);
+		if self.has_socket(handle) {
+			self.iface.get_socket::<TcpSocket<'_>>(handle).close();
+			self.closing.push(handle);
 		}
-		#[cfg(feature = "dhcpv4")]
-		match self
+	}
+
+	/// Removes the closed TCP sockets, whose connection has ended.
+	fn remove_closed_sockets(&mut self) {
+		let iface = &mut self.iface;
+		self.closing.retain(|handle| {
+			let ended = matches!(
+				iface.get_socket::<TcpSocket<'_>>(*handle).state(),
+				TcpState::Closed | TcpState::TimeWait
+			);
+			if ended {
+				iface.remove_socket(*handle);
+			}
+			!ended
+		});
+	}
+
+	pub(crate) fn create_udp_handle(&mut self) -> Result<SocketHandle, ()> {
+		let udp_rx_buffer =
+			UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 64], vec![0; 65535]);
+		let udp_tx_buffer =
+			UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY; 64], vec![0; 65535]);
+		let udp_socket = UdpSocket::new(udp_rx_buffer, udp_tx_buffer);
+		let udp_handle = self.iface.add_socket(udp_socket);
+
+		Ok(udp_handle)
+	}
+
+	pub(crate) fn destroy_udp_handle(&mut self, handle: SocketHandle) {
+		self.udp_peers.remove(&handle);
+		self.iface.remove_socket(handle);
+	}
+
+	fn udp_port_in_use(&self, port: u16) -> bool {
+		self.iface.sockets().any(|(_, socket)| match socket {
+			Socket::Udp(socket) => socket.is_open() && socket.endpoint().port == port,
+			_ => false,
+		})
+	}
+
+	fn create_listening_handle(
+		&mut self,
+		endpoint: IpEndpoint,
+		config: &TcpConfig,
+	) -> Result<SocketHandle, i32> {
+		let handle = self.create_handle(config)?;
+		if let Err(err) = self
 			.iface
-			.get_socket::<Dhcpv4Socket>(self.dhcp_handle)
-			.poll()
+			.get_socket::<TcpSocket<'_>>(handle)
+			.listen(endpoint)
 		{
-			None => {}
-			Some(Dhcpv4Event::Configured(config)) => {
-				info!("DHCP config acquired!");
-				info!("IP address:      {}", config.address);
-				self.iface.update_ip_addrs(|addrs| {
-					let dest = addrs.iter_mut().next().unwrap();
-					*dest = IpCidr::Ipv4(config.address);
+			self.iface.remove_socket(handle);
+			return Err(match err {
+				Error::Unaddressable => EINVAL,
+				err => smoltcp_errno(err),
+			});
+		}
+
+		Ok(handle)
+	}
+
+	pub(crate) fn create_listener(
+		&mut self,
+		listener: ListenerHandle,
+		endpoint: IpEndpoint,
+		backlog: usize,
+		config: &TcpConfig,
+	) -> Result<(), i32> {
+		if self
+			.listeners
+			.values()
+			.any(|listener| listener.endpoint.port == endpoint.port)
+		{
+			return Err(EADDRINUSE);
+		}
+
+		let backlog = backlog.clamp(1, MAX_LISTEN_BACKLOG);
+		let mut handles = Vec::with_capacity(backlog);
+
+		for _ in 0..backlog {
+			match self.create_listening_handle(endpoint, config) {
+				Ok(handle) => handles.push(handle),
+				Err(err) => {
+					for handle in handles {
+						self.iface.remove_socket(handle);
+					}
+					return Err(err);
+				}
+			}
+		}
+
+		self.listeners.insert(
+			listener,
+			Listener {
+				endpoint,
+				config: *config,
+				backlog: handles,
+				size: backlog,
+			},
+		);
+
+		Ok(())
+	}
+
+	pub(crate) fn destroy_listener(&mut self, listener: ListenerHandle) -> Result<(), i32> {
+		let listener = self.listeners.remove(&listener).ok_or(EBADF)?;
+		for handle in listener.backlog {
+			self.iface.get_socket::<TcpSocket<'_>>(handle).abort();
+			self.iface.remove_socket(handle);
+		}
+
+		Ok(())
+	}
+
+	/// Removes the sockets of the listener's backlog, whose connection has
+	/// ended before it was accepted, and refills the backlog with new
+	/// listening sockets.
+	fn refill_backlog(&mut self, listener: ListenerHandle) {
+		let (endpoint, config, missing) = match self.listeners.get_mut(&listener) {
+			Some(listener) => {
+				let iface = &mut self.iface;
+				listener.backlog.retain(|handle| {
+					let usable = matches!(
+						iface.get_socket::<TcpSocket<'_>>(*handle).state(),
+						TcpState::Listen
+							| TcpState::SynReceived
+							| TcpState::Established
+							| TcpState::CloseWait
+					);
+					if !usable {
+						iface.remove_socket(*handle);
+					}
+					usable
 				});
-				if let Some(router) = config.router {
-					info!("Default gateway: {}", router);
-					self.iface
-						.routes_mut()
-						.add_default_ipv4_route(router)
-						.unwrap();
-				} else {
-					info!("Default gateway: None");
-					self.iface.routes_mut().remove_default_ipv4_route();
+
+				(
+					listener.endpoint,
+					listener.config,
+					listener.size.saturating_sub(listener.backlog.len()),
+				)
+			}
+			None => return,
+		};
+
+		for _ in 0..missing {
+			match self.create_listening_handle(endpoint, &config) {
+				Ok(handle) => self
+					.listeners
+					.get_mut(&listener)
+					.unwrap()
+					.backlog
+					.push(handle),
+				Err(errno) => {
+					warn!(
+						"Unable to refill backlog of listener {}: errno {}",
+						listener, errno
+					);
+					break;
 				}
+			}
+		}
+	}
+
+	/// Takes an established connection out of the listener's pool and
+	/// replaces it by a new listening socket.
+	fn accept_connection(
+		&mut self,
+		listener: ListenerHandle,
+		waker: &Waker,
+	) -> Poll<Result<(SocketHandle, IpEndpoint), i32>> {
+		self.refill_backlog(listener);
+
+		let (config, handle) = {
+			let listener = match self.listeners.get_mut(&listener) {
+				Some(listener) => listener,
+				None => return Poll::Ready(Err(EBADF)),
+			};
+
+			let iface = &mut self.iface;
+			let pos = listener.backlog.iter().position(|handle| {
+				matches!(
+					iface.get_socket::<TcpSocket<'_>>(*handle).state(),
+					TcpState::Established | TcpState::CloseWait
+				)
+			});
 
-				for (i, s) in config.dns_servers.iter().enumerate() {
-					if let Some(s) = s {
-						info!("DNS server {}:    {}", i, s);
+			match pos {
+				Some(pos) => (listener.config, listener.backlog.swap_remove(pos)),
+				None => {
+					for handle in listener.backlog.iter() {
+						iface
+							.get_socket::<TcpSocket<'_>>(*handle)
+							.register_recv_waker(waker);
 					}
+					return Poll::Pending;
 				}
 			}
-			Some(Dhcpv4Event::Deconfigured) => {
-				info!("DHCP lost config!");
-				let cidr = Ipv4Cidr::new(Ipv4Address::UNSPECIFIED, 0);
-				self.iface.update_ip_addrs(|addrs| {
-					let dest = addrs.iter_mut().next().unwrap();
-					*dest = IpCidr::Ipv4(cidr);
-				});
+		};
+
+		self.refill_backlog(listener);
+
+		let socket = self.iface.get_socket::<TcpSocket<'_>>(handle);
+		socket.set_keep_alive(config.keep_alive.map(Duration::from_millis));
+
+		Poll::Ready(Ok((handle, socket.remote_endpoint())))
+	}
+
+	pub(crate) fn poll_common(&mut self, timestamp: Instant) {
+		while self.iface.poll(timestamp).unwrap_or(true) {
+			// just to make progress
+		}
+		#[cfg(feature = "dhcpv4")]
+		if let Some(dhcp_handle) = self.dhcp_handle {
+			match self.iface.get_socket::<Dhcpv4Socket>(dhcp_handle).poll() {
+				None => {}
+				Some(Dhcpv4Event::Configured(config)) => {
+					info!("DHCP config acquired!");
+					info!("IP address:      {}", config.address);
+					if let Some(router) = config.router {
+						info!("Default gateway: {}", router);
+					} else {
+						info!("Default gateway: None");
+					}
+					self.apply_ipv4_config(Ipv4Config {
+						address: config.address,
+						gateway: config.router,
+					});
+
+					self.dns_servers.clear();
+					for (i, s) in config.dns_servers.iter().enumerate() {
+						if let Some(s) = s {
+							info!("DNS server {}:    {}", i, s);
+							self.dns_servers.push(IpAddress::Ipv4(*s));
+						}
+					}
+				}
+				Some(Dhcpv4Event::Deconfigured) => {
+					info!("DHCP lost config!");
+					self.apply_ipv4_config(Ipv4Config::unspecified());
+					self.dns_servers.clear();
+				}
+			};
+		}
+		self.poll_autoconfiguration(timestamp);
+		self.remove_closed_sockets();
+	}
+
+	/// Returns true, if `address` belongs to the subnet of one of the interface's addresses.
+	fn is_local(&self, address: &IpAddress) -> bool {
+		self.iface
+			.ip_addrs()
+			.iter()
+			.any(|cidr| !cidr.address().is_unspecified() && cidr.contains_addr(address))
+	}
+
+	fn has_gateway(&self, address: &IpAddress) -> bool {
+		match address {
+			IpAddress::Ipv4(_) => self.ipv4_gateway.is_some(),
+			IpAddress::Ipv6(_) => self.ipv6_gateway.is_some(),
+			_ => false,
+		}
+	}
+
+	pub(crate) fn mac_address(&self) -> EthernetAddress {
+		self.mac
+	}
+
+	pub(crate) fn mtu(&self) -> usize {
+		self.iface.device().capabilities().max_transmission_unit
+	}
+
+	/// Returns the configured addresses of the interface without duplicates.
+	pub(crate) fn addresses(&self) -> Vec<IpCidr> {
+		let mut addresses: Vec<IpCidr> = Vec::new();
+		for cidr in self.iface.ip_addrs() {
+			if !cidr.address().is_unspecified() && !addresses.contains(cidr) {
+				addresses.push(*cidr);
+			}
+		}
+
+		addresses
+	}
+
+	/// Returns the current IPv4 address and gateway of the interface.
+	pub(crate) fn ipv4_config(&self) -> Ipv4Config {
+		let address = match self.iface.ip_addrs()[IPV4_SLOT] {
+			IpCidr::Ipv4(address) => address,
+			_ => Ipv4Config::unspecified().address,
+		};
+
+		Ipv4Config {
+			address,
+			gateway: self.ipv4_gateway,
+		}
+	}
+
+	/// Configures the IPv4 address and gateway statically and stops the DHCP client.
+	pub(crate) fn set_ipv4_config(&mut self, config: Ipv4Config) {
+		#[cfg(feature = "dhcpv4")]
+		if let Some(dhcp_handle) = self.dhcp_handle.take() {
+			self.iface.remove_socket(dhcp_handle);
+			self.dns_servers.clear();
+		}
+
+		self.apply_ipv4_config(config);
+	}
+
+	fn apply_ipv4_config(&mut self, config: Ipv4Config) {
+		self.iface.update_ip_addrs(|addrs| {
+			addrs[IPV4_SLOT] = IpCidr::Ipv4(config.address);
+		});
+		match config.gateway {
+			Some(gateway) => {
+				self.iface
+					.routes_mut()
+					.add_default_ipv4_route(gateway)
+					.unwrap();
+			}
+			None => {
 				self.iface.routes_mut().remove_default_ipv4_route();
 			}
+		}
+		self.ipv4_gateway = config.gateway;
+	}
+
+	#[cfg(feature = "dhcpv4")]
+	pub(crate) fn dhcp_enabled(&self) -> bool {
+		self.dhcp_handle.is_some()
+	}
+
+	#[cfg(not(feature = "dhcpv4"))]
+	pub(crate) fn dhcp_enabled(&self) -> bool {
+		false
+	}
+
+	/// Starts or stops the DHCP client. A stopped client leaves the
+	/// acquired address behind as static configuration.
+	#[cfg(feature = "dhcpv4")]
+	pub(crate) fn set_dhcp(&mut self, enabled: bool) -> Result<(), i32> {
+		if enabled && self.dhcp_handle.is_none() {
+			self.apply_ipv4_config(Ipv4Config::unspecified());
+			self.dhcp_handle = Some(self.iface.add_socket(Dhcpv4Socket::new()));
+		} else if !enabled {
+			if let Some(dhcp_handle) = self.dhcp_handle.take() {
+				self.iface.remove_socket(dhcp_handle);
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Returns `EOPNOTSUPP` on attempts to start the DHCP client,
+	/// because the kernel is built without the `dhcpv4` feature.
+	#[cfg(not(feature = "dhcpv4"))]
+	pub(crate) fn set_dhcp(&mut self, enabled: bool) -> Result<(), i32> {
+		if enabled {
+			Err(EOPNOTSUPP)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Solicits router advertisements, which are used to configure
+	/// a global IPv6 address and the default gateway.
+	pub(crate) fn start_autoconfiguration(&mut self) {
+		let rx_buffer = RawSocketBuffer::new(vec![RawPacketMetadata::EMPTY; 4], vec![0; 2048]);
+		let tx_buffer = RawSocketBuffer::new(vec![RawPacketMetadata::EMPTY; 1], vec![0; 128]);
+		let socket = RawSocket::new(IpVersion::Ipv6, IpProtocol::Icmpv6, rx_buffer, tx_buffer);
+
+		self.autoconf = Some(Autoconfiguration {
+			handle: self.iface.add_socket(socket),
+			solicitations: 0,
+			next_solicitation: Some(now()),
+			address: None,
+		});
+	}
+
+	fn poll_autoconfiguration(&mut self, timestamp: Instant) {
+		let handle = match &self.autoconf {
+			Some(autoconf) => autoconf.handle,
+			None => return,
+		};
+
+		let mut advertisements = Vec::new();
+		let socket = self.iface.get_socket::<RawSocket<'_>>(handle);
+		while let Ok(packet) = socket.recv() {
+			advertisements.extend(ipv6::parse_router_advertisement(packet));
+		}
+
+		for advertisement in advertisements {
+			if let Some(prefix) = advertisement.prefixes.first() {
+				self.update_autoconfigured_address(prefix, timestamp);
+			}
+			if let Some(router) = advertisement.router {
+				// a router has answered => no further solicitations
+				if let Some(autoconf) = self.autoconf.as_mut() {
+					autoconf.next_solicitation = None;
+				}
+				self.set_default_ipv6_route(router);
+			}
+		}
+
+		self.solicit_routers(timestamp);
+		self.apply_autoconfigured_address(timestamp);
+	}
+
+	/// Sends a router solicitation, until a router answers or
+	/// `MAX_RTR_SOLICITATIONS` are sent (RFC 4861, section 6.3.7).
+	fn solicit_routers(&mut self, timestamp: Instant) {
+		let autoconf = match self.autoconf.as_mut() {
+			Some(autoconf) => autoconf,
+			None => return,
+		};
+		match autoconf.next_solicitation {
+			Some(next_solicitation) if next_solicitation <= timestamp => {}
+			_ => return,
+		}
+
+		let link_local = ipv6::link_local_address(&self.interface_id);
+		let solicitation = ipv6::router_solicitation(&link_local.address(), &self.mac);
+		let socket = self.iface.get_socket::<RawSocket<'_>>(autoconf.handle);
+		if let Err(err) = socket.send_slice(&solicitation) {
+			warn!("Unable to send router solicitation: {}", err);
+		}
+
+		autoconf.solicitations += 1;
+		autoconf.next_solicitation = (autoconf.solicitations < ipv6::MAX_RTR_SOLICITATIONS)
+			.then(|| timestamp + Duration::from_secs(ipv6::RTR_SOLICITATION_INTERVAL));
+	}
+
+	/// Configures the address of an advertised prefix or updates its lifetimes.
+	fn update_autoconfigured_address(
+		&mut self,
+		prefix: &ipv6::PrefixInformation,
+		timestamp: Instant,
+	) {
+		let autoconf = match self.autoconf.as_mut() {
+			Some(autoconf) => autoconf,
+			None => return,
+		};
+		let cidr = ipv6::autoconfigured_address(&prefix.prefix, &self.interface_id);
+		let preferred_until = lifetime_end(timestamp, prefix.preferred_lifetime);
+		let valid_until = lifetime_end(timestamp, prefix.valid_lifetime);
+
+		match autoconf.address.as_mut() {
+			Some(address) if address.cidr == cidr => {
+				// An advertisement must not shorten the remaining valid lifetime
+				// below two hours (RFC 4862, section 5.5.3).
+				let two_hours = timestamp + Duration::from_secs(2 * 60 * 60);
+				let remaining = address.valid_until;
+				let accepted = match valid_until {
+					None => true,
+					Some(end) => end > two_hours || remaining.map_or(false, |r| end > r),
+				};
+
+				address.preferred_until = preferred_until;
+				address.valid_until = if accepted {
+					valid_until
+				} else if remaining.map_or(false, |r| r <= two_hours) {
+					remaining
+				} else {
+					Some(two_hours)
+				};
+			}
+			_ => {
+				autoconf.address = Some(AutoconfiguredAddress {
+					cidr,
+					preferred_until,
+					valid_until,
+				})
+			}
+		}
+	}
+
+	/// Applies the lifetimes of the autoconfigured address to the address list.
+	/// After its preferred lifetime, the address is deprecated and the link-local
+	/// address becomes the source of new connections. After its valid lifetime,
+	/// the address is removed.
+	fn apply_autoconfigured_address(&mut self, timestamp: Instant) {
+		let autoconf = match self.autoconf.as_mut() {
+			Some(autoconf) => autoconf,
+			None => return,
+		};
+		if let Some(address) = &autoconf.address {
+			if address.valid_until.map_or(false, |end| end <= timestamp) {
+				info!("IPv6 address {} expired", address.cidr);
+				autoconf.address = None;
+			}
+		}
+
+		let link_local = IpCidr::Ipv6(ipv6::link_local_address(&self.interface_id));
+		let (preferred, other) = match &autoconf.address {
+			Some(address) if address.preferred_until.map_or(true, |end| end > timestamp) => {
+				(IpCidr::Ipv6(address.cidr), link_local)
+			}
+			Some(address) => (link_local, IpCidr::Ipv6(address.cidr)),
+			None => (link_local, link_local),
 		};
+
+		let addrs = self.iface.ip_addrs();
+		if addrs[ipv6::ADDRESS_SLOT] != preferred || addrs[ipv6::LINK_LOCAL_SLOT] != other {
+			if preferred != link_local {
+				info!("IPv6 address:   {}", preferred);
+			} else if other != link_local {
+				info!("IPv6 address {} is deprecated", other);
+			}
+			self.iface.update_ip_addrs(|addrs| {
+				addrs[ipv6::ADDRESS_SLOT] = preferred;
+				addrs[ipv6::LINK_LOCAL_SLOT] = other;
+			});
+		}
+	}
+
+	fn set_default_ipv6_route(&mut self, router: Ipv6Address) {
+		self.ipv6_gateway = Some(router);
+		match self.iface.routes_mut().add_default_ipv6_route(router) {
+			Ok(Some(previous)) if previous.via_router == IpAddress::Ipv6(router) => {}
+			Ok(_) => info!("IPv6 gateway:   {}", router),
+			Err(err) => warn!("Unable to add IPv6 gateway {}: {}", router, err),
+		}
 	}
 
 	pub(crate) fn poll_delay(&mut self, timestamp: Instant) -> Option<Duration> {
-		self.iface.poll_delay(timestamp)
+		let delay = self.iface.poll_delay(timestamp);
+		let autoconf_delay = self
+			.autoconf
+			.as_ref()
+			.and_then(|autoconf| autoconf.next_event(timestamp))
+			.map(|event| event - timestamp);
+
+		match (delay, autoconf_delay) {
+			(Some(delay), Some(autoconf_delay)) => Some(delay.min(autoconf_delay)),
+			(delay, autoconf_delay) => delay.or(autoconf_delay),
+		}
 	}
 }
 
-pub(crate) struct AsyncSocket(Handle);
+pub(crate) struct AsyncSocket(InterfaceHandle);
 
 impl AsyncSocket {
-	pub(crate) fn new() -> Self {
-		let handle = NIC.lock().as_nic_mut().unwrap().create_handle().unwrap();
-		Self(handle)
+	/// Creates a TCP socket on the given interface.
+	pub(crate) fn new(interface: usize, config: &TcpConfig) -> Result<Self, i32> {
+		let socket = NIC.lock().nic_mut(interface)?.create_handle(config)?;
+		Ok(Self(InterfaceHandle { interface, socket }))
 	}
 
-	pub(crate) fn inner(&self) -> Handle {
-		self.0
+	/// Removes a socket, which has never been connected, from its interface.
+	pub(crate) fn destroy(&self) {
+		if let Ok(nic) = NIC.lock().nic_mut(self.0.interface) {
+			nic.destroy_handle(self.0.socket);
+		}
 	}
 
-	fn with<R>(&self, f: impl FnOnce(&mut TcpSocket<'_>) -> R) -> R {
+	pub(crate) fn options(&self) -> SocketOptions {
+		NIC.lock()
+			.nic_mut(self.0.interface)
+			.ok()
+			.and_then(|nic| nic.tcp_options.get(&self.0.socket).copied())
+			.unwrap_or_default()
+	}
+
+	pub(crate) fn set_options(&self, f: impl FnOnce(&mut SocketOptions)) -> Result<(), i32> {
 		let mut guard = NIC.lock();
-		let nic = guard.as_nic_mut().unwrap();
+		let nic = guard.nic_mut(self.0.interface)?;
+		f(nic.tcp_options.entry(self.0.socket).or_default());
+
+		Ok(())
+	}
+
+	fn with<T>(
+		&self,
+		f: impl FnOnce(&mut TcpSocket<'_>) -> Poll<Result<T, i32>>,
+	) -> Poll<Result<T, i32>> {
+		let mut guard = NIC.lock();
+		let nic = match guard.nic_mut(self.0.interface) {
+			Ok(nic) => nic,
+			Err(_) => return Poll::Ready(Err(EBADF)),
+		};
+		if !nic.has_socket(self.0.socket) {
+			return Poll::Ready(Err(ENOTCONN));
+		}
 		let res = {
-			let s = nic.iface.get_socket::<TcpSocket<'_>>(self.0);
+			let s = nic.iface.get_socket::<TcpSocket<'_>>(self.0.socket);
 			f(s)
 		};
 		let t = now();
@@ -216,37 +1014,44 @@ impl AsyncSocket {
 	fn with_context<R>(
 		&self,
 		f: impl FnOnce(&mut TcpSocket<'_>, &mut iface::Context<'_>) -> R,
-	) -> R {
+	) -> Result<R, i32> {
 		let mut guard = NIC.lock();
-		let nic = guard.as_nic_mut().unwrap();
+		let nic = guard.nic_mut(self.0.interface).map_err(|_| EBADF)?;
+		if !nic.has_socket(self.0.socket) {
+			return Err(ENOTCONN);
+		}
 		let res = {
-			let (s, cx) = nic.iface.get_socket_and_context::<TcpSocket<'_>>(self.0);
+			let (s, cx) = nic
+				.iface
+				.get_socket_and_context::<TcpSocket<'_>>(self.0.socket);
 			f(s, cx)
 		};
 		let t = now();
 		if nic.poll_delay(t).map(|d| d.total_millis()).unwrap_or(0) == 0 {
 			nic.poll_common(t);
 		}
-		res
+		Ok(res)
 	}
 
-	pub(crate) async fn connect(&self, ip: &[u8], port: u16) -> Result<Handle, Error> {
-		let address = IpAddress::from_str(core::str::from_utf8(ip).map_err(|_| Error::Illegal)?)
-			.map_err(|_| Error::Illegal)?;
+	pub(crate) async fn connect(&self, ip: &[u8], port: u16) -> Result<InterfaceHandle, i32> {
+		let address = parse_address(ip)?;
+		if address.is_unspecified() || port == 0 {
+			return Err(EINVAL);
+		}
 
-		self.with_context(|socket, cx| {
-			socket.connect(
-				cx,
-				(address, port),
-				LOCAL_ENDPOINT.fetch_add(1, Ordering::SeqCst),
-			)
-		})
-		.map_err(|_| Error::Illegal)?;
+		self.with_context(|socket, cx| socket.connect(cx, (address, port), next_local_endpoint()))?
+			.map_err(|err| match err {
+				Error::Illegal => EISCONN,
+				// no source address to reach the destination
+				Error::Unaddressable => ENETUNREACH,
+				err => smoltcp_errno(err),
+			})?;
 
 		future::poll_fn(|cx| {
 			self.with(|socket| match socket.state() {
-				TcpState::Closed | TcpState::TimeWait => Poll::Ready(Err(Error::Unaddressable)),
-				TcpState::Listen => Poll::Ready(Err(Error::Illegal)),
+				// the peer answered our SYN with a RST
+				TcpState::Closed | TcpState::TimeWait => Poll::Ready(Err(ECONNREFUSED)),
+				TcpState::Listen => Poll::Ready(Err(EINVAL)),
 				TcpState::SynSent | TcpState::SynReceived => {
 					socket.register_send_waker(cx.waker());
 					Poll::Pending
@@ -257,82 +1062,85 @@ impl AsyncSocket {
 		.await
 	}
 
-	pub(crate) async fn accept(&self, port: u16) -> Result<(IpAddress, u16), Error> {
-		self.with(|socket| socket.listen(port).map_err(|_| Error::Illegal))?;
+	pub(crate) async fn read(&self, buffer: &mut [u8]) -> Result<usize, i32> {
+		self.receive(buffer, false).await
+	}
+
+	/// Receives data without removing it from the receive queue.
+	pub(crate) async fn peek(&self, buffer: &mut [u8]) -> Result<usize, i32> {
+		self.receive(buffer, true).await
+	}
+
+	async fn receive(&self, buffer: &mut [u8], peek: bool) -> Result<usize, i32> {
+		let options = self.options();
+		if options.read_shutdown {
+			return Ok(0);
+		}
 
 		future::poll_fn(|cx| {
 			self.with(|socket| {
-				if socket.is_active() {
-					Poll::Ready(Ok(()))
-				} else {
-					match socket.state() {
-						TcpState::Closed
-						| TcpState::Closing
-						| TcpState::FinWait1
-						| TcpState::FinWait2 => Poll::Ready(Err(Error::Illegal)),
-						_ => {
-							socket.register_recv_waker(cx.waker());
-							Poll::Pending
-						}
+				if socket.can_recv() {
+					let n = if peek {
+						socket.peek_slice(buffer)
+					} else {
+						socket.recv_slice(buffer)
+					}
+					.map_err(smoltcp_errno)?;
+					if n > 0 || buffer.is_empty() {
+						return Poll::Ready(Ok(n));
 					}
 				}
-			})
-		})
-		.await?;
-
-		let mut guard = NIC.lock();
-		let nic = guard.as_nic_mut().map_err(|_| Error::Illegal)?;
-		let socket = nic.iface.get_socket::<TcpSocket<'_>>(self.0);
-		socket.set_keep_alive(Some(Duration::from_millis(DEFAULT_KEEP_ALIVE_INTERVAL)));
-		let endpoint = socket.remote_endpoint();
 
-		Ok((endpoint.addr, endpoint.port))
-	}
-
-	pub(crate) async fn read(&self, buffer: &mut [u8]) -> Result<usize, Error> {
-		future::poll_fn(|cx| {
-			self.with(|socket| match socket.state() {
-				TcpState::FinWait1
-				| TcpState::FinWait2
-				| TcpState::Closed
-				| TcpState::Closing
-				| TcpState::TimeWait => Poll::Ready(Err(Error::Illegal)),
-				_ => {
-					if socket.can_recv() {
-						let n = socket.recv_slice(buffer).map_err(|_| Error::Illegal)?;
-						if n > 0 || buffer.is_empty() {
-							return Poll::Ready(Ok(n));
-						}
+				match socket.state() {
+					// the connection is not yet established
+					TcpState::Listen | TcpState::SynSent | TcpState::SynReceived => {}
+					_ if !socket.may_recv() => {
+						// smoltcp remembers, if the connection was closed by a FIN
+						return Poll::Ready(match socket.recv_slice(&mut []) {
+							// the peer has closed its sending half of the connection
+							// and all remaining data is consumed => EOF
+							Err(Error::Finished) => Ok(0),
+							_ => Err(ECONNRESET),
+						});
 					}
+					_ => {}
+				}
 
-					socket.register_recv_waker(cx.waker());
-					Poll::Pending
+				if options.nonblocking {
+					return Poll::Ready(Err(EAGAIN));
 				}
+
+				socket.register_recv_waker(cx.waker());
+				Poll::Pending
 			})
 		})
 		.await
 	}
 
-	pub(crate) async fn write(&self, buffer: &[u8]) -> Result<usize, Error> {
+	pub(crate) async fn write(&self, buffer: &[u8]) -> Result<usize, i32> {
+		let nonblocking = self.options().nonblocking;
 		let len = buffer.len();
 		let mut pos: usize = 0;
 
 		while pos < len {
 			let n = future::poll_fn(|cx| {
 				self.with(|socket| match socket.state() {
+					TcpState::Listen | TcpState::SynSent | TcpState::SynReceived => {
+						Poll::Ready(Err(ENOTCONN))
+					}
+					// our sending half of the connection is closed
 					TcpState::FinWait1
 					| TcpState::FinWait2
 					| TcpState::Closed
 					| TcpState::Closing
-					| TcpState::TimeWait => Poll::Ready(Err(Error::Illegal)),
+					| TcpState::LastAck
+					| TcpState::TimeWait => Poll::Ready(Err(EPIPE)),
 					_ => {
 						if !socket.may_send() {
-							return Poll::Ready(Err(Error::Illegal));
+							return Poll::Ready(Err(EPIPE));
 						} else if socket.can_send() {
 							return Poll::Ready(
-								socket
-									.send_slice(&buffer[pos..])
-									.map_err(|_| Error::Illegal),
+								socket.send_slice(&buffer[pos..]).map_err(smoltcp_errno),
 							);
 						}
 
@@ -342,6 +1150,10 @@ impl AsyncSocket {
 							return Poll::Ready(Ok(0));
 						}
 
+						if nonblocking {
+							return Poll::Ready(Err(EAGAIN));
+						}
+
 						socket.register_send_waker(cx.waker());
 						Poll::Pending
 					}
@@ -359,46 +1171,361 @@ impl AsyncSocket {
 		Ok(pos)
 	}
 
-	pub(crate) async fn close(&self) -> Result<(), Error> {
+	/// Sends a FIN after all queued data is transmitted. Afterwards, the
+	/// socket is still able to receive the remaining data of the peer.
+	/// The function doesn't wait for the transmission.
+	pub(crate) fn shutdown_write(&self) -> Result<(), i32> {
+		self.with_context(|socket, _| socket.close())
+	}
+
+	/// Marks the receiving half of the connection as closed. All following
+	/// reads return EOF.
+	pub(crate) fn shutdown_read(&self) -> Result<(), i32> {
+		self.set_options(|options| options.read_shutdown = true)
+	}
+
+	/// Closes the connection without waiting for the peer. The interface
+	/// sends the queued data and the FIN, and removes the socket afterwards.
+	pub(crate) fn close(&self) -> Result<(), i32> {
+		NIC.lock()
+			.nic_mut(self.0.interface)
+			.map_err(|_| EBADF)?
+			.release_handle(self.0.socket);
+
+		Ok(())
+	}
+}
+
+impl From<InterfaceHandle> for AsyncSocket {
+	fn from(handle: InterfaceHandle) -> Self {
+		AsyncSocket(handle)
+	}
+}
+
+fn parse_address(ip: &[u8]) -> Result<IpAddress, i32> {
+	IpAddress::from_str(core::str::from_utf8(ip).map_err(|_| EINVAL)?).map_err(|_| EINVAL)
+}
+
+/// Returns the interface, which reaches the address `ip`.
+pub(crate) fn route(ip: &[u8]) -> Result<usize, i32> {
+	let address = parse_address(ip)?;
+	NIC.lock().route(address)
+}
+
+/// Returns the interface, which owns the local address `ip`. An empty
+/// or unspecified address selects the first interface.
+pub(crate) fn local_interface(ip: &[u8]) -> Result<usize, i32> {
+	if ip.is_empty() {
+		return Ok(0);
+	}
+
+	let address = parse_address(ip)?;
+	if address.is_unspecified() {
+		return Ok(0);
+	}
+
+	NIC.lock()
+		.nics_mut()
+		.iter()
+		.position(|nic| nic.iface.has_ip_addr(address))
+		.ok_or(EADDRNOTAVAIL)
+}
+
+/// Maps smoltcp errors, which do not need a context-specific
+/// interpretation, to `errno` values.
+fn smoltcp_errno(err: Error) -> i32 {
+	match err {
+		Error::Exhausted => ENOBUFS,
+		Error::Illegal => EINVAL,
+		Error::Unaddressable => EADDRNOTAVAIL,
+		Error::Finished => ENOTCONN,
+		Error::Truncated => EMSGSIZE,
+		_ => EIO,
+	}
+}
+
+pub(crate) struct AsyncListener(ListenerHandle);
+
+impl AsyncListener {
+	/// Creates a listener with `backlog` sockets listening on the local
+	/// address. An empty address listens on all interfaces, unless
+	/// `interface` restricts the listener to one of them.
+	pub(crate) fn bind(
+		interface: Option<usize>,
+		ip: &[u8],
+		port: u16,
+		backlog: usize,
+		config: &TcpConfig,
+	) -> Result<Self, i32> {
+		let address = if ip.is_empty() {
+			IpAddress::Unspecified
+		} else {
+			parse_address(ip)?
+		};
+		let endpoint = IpEndpoint::new(address, port);
+		let listener = NEXT_LISTENER.fetch_add(1, Ordering::Relaxed);
+
+		let mut guard = NIC.lock();
+		let nics = guard.nics_mut();
+		if nics.is_empty() {
+			return Err(ENETDOWN);
+		}
+
+		let selected: Vec<usize> = (0..nics.len())
+			.filter(|i| match interface {
+				Some(interface) => interface == *i,
+				None => address.is_unspecified() || nics[*i].iface.has_ip_addr(address),
+			})
+			.collect();
+		if selected.is_empty() {
+			return Err(if interface.is_some() {
+				ENODEV
+			} else {
+				EADDRNOTAVAIL
+			});
+		}
+
+		for (n, i) in selected.iter().enumerate() {
+			if let Err(errno) = nics[*i].create_listener(listener, endpoint, backlog, config) {
+				for i in &selected[..n] {
+					let _ = nics[*i].destroy_listener(listener);
+				}
+				return Err(errno);
+			}
+		}
+
+		Ok(Self(listener))
+	}
+
+	pub(crate) fn inner(&self) -> ListenerHandle {
+		self.0
+	}
+
+	pub(crate) async fn accept(&self) -> Result<(InterfaceHandle, IpEndpoint), i32> {
 		future::poll_fn(|cx| {
-			self.with(|socket| match socket.state() {
-				TcpState::FinWait1
-				| TcpState::FinWait2
-				| TcpState::Closed
-				| TcpState::Closing
-				| TcpState::TimeWait => Poll::Ready(Err(Error::Illegal)),
-				_ => {
-					if socket.send_queue() > 0 {
+			let mut guard = NIC.lock();
+			let nics = guard.nics_mut();
+			if nics.is_empty() {
+				return Poll::Ready(Err(ENETDOWN));
+			}
+
+			let mut res = Poll::Ready(Err(EBADF));
+			for (interface, nic) in nics.iter_mut().enumerate() {
+				let accepted = nic.accept_connection(self.0, cx.waker());
+				let t = now();
+				if nic.poll_delay(t).map(|d| d.total_millis()).unwrap_or(0) == 0 {
+					nic.poll_common(t);
+				}
+
+				match accepted {
+					// the listener does not use this interface
+					Poll::Ready(Err(EBADF)) => {}
+					Poll::Ready(Ok((socket, endpoint))) => {
+						return Poll::Ready(Ok((InterfaceHandle { interface, socket }, endpoint)));
+					}
+					Poll::Ready(Err(errno)) => return Poll::Ready(Err(errno)),
+					Poll::Pending => res = Poll::Pending,
+				}
+			}
+
+			res
+		})
+		.await
+	}
+
+	pub(crate) fn close(&self) -> Result<(), i32> {
+		let mut guard = NIC.lock();
+		let mut res = Err(EBADF);
+		for nic in guard.nics_mut().iter_mut() {
+			if nic.destroy_listener(self.0).is_ok() {
+				res = Ok(());
+			}
+		}
+
+		res
+	}
+}
+
+impl From<ListenerHandle> for AsyncListener {
+	fn from(handle: ListenerHandle) -> Self {
+		AsyncListener(handle)
+	}
+}
+
+pub(crate) struct AsyncUdpSocket(InterfaceHandle);
+
+impl AsyncUdpSocket {
+	/// Creates a UDP socket on the given interface.
+	pub(crate) fn new(interface: usize) -> Result<Self, i32> {
+		let socket = NIC
+			.lock()
+			.nic_mut(interface)?
+			.create_udp_handle()
+			.map_err(|_| ENOBUFS)?;
+		Ok(Self(InterfaceHandle { interface, socket }))
+	}
+
+	pub(crate) fn inner(&self) -> InterfaceHandle {
+		self.0
+	}
+
+	fn with<T>(
+		&self,
+		f: impl FnOnce(&mut UdpSocket<'_>) -> Poll<Result<T, i32>>,
+	) -> Poll<Result<T, i32>> {
+		let mut guard = NIC.lock();
+		let nic = match guard.nic_mut(self.0.interface) {
+			Ok(nic) => nic,
+			Err(_) => return Poll::Ready(Err(EBADF)),
+		};
+		if !nic.has_socket(self.0.socket) {
+			return Poll::Ready(Err(EBADF));
+		}
+		let res = {
+			let s = nic.iface.get_socket::<UdpSocket<'_>>(self.0.socket);
+			f(s)
+		};
+		let t = now();
+		if nic.poll_delay(t).map(|d| d.total_millis()).unwrap_or(0) == 0 {
+			nic.poll_common(t);
+		}
+		res
+	}
+
+	/// Binds the socket to a local address. An empty address binds to all
+	/// interfaces and port 0 selects an ephemeral port.
+	pub(crate) fn bind(&self, ip: &[u8], port: u16) -> Result<(), i32> {
+		let address = if ip.is_empty() {
+			IpAddress::Unspecified
+		} else {
+			parse_address(ip)?
+		};
+		let port = if port == 0 {
+			next_local_endpoint()
+		} else {
+			port
+		};
+
+		let mut guard = NIC.lock();
+		let nic = guard.nic_mut(self.0.interface)?;
+		if nic.udp_port_in_use(port) {
+			return Err(EADDRINUSE);
+		}
+
+		nic.iface
+			.get_socket::<UdpSocket<'_>>(self.0.socket)
+			.bind((address, port))
+			.map_err(smoltcp_errno)
+	}
+
+	/// Sets the default destination for [`send`](Self::send) and the only
+	/// source, from which [`recv`](Self::recv) accepts datagrams.
+	pub(crate) fn connect(&self, ip: &[u8], port: u16) -> Result<(), i32> {
+		let address = parse_address(ip)?;
+		if address.is_unspecified() || port == 0 {
+			return Err(EINVAL);
+		}
+
+		let mut guard = NIC.lock();
+		let nic = guard.nic_mut(self.0.interface)?;
+		if !nic
+			.iface
+			.get_socket::<UdpSocket<'_>>(self.0.socket)
+			.is_open()
+		{
+			return Err(EBADF);
+		}
+		nic.udp_peers
+			.insert(self.0.socket, IpEndpoint::new(address, port));
+
+		Ok(())
+	}
+
+	fn peer(&self) -> Option<IpEndpoint> {
+		NIC.lock()
+			.nic_mut(self.0.interface)
+			.ok()
+			.and_then(|nic| nic.udp_peers.get(&self.0.socket).copied())
+	}
+
+	pub(crate) async fn send_to(&self, buffer: &[u8], ip: &[u8], port: u16) -> Result<usize, i32> {
+		let address = parse_address(ip)?;
+		if address.is_unspecified() || port == 0 {
+			return Err(EINVAL);
+		}
+
+		self.send_to_endpoint(buffer, IpEndpoint::new(address, port))
+			.await
+	}
+
+	pub(crate) async fn send(&self, buffer: &[u8]) -> Result<usize, i32> {
+		let endpoint = self.peer().ok_or(EDESTADDRREQ)?;
+		self.send_to_endpoint(buffer, endpoint).await
+	}
+
+	async fn send_to_endpoint(&self, buffer: &[u8], endpoint: IpEndpoint) -> Result<usize, i32> {
+		future::poll_fn(|cx| {
+			self.with(|socket| {
+				if !socket.is_open() {
+					return Poll::Ready(Err(EBADF));
+				}
+
+				match socket.send_slice(buffer, endpoint) {
+					Ok(()) => Poll::Ready(Ok(buffer.len())),
+					// the datagram does not fit into the remaining buffer space
+					Err(Error::Exhausted) => {
 						socket.register_send_waker(cx.waker());
 						Poll::Pending
-					} else {
-						socket.close();
-						Poll::Ready(Ok(()))
 					}
+					// the datagram is larger than the whole buffer
+					Err(Error::Truncated) => Poll::Ready(Err(EMSGSIZE)),
+					Err(err) => Poll::Ready(Err(smoltcp_errno(err))),
 				}
 			})
 		})
-		.await?;
+		.await
+	}
 
+	pub(crate) async fn recv_from(&self, buffer: &mut [u8]) -> Result<(usize, IpEndpoint), i32> {
 		future::poll_fn(|cx| {
-			self.with(|socket| match socket.state() {
-				TcpState::FinWait1
-				| TcpState::FinWait2
-				| TcpState::Closed
-				| TcpState::Closing
-				| TcpState::TimeWait => Poll::Ready(Ok(())),
-				_ => {
-					socket.register_send_waker(cx.waker());
+			self.with(|socket| {
+				if !socket.is_open() {
+					Poll::Ready(Err(EBADF))
+				} else if socket.can_recv() {
+					Poll::Ready(socket.recv_slice(buffer).map_err(smoltcp_errno))
+				} else {
+					socket.register_recv_waker(cx.waker());
 					Poll::Pending
 				}
 			})
 		})
 		.await
 	}
+
+	/// Receives a datagram from the connected peer. Datagrams from other
+	/// sources are discarded.
+	pub(crate) async fn recv(&self, buffer: &mut [u8]) -> Result<usize, i32> {
+		let peer = self.peer().ok_or(ENOTCONN)?;
+
+		loop {
+			let (len, endpoint) = self.recv_from(buffer).await?;
+			if endpoint == peer {
+				return Ok(len);
+			}
+		}
+	}
+
+	pub(crate) fn close(&self) -> Result<(), i32> {
+		let mut guard = NIC.lock();
+		let nic = guard.nic_mut(self.0.interface)?;
+		nic.destroy_udp_handle(self.0.socket);
+
+		Ok(())
+	}
 }
 
-impl From<Handle> for AsyncSocket {
-	fn from(handle: Handle) -> Self {
-		AsyncSocket(handle)
+impl From<InterfaceHandle> for AsyncUdpSocket {
+	fn from(handle: InterfaceHandle) -> Self {
+		AsyncUdpSocket(handle)
 	}
 }
@@ -1,9 +1,14 @@
 use alloc::boxed::Box;
 use alloc::collections::{BTreeMap, VecDeque};
 use alloc::rc::Rc;
+#[cfg(feature = "smp")]
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::cell::RefCell;
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::cmp;
+#[cfg(feature = "smp")]
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use crossbeam_utils::Backoff;
 
 use crate::arch;
@@ -11,6 +16,8 @@ use crate::arch::irq;
 use crate::arch::percore::*;
 use crate::arch::switch::{switch_to_fpu_owner, switch_to_task};
 use crate::collections::irqsave;
+use crate::config::DEFAULT_TIMESLICE;
+use crate::env;
 use crate::kernel::scheduler::TaskStacks;
 use crate::scheduler::task::*;
 use crate::synch::spinlock::*;
@@ -18,6 +25,8 @@ use crate::synch::spinlock::*;
 pub mod task;
 
 static NO_TASKS: AtomicU32 = AtomicU32::new(0);
+/// CPU time of all tasks except the idle tasks until their last switch (in microseconds)
+static CPU_TIME: AtomicU64 = AtomicU64::new(0);
 /// Map between Core ID and per-core scheduler
 static mut SCHEDULERS: Vec<&PerCoreScheduler> = Vec::new();
 /// Map between Task ID and Queue of waiting tasks
@@ -25,16 +34,59 @@ static WAITING_TASKS: SpinlockIrqSave<BTreeMap<TaskId, VecDeque<TaskHandle>>> =
 	SpinlockIrqSave::new(BTreeMap::new());
 /// Map between Task ID and TaskHandle
 static TASKS: SpinlockIrqSave<BTreeMap<TaskId, TaskHandle>> = SpinlockIrqSave::new(BTreeMap::new());
+/// Map between Task ID and the priorities of tasks, which have inherited a priority
+static INHERITED_PRIORITIES: SpinlockIrqSave<BTreeMap<TaskId, InheritedPriorities>> =
+	SpinlockIrqSave::new(BTreeMap::new());
+
+/// Priorities of a task, which has inherited the priority of a mutex waiter
+struct InheritedPriorities {
+	/// Priority of the task itself
+	own: Priority,
+	/// Priorities of the waiters, one for each mutex of the task
+	inherited: Vec<Priority>,
+}
+
+impl InheritedPriorities {
+	fn new(own: Priority) -> Self {
+		Self {
+			own,
+			inherited: Vec::new(),
+		}
+	}
+
+	/// Returns the priority, with which the task runs.
+	fn effective(&self) -> Priority {
+		self.inherited.iter().copied().fold(self.own, cmp::max)
+	}
+}
 
 /// Unique identifier for a core.
 pub type CoreId = u32;
 
+/// Interval between two attempts of a core to balance its load (in microseconds)
+#[cfg(feature = "smp")]
+const BALANCE_INTERVAL: u64 = 10_000;
+
+/// Receives the affinity of a task, which belongs to another core
+#[cfg(feature = "smp")]
+type AffinityAnswer = Arc<SpinlockIrqSave<Option<CpuSet>>>;
+
 #[cfg(feature = "smp")]
 struct SchedulerInput {
 	/// Queue of new tasks
 	new_tasks: VecDeque<Rc<RefCell<Task>>>,
 	/// Queue of task, which are wakeup by another core
 	wakeup_tasks: VecDeque<TaskHandle>,
+	/// Queue of blocked tasks, which migrate to this core, and their wakeup times
+	blocked_tasks: VecDeque<(Rc<RefCell<Task>>, Option<u64>)>,
+	/// Queue of affinity changes for tasks of this core
+	affinity_requests: VecDeque<(TaskId, CpuSet)>,
+	/// Queue of tasks, which wait for the affinity of a task of this core
+	affinity_queries: VecDeque<(TaskId, TaskHandle, AffinityAnswer)>,
+	/// Queue of tasks of this core, whose priority has changed in `TASKS`
+	priority_requests: VecDeque<TaskId>,
+	/// Queue of idle cores, which ask for a task of this core
+	steal_requests: VecDeque<CoreId>,
 }
 
 #[cfg(feature = "smp")]
@@ -43,6 +95,11 @@ impl SchedulerInput {
 		Self {
 			new_tasks: VecDeque::new(),
 			wakeup_tasks: VecDeque::new(),
+			blocked_tasks: VecDeque::new(),
+			affinity_requests: VecDeque::new(),
+			affinity_queries: VecDeque::new(),
+			priority_requests: VecDeque::new(),
+			steal_requests: VecDeque::new(),
 		}
 	}
 }
@@ -68,18 +125,44 @@ pub struct PerCoreScheduler {
 	finished_tasks: VecDeque<Rc<RefCell<Task>>>,
 	/// Queue of blocked tasks, sorted by wakeup time.
 	blocked_tasks: BlockedTaskQueue,
+	/// Time of the last task switch (in microseconds)
+	last_switch: AtomicU64,
+	/// Set while the idle task is running
+	idle_running: AtomicBool,
+	/// Time, which this core has spent in the idle task until the last switch (in microseconds)
+	idle_time: AtomicU64,
 	/// Queues to handle incoming requests from the other cores
 	#[cfg(feature = "smp")]
 	input: SpinlockIrqSave<SchedulerInput>,
+	/// Number of ready and running tasks, as seen by the other cores
+	#[cfg(feature = "smp")]
+	load: AtomicUsize,
+	/// Number of ready tasks, which the other cores are allowed to take over
+	#[cfg(feature = "smp")]
+	migratable_tasks: AtomicUsize,
+	/// Set while a steal request of this core is pending
+	#[cfg(feature = "smp")]
+	stealing: AtomicBool,
+	/// Time of the next attempt to balance the load (in microseconds)
+	#[cfg(feature = "smp")]
+	next_balance: u64,
+	/// Set if the ready queue may contain tasks, which aren't allowed to run on this core
+	#[cfg(feature = "smp")]
+	misplaced_tasks: bool,
 }
 
 impl PerCoreScheduler {
 	/// Spawn a new task.
+	///
+	/// The task starts on `core_id`, which has to be part of `affinity`.
+	/// Afterwards, it may migrate to the other cores of `affinity` to
+	/// balance the load.
 	pub fn spawn(
 		func: extern "C" fn(usize),
 		arg: usize,
 		prio: Priority,
 		core_id: CoreId,
+		affinity: CpuSet,
 		stack_size: usize,
 	) -> TaskId {
 		// Create the new task.
@@ -91,7 +174,11 @@ impl PerCoreScheduler {
 			prio,
 			stack_size,
 		)));
-		task.borrow_mut().create_stack_frame(func, arg);
+		{
+			let mut borrowed = task.borrow_mut();
+			borrowed.affinity = affinity;
+			borrowed.create_stack_frame(func, arg);
+		}
 
 		// Add it to the task lists.
 		let wakeup = {
@@ -115,11 +202,13 @@ impl PerCoreScheduler {
 				true
 			} else {
 				core_scheduler().ready_queue.push(task);
+				core_scheduler().update_timeslice();
 				false
 			}
 			#[cfg(not(feature = "smp"))]
 			if core_id == 0 {
 				core_scheduler().ready_queue.push(task);
+				core_scheduler().update_timeslice();
 				false
 			} else {
 				panic!("Invalid  core_id {}!", core_id)
@@ -212,11 +301,13 @@ impl PerCoreScheduler {
 				true
 			} else {
 				core_scheduler().ready_queue.push(clone_task);
+				core_scheduler().update_timeslice();
 				false
 			}
 			#[cfg(not(feature = "smp"))]
 			if core_id == 0 {
 				core_scheduler().ready_queue.push(clone_task);
+				core_scheduler().update_timeslice();
 				false
 			} else {
 				panic!("Invalid core_id {}!", core_id);
@@ -240,31 +331,48 @@ impl PerCoreScheduler {
 	#[inline]
 	#[cfg(feature = "smp")]
 	pub fn is_scheduling(&self) -> bool {
-		self.current_task.borrow().prio < self.ready_queue.get_highest_priority()
+		let current_task_borrowed = self.current_task.borrow();
+		current_task_borrowed.prio < self.ready_queue.get_highest_priority()
+			|| !current_task_borrowed.affinity.contains(self.core_id)
 	}
 
 	#[inline]
 	pub fn handle_waiting_tasks(&mut self) {
-		irqsave(|| self.blocked_tasks.handle_waiting_tasks());
+		irqsave(|| {
+			self.blocked_tasks.handle_waiting_tasks();
+			self.update_timeslice();
+		});
 	}
 
 	#[cfg(not(feature = "smp"))]
 	pub fn custom_wakeup(&mut self, task: TaskHandle) {
-		irqsave(|| self.blocked_tasks.custom_wakeup(task));
+		irqsave(|| {
+			self.blocked_tasks.custom_wakeup(task);
+			self.update_timeslice();
+		});
 	}
 
 	#[cfg(feature = "smp")]
 	pub fn custom_wakeup(&mut self, task: TaskHandle) {
-		if task.get_core_id() == self.core_id {
-			irqsave(|| self.blocked_tasks.custom_wakeup(task));
+		// The core of the handle is outdated, if the task has migrated in the meantime.
+		let core_id = get_task_handle(task.get_id())
+			.map_or(task.get_core_id(), |handle| handle.get_core_id());
+
+		if core_id == self.core_id {
+			irqsave(|| {
+				// The task may still wait in our input queue after migrating to this core.
+				self.receive_tasks();
+				self.blocked_tasks.custom_wakeup(task);
+				self.update_timeslice();
+			});
 		} else {
-			get_scheduler(task.get_core_id())
+			get_scheduler(core_id)
 				.input
 				.lock()
 				.wakeup_tasks
 				.push_back(task);
 			// Wake up the CPU
-			arch::wakeup_core(task.get_core_id());
+			arch::wakeup_core(core_id);
 		}
 	}
 
@@ -318,6 +426,27 @@ impl PerCoreScheduler {
 		irqsave(|| self.current_task.borrow().prio)
 	}
 
+	/// Returns the CPU time of the current task (in microseconds)
+	pub fn get_current_task_cpu_time(&self) -> u64 {
+		irqsave(|| {
+			let now = arch::processor::get_timer_ticks();
+			self.current_task.borrow().cpu_time
+				+ now.saturating_sub(self.last_switch.load(Ordering::Relaxed))
+		})
+	}
+
+	/// Charge the time since the last switch to the current task.
+	fn update_cpu_time(&mut self, now: u64) {
+		let delta = now.saturating_sub(self.last_switch.load(Ordering::Relaxed));
+		self.current_task.borrow_mut().cpu_time += delta;
+
+		if Rc::ptr_eq(&self.current_task, &self.idle_task) {
+			self.idle_time.fetch_add(delta, Ordering::Relaxed);
+		} else {
+			CPU_TIME.fetch_add(delta, Ordering::Relaxed);
+		}
+	}
+
 	#[cfg(target_arch = "x86_64")]
 	pub fn set_current_kernel_stack(&self) {
 		let current_task_borrowed = self.current_task.borrow();
@@ -335,34 +464,287 @@ impl PerCoreScheduler {
 	}
 
 	pub fn set_current_task_priority(&mut self, prio: Priority) {
-		irqsave(|| {
-			trace!("Change priority of the current task");
-			self.current_task.borrow_mut().prio = prio;
-		});
+		trace!("Change priority of the current task");
+		let id = self.get_current_task_id();
+		// The current task always exists.
+		let _ = self.set_priority(id, prio);
 	}
 
+	/// Change the priority of the task `id`, which may run on any core.
+	///
+	/// As long as the task has inherited a higher priority from the waiter
+	/// of a mutex, it keeps running with the inherited priority.
+	/// Ready tasks are moved to the queue of the new priority. For a task of
+	/// another core, the request is forwarded to that core. Wait queues of
+	/// the synchronization primitives take the new priority from `TASKS`,
+	/// when `TaskHandlePriorityQueue::pop` picks the next waiter.
 	pub fn set_priority(&mut self, id: TaskId, prio: Priority) -> Result<(), ()> {
 		trace!("Change priority of task {} to priority {}", id, prio);
+		self.update_priority(id, |priorities| priorities.own = prio)
+	}
 
-		irqsave(|| {
-			let task = get_task_handle(id).ok_or(())?;
-			#[cfg(feature = "smp")]
-			let other_core = task.get_core_id() != self.core_id;
-			#[cfg(not(feature = "smp"))]
-			let other_core = false;
+	/// Let the task `id` run at least with the priority `prio` of a task,
+	/// which waits for a mutex of the task `id`.
+	pub fn inherit_priority(&mut self, id: TaskId, prio: Priority) -> Result<(), ()> {
+		trace!("Task {} inherits priority {}", id, prio);
+		self.update_priority(id, |priorities| priorities.inherited.push(prio))
+	}
 
-			if other_core {
-				warn!("Have to change the priority on another core");
-			} else if self.current_task.borrow().id == task.get_id() {
-				self.current_task.borrow_mut().prio = prio;
+	/// Take back the priority `prio`, which the task `id` has inherited
+	/// by `inherit_priority`.
+	pub fn disinherit_priority(&mut self, id: TaskId, prio: Priority) -> Result<(), ()> {
+		trace!("Task {} gives back priority {}", id, prio);
+		self.update_priority(id, |priorities| {
+			if let Some(i) = priorities.inherited.iter().position(|p| *p == prio) {
+				priorities.inherited.swap_remove(i);
+			}
+		})
+	}
+
+	/// Let `f` change the own or the inherited priorities of the task `id`
+	/// and run the task with the highest of them.
+	fn update_priority<F>(&mut self, id: TaskId, f: F) -> Result<(), ()>
+	where
+		F: FnOnce(&mut InheritedPriorities),
+	{
+		let handle = irqsave(|| -> Result<TaskHandle, ()> {
+			let mut guard = INHERITED_PRIORITIES.lock();
+			let mut tasks = TASKS.lock();
+			let handle = tasks.get_mut(&id).ok_or(())?;
+			// Without inherited priorities, the task runs with its own priority.
+			let mut priorities = guard
+				.remove(&id)
+				.unwrap_or_else(|| InheritedPriorities::new(handle.get_priority()));
+			f(&mut priorities);
+			handle.set_priority(priorities.effective());
+			if !priorities.inherited.is_empty() {
+				guard.insert(id, priorities);
+			}
+
+			Ok(*handle)
+		})?;
+
+		#[cfg(feature = "smp")]
+		if handle.get_core_id() != self.core_id {
+			get_scheduler(handle.get_core_id())
+				.input
+				.lock()
+				.priority_requests
+				.push_back(id);
+			arch::wakeup_core(handle.get_core_id());
+			return Ok(());
+		}
+
+		self.apply_priority(handle.get_id());
+
+		Ok(())
+	}
+
+	/// Let the task `id`, which belongs to this core, run with its priority
+	/// from `TASKS`. If the current task lowers its priority, it gives up
+	/// the CPU.
+	fn apply_priority(&mut self, id: TaskId) {
+		if irqsave(|| self.change_priority(id)) {
+			self.reschedule();
+		}
+	}
+
+	/// Let the task `id`, which belongs to this core, run with its priority
+	/// from `TASKS`. Returns `true` if the current task has lowered its priority.
+	fn change_priority(&mut self, id: TaskId) -> bool {
+		let handle = match get_task_handle(id) {
+			Some(handle) => handle,
+			None => return false,
+		};
+		let prio = handle.get_priority();
+
+		#[cfg(feature = "smp")]
+		if handle.get_core_id() != self.core_id {
+			// The task has migrated in the meantime.
+			get_scheduler(handle.get_core_id())
+				.input
+				.lock()
+				.priority_requests
+				.push_back(id);
+			arch::wakeup_core(handle.get_core_id());
+			return false;
+		}
+
+		if self.current_task.borrow().id == id {
+			let mut current_task_borrowed = self.current_task.borrow_mut();
+			let lowered = prio < current_task_borrowed.prio;
+			current_task_borrowed.prio = prio;
+			drop(current_task_borrowed);
+			self.update_timeslice();
+			return lowered;
+		}
+
+		if let Some(task) = self.ready_queue.pop_if(|task| task.borrow().id == id) {
+			task.borrow_mut().prio = prio;
+			self.ready_queue.push(task);
+			self.update_timeslice();
+		} else if !self.blocked_tasks.set_priority(id, prio) {
+			debug!("Unable to find task {} to change its priority", id);
+		}
+
+		false
+	}
+
+	/// Restrict the task `id` to the cores in `affinity`.
+	///
+	/// If the task runs on a core outside of `affinity`, it migrates to
+	/// another core. For a task of another core, the request is forwarded
+	/// to that core.
+	pub fn set_affinity(&mut self, id: TaskId, affinity: CpuSet) -> Result<(), ()> {
+		trace!("Change affinity of task {} to {:?}", id, affinity);
+
+		if !(0..arch::get_processor_count()).any(|core_id| affinity.contains(core_id)) {
+			return Err(());
+		}
+
+		#[cfg(feature = "smp")]
+		irqsave(|| -> Result<(), ()> {
+			let core_id = get_task_handle(id).ok_or(())?.get_core_id();
+
+			if core_id == self.core_id {
+				self.apply_affinity(id, affinity);
+				self.migrate_misplaced_tasks();
 			} else {
-				self.ready_queue
-					.set_priority(task, prio)
-					.expect("Do not find valid task in ready queue");
+				get_scheduler(core_id)
+					.input
+					.lock()
+					.affinity_requests
+					.push_back((id, affinity));
+				arch::wakeup_core(core_id);
 			}
 
 			Ok(())
-		})
+		})?;
+
+		#[cfg(not(feature = "smp"))]
+		get_task_handle(id).ok_or(())?;
+
+		// The current task has to leave this core
+		#[cfg(feature = "smp")]
+		if self.is_scheduling() {
+			self.reschedule();
+		}
+
+		Ok(())
+	}
+
+	/// Change the affinity of the task `id`, which belongs to this core.
+	#[cfg(feature = "smp")]
+	fn apply_affinity(&mut self, id: TaskId, affinity: CpuSet) {
+		let core_id = match get_task_handle(id) {
+			Some(handle) => handle.get_core_id(),
+			None => return,
+		};
+
+		if core_id != self.core_id {
+			// The task has migrated in the meantime.
+			get_scheduler(core_id)
+				.input
+				.lock()
+				.affinity_requests
+				.push_back((id, affinity));
+			arch::wakeup_core(core_id);
+			return;
+		}
+
+		// The task may still wait in our input queue after migrating to this core.
+		self.receive_tasks();
+
+		if self.current_task.borrow().id == id {
+			// The scheduler moves the task after switching to another task.
+			self.current_task.borrow_mut().affinity = affinity;
+		} else if let Some(task) = self.ready_queue.pop_if(|task| task.borrow().id == id) {
+			task.borrow_mut().affinity = affinity;
+			self.ready_queue.push(task);
+			if !affinity.contains(self.core_id) {
+				self.misplaced_tasks = true;
+			}
+		} else if let Some((task, wakeup_time)) = self.blocked_tasks.remove(id) {
+			task.borrow_mut().affinity = affinity;
+			// Like a ready task, the FPU owner leaves this core after running once more.
+			let stays = affinity.contains(self.core_id) || Rc::ptr_eq(&task, &self.fpu_owner);
+			match self.select_core(affinity) {
+				Some(core_id) if !stays => self.send_task(task, core_id, wakeup_time),
+				_ => self.blocked_tasks.insert(task, wakeup_time),
+			}
+		}
+	}
+
+	/// Returns the cores, on which the task `id` is allowed to run.
+	///
+	/// Only the core of a task knows its affinity. For a task of another core,
+	/// the current task blocks until that core has answered.
+	#[cfg(feature = "smp")]
+	pub fn get_affinity(&mut self, id: TaskId) -> Option<CpuSet> {
+		// The task isn't able to leave this core, while interrupts are disabled.
+		let core_id = match irqsave(|| -> Result<CoreId, Option<CpuSet>> {
+			let core_id = get_task_handle(id).ok_or(None)?.get_core_id();
+			if core_id == self.core_id {
+				Err(self.local_affinity(id))
+			} else {
+				Ok(core_id)
+			}
+		}) {
+			Ok(core_id) => core_id,
+			Err(affinity) => return affinity,
+		};
+
+		let answer = AffinityAnswer::default();
+		let requester = self.get_current_task_handle();
+		// The current task blocks before sending the query, so that the
+		// other core is able to wake it up immediately.
+		self.block_current_task(None);
+		get_scheduler(core_id)
+			.input
+			.lock()
+			.affinity_queries
+			.push_back((id, requester, answer.clone()));
+		arch::wakeup_core(core_id);
+		self.reschedule();
+
+		let affinity = *answer.lock();
+		affinity
+	}
+
+	/// Answer the query of `requester` for the affinity of the task `id`,
+	/// which belongs to this core.
+	#[cfg(feature = "smp")]
+	fn answer_affinity_query(&mut self, id: TaskId, requester: TaskHandle, answer: AffinityAnswer) {
+		match get_task_handle(id) {
+			Some(handle) if handle.get_core_id() != self.core_id => {
+				// The task has migrated in the meantime.
+				let core_id = handle.get_core_id();
+				get_scheduler(core_id)
+					.input
+					.lock()
+					.affinity_queries
+					.push_back((id, requester, answer));
+				arch::wakeup_core(core_id);
+			}
+			_ => {
+				*answer.lock() = self.local_affinity(id);
+				self.custom_wakeup(requester);
+			}
+		}
+	}
+
+	/// Returns the affinity of the task `id`, which belongs to this core.
+	#[cfg(feature = "smp")]
+	fn local_affinity(&mut self, id: TaskId) -> Option<CpuSet> {
+		self.receive_tasks();
+
+		[&self.current_task, &self.idle_task]
+			.into_iter()
+			.find(|task| task.borrow().id == id)
+			.cloned()
+			.or_else(|| self.ready_queue.find(|task| task.borrow().id == id))
+			.or_else(|| self.blocked_tasks.get(id))
+			.map(|task| task.borrow().affinity)
 	}
 
 	/// Save the FPU context for the current FPU owner and restore it for the current task,
@@ -390,6 +772,7 @@ impl PerCoreScheduler {
 		while let Some(finished_task) = self.finished_tasks.pop_front() {
 			let borrowed = finished_task.borrow();
 			debug!("Cleaning up task {}", borrowed.id);
+			INHERITED_PRIORITIES.lock().remove(&borrowed.id);
 
 			// wakeup tasks, which are waiting for task with the identifier id
 			if let Some(mut queue) = WAITING_TASKS.lock().remove(&borrowed.id) {
@@ -405,14 +788,256 @@ impl PerCoreScheduler {
 
 	#[cfg(feature = "smp")]
 	pub fn check_input(&mut self) {
-		let mut input_locked = self.input.lock();
+		// Migrated tasks are received first, because they are already able to
+		// receive wakeups, which are sent to this core.
+		self.receive_tasks();
 
-		while let Some(task) = input_locked.wakeup_tasks.pop_front() {
-			self.blocked_tasks.custom_wakeup(task);
+		let (wakeup_tasks, priority_requests, affinity_requests, affinity_queries, steal_requests) = {
+			let mut input_locked = self.input.lock();
+			(
+				core::mem::take(&mut input_locked.wakeup_tasks),
+				core::mem::take(&mut input_locked.priority_requests),
+				core::mem::take(&mut input_locked.affinity_requests),
+				core::mem::take(&mut input_locked.affinity_queries),
+				core::mem::take(&mut input_locked.steal_requests),
+			)
+		};
+
+		// The requests are handled without holding our own lock, because they
+		// may be forwarded to the input queues of other cores.
+		for task in wakeup_tasks {
+			self.custom_wakeup(task);
 		}
 
+		// The wakeup handler reschedules, if the current task has lowered its priority.
+		for id in priority_requests {
+			self.change_priority(id);
+		}
+
+		for (id, affinity) in affinity_requests {
+			self.apply_affinity(id, affinity);
+		}
+		self.migrate_misplaced_tasks();
+
+		for (id, requester, answer) in affinity_queries {
+			self.answer_affinity_query(id, requester, answer);
+		}
+
+		for core_id in steal_requests {
+			self.migrate_task(core_id);
+			get_scheduler(core_id)
+				.stealing
+				.store(false, Ordering::Relaxed);
+		}
+
+		self.publish_load();
+	}
+
+	/// Move the new and blocked tasks, which other cores have sent to this
+	/// core, from the input queue to our own queues.
+	#[cfg(feature = "smp")]
+	fn receive_tasks(&mut self) {
+		let mut input_locked = self.input.lock();
+
 		while let Some(task) = input_locked.new_tasks.pop_front() {
-			self.ready_queue.push(task.clone());
+			self.ready_queue.push(task);
+		}
+
+		while let Some((task, wakeup_time)) = input_locked.blocked_tasks.pop_front() {
+			self.blocked_tasks.insert(task, wakeup_time);
+		}
+		drop(input_locked);
+
+		self.update_timeslice();
+	}
+
+	/// Starts the timeslice of the current task, if a ready task with at least
+	/// the same priority competes for the CPU. Otherwise, the timeslice is
+	/// disabled, so that the timer doesn't interrupt a busy core periodically.
+	fn update_timeslice(&mut self) {
+		let contended = {
+			let current_task_borrowed = self.current_task.borrow();
+			current_task_borrowed.status == TaskStatus::Running
+				&& self
+					.ready_queue
+					.has_task_with_prio(current_task_borrowed.prio)
+		};
+
+		match (contended, self.blocked_tasks.get_timeslice_end()) {
+			(true, None) => {
+				let now = arch::processor::get_timer_ticks();
+				self.blocked_tasks
+					.set_timeslice_end(timeslice().map(|timeslice| now + timeslice));
+			}
+			(false, Some(_)) => self.blocked_tasks.set_timeslice_end(None),
+			_ => {}
+		}
+	}
+
+	/// Publish the load of this core, which the other cores use to
+	/// decide on migrating tasks.
+	#[cfg(feature = "smp")]
+	fn publish_load(&self) {
+		let running = usize::from(self.current_task.borrow().status == TaskStatus::Running);
+		// The FPU state of the FPU owner is still in the registers of this core.
+		// Consequently, the FPU owner isn't able to migrate.
+		let fpu_owner_waits = {
+			let fpu_owner = self.fpu_owner.borrow();
+			fpu_owner.status == TaskStatus::Ready && fpu_owner.may_migrate()
+		};
+
+		self.load
+			.store(self.ready_queue.len() + running, Ordering::Relaxed);
+		self.migratable_tasks.store(
+			self.ready_queue
+				.migratable()
+				.saturating_sub(usize::from(fpu_owner_waits)),
+			Ordering::Relaxed,
+		);
+	}
+
+	/// Move a ready task, which is allowed to run on the core `core_id`, to this core.
+	/// Returns `false` if no task is able to migrate.
+	#[cfg(feature = "smp")]
+	fn migrate_task(&mut self, core_id: CoreId) -> bool {
+		let fpu_owner = &self.fpu_owner;
+		let task = self.ready_queue.pop_if(|task| {
+			!Rc::ptr_eq(task, fpu_owner) && task.borrow().affinity.contains(core_id)
+		});
+
+		if let Some(task) = task {
+			self.send_task(task, core_id, None);
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Move the ready tasks, which aren't allowed to run on this core, to other cores.
+	#[cfg(feature = "smp")]
+	fn migrate_misplaced_tasks(&mut self) {
+		if !self.misplaced_tasks {
+			return;
+		}
+		self.misplaced_tasks = false;
+
+		// The FPU state of the FPU owner is still in the registers of this core.
+		// Therefore, it runs once more and leaves this core afterwards.
+		let fpu_owner = &self.fpu_owner;
+		let this_core = self.core_id;
+		while let Some(task) = self.ready_queue.pop_if(|task| {
+			!Rc::ptr_eq(task, fpu_owner) && !task.borrow().affinity.contains(this_core)
+		}) {
+			let affinity = task.borrow().affinity;
+			if let Some(core_id) = self.select_core(affinity) {
+				self.send_task(task, core_id, None);
+			} else {
+				// None of the cores is available yet, try it again later.
+				self.ready_queue.push(task);
+				self.misplaced_tasks = true;
+				break;
+			}
+		}
+	}
+
+	/// Returns the core with the lowest load, which differs from this core
+	/// and is part of `affinity`.
+	#[cfg(feature = "smp")]
+	fn select_core(&self, affinity: CpuSet) -> Option<CoreId> {
+		unsafe { SCHEDULERS.iter() }
+			.filter(|scheduler| {
+				scheduler.core_id != self.core_id && affinity.contains(scheduler.core_id)
+			})
+			.min_by_key(|scheduler| scheduler.load.load(Ordering::Relaxed))
+			.map(|scheduler| scheduler.core_id)
+	}
+
+	/// Move `task` from this core to the core `core_id`. A blocked task stays
+	/// blocked on the other core until `wakeup_time`.
+	#[cfg(feature = "smp")]
+	fn send_task(&self, task: Rc<RefCell<Task>>, core_id: CoreId, wakeup_time: Option<u64>) {
+		let (tid, is_blocked) = {
+			let mut borrowed = task.borrow_mut();
+			borrowed.core_id = core_id;
+			(borrowed.id, borrowed.status == TaskStatus::Blocked)
+		};
+
+		debug!(
+			"Migrating task {} from core {} to core {}",
+			tid, self.core_id, core_id
+		);
+
+		let scheduler = get_scheduler(core_id);
+		{
+			let mut input_locked = scheduler.input.lock();
+			// Wakeups of this task have to be sent to its new core.
+			if let Some(handle) = TASKS.lock().get_mut(&tid) {
+				handle.set_core_id(core_id);
+			}
+			if is_blocked {
+				input_locked.blocked_tasks.push_back((task, wakeup_time));
+			} else {
+				input_locked.new_tasks.push_back(task);
+			}
+		}
+		if !is_blocked {
+			// Count the task until the other core publishes its own load.
+			scheduler.load.fetch_add(1, Ordering::Relaxed);
+		}
+		arch::wakeup_core(core_id);
+	}
+
+	/// Ask the core with the most migratable tasks for one of them.
+	/// Only the idle task should call this function.
+	#[cfg(feature = "smp")]
+	fn steal_task(&self) {
+		if self.stealing.load(Ordering::Relaxed) {
+			return;
+		}
+
+		let victim = unsafe { SCHEDULERS.iter() }
+			.filter(|scheduler| scheduler.core_id != self.core_id)
+			.max_by_key(|scheduler| scheduler.migratable_tasks.load(Ordering::Relaxed));
+
+		if let Some(victim) = victim {
+			if victim.migratable_tasks.load(Ordering::Relaxed) > 0 {
+				debug!(
+					"Core {} tries to steal a task from core {}",
+					self.core_id, victim.core_id
+				);
+				self.stealing.store(true, Ordering::Relaxed);
+				victim.input.lock().steal_requests.push_back(self.core_id);
+				arch::wakeup_core(victim.core_id);
+			}
+		}
+	}
+
+	/// Periodically move tasks to the core with the lowest load, until
+	/// both cores have roughly the same number of tasks.
+	#[cfg(feature = "smp")]
+	fn balance_load(&mut self) {
+		let now = arch::processor::get_timer_ticks();
+		if now < self.next_balance {
+			return;
+		}
+		self.next_balance = now + BALANCE_INTERVAL;
+
+		let target = unsafe { SCHEDULERS.iter() }
+			.filter(|scheduler| scheduler.core_id != self.core_id)
+			.min_by_key(|scheduler| scheduler.load.load(Ordering::Relaxed));
+
+		if let Some(target) = target {
+			let load = self.load.load(Ordering::Relaxed);
+			let target_load = target.load.load(Ordering::Relaxed);
+
+			if load > target_load + 1 {
+				for _ in 0..(load - target_load) / 2 {
+					if !self.migrate_task(target.core_id) {
+						break;
+					}
+				}
+				self.publish_load();
+			}
 		}
 	}
 
@@ -441,6 +1066,9 @@ impl PerCoreScheduler {
 			// This atomic operation guarantees that we cannot miss a wakeup interrupt in between.
 			if !wakeup_tasks {
 				if backoff.is_completed() {
+					// Before halting, ask a busy core for work. Its answer wakes us up.
+					#[cfg(feature = "smp")]
+					self.steal_task();
 					irq::enable_and_wait();
 				} else {
 					irq::enable();
@@ -461,6 +1089,13 @@ impl PerCoreScheduler {
 		// => we have time to cleanup the system
 		let _ = self.cleanup_tasks();
 
+		#[cfg(feature = "smp")]
+		{
+			self.migrate_misplaced_tasks();
+			self.publish_load();
+			self.balance_load();
+		}
+
 		// Get information about the current task.
 		let (id, last_stack_pointer, prio, status) = {
 			let mut borrowed = self.current_task.borrow_mut();
@@ -472,9 +1107,16 @@ impl PerCoreScheduler {
 			)
 		};
 
+		// A running task, which isn't allowed to run on this core anymore, has to give up the CPU.
+		#[cfg(feature = "smp")]
+		let leaving = status == TaskStatus::Running
+			&& !self.current_task.borrow().affinity.contains(self.core_id);
+		#[cfg(not(feature = "smp"))]
+		let leaving = false;
+
 		let mut new_task = None;
 
-		if status == TaskStatus::Running {
+		if status == TaskStatus::Running && !leaving {
 			// A task is currently running.
 			// Check if a task with a equal or higher priority is available.
 			if let Some(task) = self.ready_queue.pop_with_prio(prio) {
@@ -507,6 +1149,18 @@ impl PerCoreScheduler {
 			if status == TaskStatus::Running {
 				// Mark the running task as ready again and add it back to the queue.
 				self.current_task.borrow_mut().status = TaskStatus::Ready;
+
+				// The task migrates after we have switched to the new task.
+				#[cfg(feature = "smp")]
+				if leaving {
+					// The FPU state has to be in memory to restore it on another core.
+					if Rc::ptr_eq(&self.current_task, &self.fpu_owner) {
+						self.fpu_owner.borrow_mut().last_fpu_state.save();
+						self.fpu_owner = self.idle_task.clone();
+					}
+					self.misplaced_tasks = true;
+				}
+
 				self.ready_queue.push(self.current_task.clone());
 			}
 
@@ -534,7 +1188,16 @@ impl PerCoreScheduler {
 					unsafe { *last_stack_pointer },
 					new_stack_pointer
 				);
+				let now = arch::processor::get_timer_ticks();
+				self.update_cpu_time(now);
 				self.current_task = task;
+				self.last_switch.store(now, Ordering::Relaxed);
+				self.idle_running.store(is_idle, Ordering::Relaxed);
+
+				// The timer interrupt preempts the new task at the end of its timeslice,
+				// if another task with the same priority is ready.
+				self.blocked_tasks.set_timeslice_end(None);
+				self.update_timeslice();
 
 				// Finally save our current context and restore the context of the new task.
 				if is_idle || Rc::ptr_eq(&self.current_task, &self.fpu_owner) {
@@ -546,15 +1209,35 @@ impl PerCoreScheduler {
 						switch_to_task(last_stack_pointer, new_stack_pointer.as_usize());
 					}
 				}
+
+				// The previous task is completely switched out now. As we may continue
+				// on another core, `self` isn't valid anymore.
+				#[cfg(feature = "smp")]
+				core_scheduler().migrate_misplaced_tasks();
 			}
 
 			false
 		} else {
+			if status == TaskStatus::Running {
+				// No task of the same priority is ready, so the timeslice isn't required.
+				self.update_timeslice();
+			}
+
 			status == TaskStatus::Idle
 		}
 	}
 }
 
+/// Returns the length of a timeslice (in microseconds) or `None`, if tasks
+/// of the same priority aren't preempted. A timeslice only runs, while
+/// another task with the same priority is ready.
+fn timeslice() -> Option<u64> {
+	match env::timeslice().unwrap_or(DEFAULT_TIMESLICE) {
+		0 => None,
+		timeslice => Some(timeslice),
+	}
+}
+
 fn get_tid() -> TaskId {
 	static TID_COUNTER: AtomicU32 = AtomicU32::new(0);
 	let guard = TASKS.lock();
@@ -604,8 +1287,21 @@ pub fn add_current_core() {
 		ready_queue: PriorityTaskQueue::new(),
 		finished_tasks: VecDeque::new(),
 		blocked_tasks: BlockedTaskQueue::new(),
+		last_switch: AtomicU64::new(arch::processor::get_timer_ticks()),
+		idle_running: AtomicBool::new(true),
+		idle_time: AtomicU64::new(0),
 		#[cfg(feature = "smp")]
 		input: SpinlockIrqSave::new(SchedulerInput::new()),
+		#[cfg(feature = "smp")]
+		load: AtomicUsize::new(0),
+		#[cfg(feature = "smp")]
+		migratable_tasks: AtomicUsize::new(0),
+		#[cfg(feature = "smp")]
+		stealing: AtomicBool::new(false),
+		#[cfg(feature = "smp")]
+		next_balance: 0,
+		#[cfg(feature = "smp")]
+		misplaced_tasks: false,
 	});
 
 	let scheduler = Box::into_raw(boxed_scheduler);
@@ -656,6 +1352,53 @@ pub fn join(id: TaskId) -> Result<(), ()> {
 	Ok(())
 }
 
+/// Returns the priority of the task `id`.
+pub fn get_priority(id: TaskId) -> Option<Priority> {
+	get_task_handle(id).map(|handle| handle.get_priority())
+}
+
+/// Returns the CPU time of all tasks (in microseconds)
+pub fn get_cpu_time() -> u64 {
+	let now = arch::processor::get_timer_ticks();
+	// Add the time of the tasks, which are running right now.
+	let running: u64 = unsafe { SCHEDULERS.iter() }
+		.filter(|scheduler| !scheduler.idle_running.load(Ordering::Relaxed))
+		.map(|scheduler| now.saturating_sub(scheduler.last_switch.load(Ordering::Relaxed)))
+		.sum();
+
+	CPU_TIME.load(Ordering::Relaxed) + running
+}
+
+/// Returns the time, which the core `core_id` has spent in its idle task (in microseconds)
+pub fn get_idle_time(core_id: CoreId) -> Option<u64> {
+	let scheduler = unsafe { SCHEDULERS.get(usize::try_from(core_id).unwrap()) }?;
+	let mut idle_time = scheduler.idle_time.load(Ordering::Relaxed);
+
+	if scheduler.idle_running.load(Ordering::Relaxed) {
+		let now = arch::processor::get_timer_ticks();
+		idle_time += now.saturating_sub(scheduler.last_switch.load(Ordering::Relaxed));
+	}
+
+	Some(idle_time)
+}
+
+/// Returns the cores, on which the task `id` is allowed to run.
+#[cfg(feature = "smp")]
+pub fn get_affinity(id: TaskId) -> Option<CpuSet> {
+	core_scheduler().get_affinity(id)
+}
+
+/// Returns the cores, on which the task `id` is allowed to run.
+#[cfg(not(feature = "smp"))]
+pub fn get_affinity(id: TaskId) -> Option<CpuSet> {
+	get_task_handle(id).map(|_| CpuSet::single(0))
+}
+
+/// Returns `true` if the task `id` exists.
+pub fn task_exists(id: TaskId) -> bool {
+	get_task_handle(id).is_some()
+}
+
 fn get_task_handle(id: TaskId) -> Option<TaskHandle> {
 	TASKS.lock().get(&id).copied()
 }
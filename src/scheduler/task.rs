@@ -6,11 +6,9 @@ use crate::scheduler::CoreId;
 use alloc::collections::{LinkedList, VecDeque};
 use alloc::rc::Rc;
 use core::cell::RefCell;
-use core::cmp::Ordering;
+use core::cmp::{self, Ordering};
 use core::fmt;
 use core::num::NonZeroU64;
-#[cfg(feature = "tcp")]
-use core::ops::DerefMut;
 
 /// Returns the most significant bit.
 ///
@@ -87,6 +85,39 @@ pub const IDLE_PRIO: Priority = Priority::from(0);
 /// Maximum number of priorities
 pub const NO_PRIORITIES: usize = 31;
 
+/// Set of cores, on which a task is allowed to run.
+///
+/// Bit `n` of the mask represents the core with the ID `n`. Consequently,
+/// a set is able to select one of the first 64 cores. Only the set of all
+/// cores includes the cores beyond.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct CpuSet(u64);
+
+impl CpuSet {
+	pub const fn from_bits(bits: u64) -> Self {
+		CpuSet(bits)
+	}
+
+	pub const fn into_bits(self) -> u64 {
+		self.0
+	}
+
+	/// Returns the set of all cores
+	pub const fn all() -> Self {
+		CpuSet(u64::MAX)
+	}
+
+	/// Returns the set, which only includes the core `core_id`
+	pub fn single(core_id: CoreId) -> Self {
+		CpuSet(1u64.checked_shl(core_id).unwrap_or(0))
+	}
+
+	/// Returns `true` if the set includes the core `core_id`
+	pub fn contains(self, core_id: CoreId) -> bool {
+		self == Self::all() || self.0 & Self::single(core_id).0 != 0
+	}
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct TaskHandle {
 	id: TaskId,
@@ -110,6 +141,11 @@ impl TaskHandle {
 		self.core_id
 	}
 
+	#[cfg(feature = "smp")]
+	pub fn set_core_id(&mut self, core_id: CoreId) {
+		self.core_id = core_id;
+	}
+
 	pub fn get_id(&self) -> TaskId {
 		self.id
 	}
@@ -117,6 +153,10 @@ impl TaskHandle {
 	pub fn get_priority(&self) -> Priority {
 		self.priority
 	}
+
+	pub fn set_priority(&mut self, priority: Priority) {
+		self.priority = priority;
+	}
 }
 
 impl Ord for TaskHandle {
@@ -187,8 +227,43 @@ impl TaskHandlePriorityQueue {
 		}
 	}
 
+	/// Move the task handles, whose tasks have changed their priority after
+	/// they were added, to the queue of their current priority.
+	fn update_priorities(&mut self) {
+		let mut changed = VecDeque::new();
+		{
+			let tasks = super::TASKS.lock();
+			let mut bitmap = self.prio_bitmap;
+
+			while let Some(i) = msb(bitmap) {
+				let i = i as usize;
+				if let Some(queue) = &mut self.queues[i] {
+					queue.retain(|handle| match tasks.get(&handle.id) {
+						Some(task) if task.priority != handle.priority => {
+							changed.push_back(*task);
+							false
+						}
+						_ => true,
+					});
+
+					if queue.is_empty() {
+						self.prio_bitmap &= !(1 << i as u64);
+					}
+				}
+
+				bitmap &= !(1 << i as u64);
+			}
+		}
+
+		for task in changed {
+			self.push(task);
+		}
+	}
+
 	/// Pop the task handle with the highest priority from the queue
 	pub fn pop(&mut self) -> Option<TaskHandle> {
+		self.update_priorities();
+
 		if let Some(i) = msb(self.prio_bitmap) {
 			return self.pop_from_queue(i as usize);
 		}
@@ -197,23 +272,23 @@ impl TaskHandlePriorityQueue {
 	}
 
 	/// Remove a specific task handle from the priority queue.
+	///
+	/// The priority of the task may have changed after adding it to the queue.
+	/// Therefore, all priorities are searched for the task.
 	pub fn remove(&mut self, task: TaskHandle) {
-		let queue_index = task.priority.into() as usize;
-		//assert!(queue_index < NO_PRIORITIES, "Priority {} is too high", queue_index);
+		let mut bitmap = self.prio_bitmap;
 
-		if let Some(queue) = &mut self.queues[queue_index] {
-			let mut i = 0;
-			while i != queue.len() {
-				if queue[i].id == task.id {
-					queue.remove(i);
-				} else {
-					i += 1;
+		while let Some(queue_index) = msb(bitmap) {
+			let queue_index = queue_index as usize;
+			if let Some(queue) = &mut self.queues[queue_index] {
+				queue.retain(|handle| handle.id != task.id);
+
+				if queue.is_empty() {
+					self.prio_bitmap &= !(1 << queue_index as u64);
 				}
 			}
 
-			if queue.is_empty() {
-				self.prio_bitmap &= !(1 << queue_index as u64);
-			}
+			bitmap &= !(1 << queue_index as u64);
 		}
 	}
 }
@@ -237,6 +312,10 @@ impl QueueHead {
 pub struct PriorityTaskQueue {
 	queues: [QueueHead; NO_PRIORITIES],
 	prio_bitmap: u64,
+	/// Number of tasks in the queue
+	len: usize,
+	/// Number of tasks in the queue, which are allowed to migrate to another core
+	migratable: usize,
 }
 
 impl PriorityTaskQueue {
@@ -246,14 +325,33 @@ impl PriorityTaskQueue {
 		PriorityTaskQueue {
 			queues: [QUEUE_HEAD; NO_PRIORITIES],
 			prio_bitmap: 0,
+			len: 0,
+			migratable: 0,
 		}
 	}
 
+	/// Returns the number of tasks in the queue
+	#[cfg(feature = "smp")]
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	/// Returns the number of tasks in the queue, which are allowed to run on another core
+	#[cfg(feature = "smp")]
+	pub fn migratable(&self) -> usize {
+		self.migratable
+	}
+
 	/// Add a task by its priority to the queue
 	pub fn push(&mut self, task: Rc<RefCell<Task>>) {
 		let i = task.borrow().prio.into() as usize;
 		//assert!(i < NO_PRIORITIES, "Priority {} is too high", i);
 
+		self.len += 1;
+		if task.borrow().may_migrate() {
+			self.migratable += 1;
+		}
+
 		self.prio_bitmap |= (1 << i) as u64;
 		match self.queues[i].tail {
 			None => {
@@ -292,6 +390,11 @@ impl PriorityTaskQueue {
 
 			let task = head.clone();
 
+			self.len -= 1;
+			if borrow.may_migrate() {
+				self.migratable -= 1;
+			}
+
 			(new_head, task)
 		};
 
@@ -324,6 +427,11 @@ impl PriorityTaskQueue {
 		None
 	}
 
+	/// Returns `true` if a task with a priority of at least `prio` is available
+	pub fn has_task_with_prio(&self, prio: Priority) -> bool {
+		msb(self.prio_bitmap).map_or(false, |i| i >= u32::from(prio.into()))
+	}
+
 	/// Returns the highest priority of all available task
 	#[cfg(feature = "smp")]
 	pub fn get_highest_priority(&self) -> Priority {
@@ -334,52 +442,95 @@ impl PriorityTaskQueue {
 		}
 	}
 
-	/// Change priority of specific task
-	pub fn set_priority(&mut self, handle: TaskHandle, prio: Priority) -> Result<(), ()> {
-		let i = handle.get_priority().into() as usize;
-		let mut pos = self.queues[i].head.as_mut().ok_or(())?;
+	/// Remove `task` from the queue with index `queue_index`
+	fn unlink(&mut self, queue_index: usize, task: &Rc<RefCell<Task>>) {
+		let mut borrow = task.borrow_mut();
 
-		loop {
-			if handle.id == pos.borrow().id {
-				let task = pos.clone();
+		let new = borrow.next.as_ref().cloned();
+		if let Some(prev) = borrow.prev.as_mut() {
+			prev.borrow_mut().next = new;
+		}
 
-				// Extract found task from queue and set new priority
-				{
-					let mut borrow = task.borrow_mut();
+		let new = borrow.prev.as_ref().cloned();
+		if let Some(next) = borrow.next.as_mut() {
+			next.borrow_mut().prev = new;
+		}
 
-					let new = borrow.next.as_ref().cloned();
-					if let Some(prev) = borrow.prev.as_mut() {
-						prev.borrow_mut().next = new;
-					}
+		if borrow.prev.as_mut().is_none() {
+			// Ok, the task is head of the list
+			self.queues[queue_index].head = borrow.next.as_ref().cloned();
+		}
 
-					let new = borrow.prev.as_ref().cloned();
-					if let Some(next) = borrow.next.as_mut() {
-						next.borrow_mut().prev = new;
-					}
+		if borrow.next.as_mut().is_none() {
+			// Ok, the task is tail of the list
+			self.queues[queue_index].tail = borrow.prev.as_ref().cloned();
+		}
 
-					if borrow.prev.as_mut().is_none() {
-						// Ok, the task is head of the list
-						self.queues[i].head = borrow.next.as_ref().cloned();
-					}
+		if self.queues[queue_index].head.is_none() {
+			self.prio_bitmap &= !(1 << queue_index as u64);
+		}
 
-					if borrow.next.as_mut().is_none() {
-						// Ok, the task is tail of the list
-						self.queues[i].tail = borrow.prev.as_ref().cloned();
-					}
+		borrow.next = None;
+		borrow.prev = None;
+
+		self.len -= 1;
+		if borrow.may_migrate() {
+			self.migratable -= 1;
+		}
+	}
 
-					borrow.prio = prio;
-					borrow.next = None;
-					borrow.prev = None;
+	/// Pop a task, for which `f` returns `true`.
+	///
+	/// Starting with the highest priority, the tasks are checked from the end
+	/// of the queue, because these would be the last to run on this core.
+	pub fn pop_if<F>(&mut self, f: F) -> Option<Rc<RefCell<Task>>>
+	where
+		F: Fn(&Rc<RefCell<Task>>) -> bool,
+	{
+		let mut bitmap = self.prio_bitmap;
+
+		while let Some(i) = msb(bitmap) {
+			let i = i as usize;
+			let mut pos = self.queues[i].tail.clone();
+
+			while let Some(task) = pos {
+				if f(&task) {
+					self.unlink(i, &task);
+					return Some(task);
 				}
 
-				self.push(task);
+				pos = task.borrow().prev.clone();
+			}
+
+			bitmap &= !(1 << i as u64);
+		}
+
+		None
+	}
+
+	/// Returns a task, for which `f` returns `true`, without removing it from the queue.
+	#[cfg(feature = "smp")]
+	pub fn find<F>(&self, f: F) -> Option<Rc<RefCell<Task>>>
+	where
+		F: Fn(&Rc<RefCell<Task>>) -> bool,
+	{
+		let mut bitmap = self.prio_bitmap;
+
+		while let Some(i) = msb(bitmap) {
+			let mut pos = self.queues[i as usize].head.clone();
+
+			while let Some(task) = pos {
+				if f(&task) {
+					return Some(task);
+				}
 
-				return Ok(());
+				pos = task.borrow().next.clone();
 			}
 
-			let ptr = pos.as_ptr();
-			pos = unsafe { (*ptr).next.as_mut().ok_or(())? };
+			bitmap &= !(1 << i as u64);
 		}
+
+		None
 	}
 }
 
@@ -404,6 +555,10 @@ pub struct Task {
 	pub last_fpu_state: arch::processor::FPUState,
 	/// ID of the core this task is running on
 	pub core_id: CoreId,
+	/// Cores, on which the task is allowed to run
+	pub affinity: CpuSet,
+	/// Time, which the task has spent running until its last switch (in microseconds)
+	pub cpu_time: u64,
 	/// Stack of the task
 	pub stacks: TaskStacks,
 	/// next task in queue
@@ -440,6 +595,8 @@ impl Task {
 			user_stack_pointer: VirtAddr(0u64),
 			last_fpu_state: arch::processor::FPUState::new(),
 			core_id,
+			affinity: CpuSet::all(),
+			cpu_time: 0,
 			stacks: TaskStacks::new(stack_size),
 			next: None,
 			prev: None,
@@ -449,6 +606,11 @@ impl Task {
 		}
 	}
 
+	/// Returns `true` if the task is allowed to run on another core
+	pub fn may_migrate(&self) -> bool {
+		self.affinity.into_bits() & !CpuSet::single(self.core_id).into_bits() != 0
+	}
+
 	pub fn new_idle(tid: TaskId, core_id: CoreId) -> Task {
 		debug!("Creating idle task {}", tid);
 
@@ -460,6 +622,8 @@ impl Task {
 			user_stack_pointer: VirtAddr(0u64),
 			last_fpu_state: arch::processor::FPUState::new(),
 			core_id,
+			affinity: CpuSet::single(core_id),
+			cpu_time: 0,
 			stacks: TaskStacks::from_boot_stacks(),
 			next: None,
 			prev: None,
@@ -484,6 +648,8 @@ impl Task {
 			user_stack_pointer: VirtAddr(0u64),
 			last_fpu_state: arch::processor::FPUState::new(),
 			core_id,
+			affinity: CpuSet::all(),
+			cpu_time: 0,
 			stacks: TaskStacks::new(task.stacks.get_user_stack_size()),
 			next: None,
 			prev: None,
@@ -515,6 +681,8 @@ pub struct BlockedTaskQueue {
 	list: LinkedList<BlockedTask>,
 	#[cfg(feature = "tcp")]
 	network_wakeup_time: Option<u64>,
+	/// End of the timeslice of the running task
+	timeslice_end: Option<u64>,
 }
 
 impl BlockedTaskQueue {
@@ -523,9 +691,39 @@ impl BlockedTaskQueue {
 			list: LinkedList::new(),
 			#[cfg(feature = "tcp")]
 			network_wakeup_time: None,
+			timeslice_end: None,
 		}
 	}
 
+	/// Programs the One-Shot Timer to fire at `wakeup_time`, but not after the end of
+	/// the timeslice.
+	fn set_oneshot_timer(wakeup_time: Option<u64>, timeslice_end: Option<u64>) {
+		let wakeup_time = match (wakeup_time, timeslice_end) {
+			(Some(wt), Some(end)) => Some(cmp::min(wt, end)),
+			(wt, end) => wt.or(end),
+		};
+		arch::set_oneshot_timer(wakeup_time);
+	}
+
+	/// Sets the end of the timeslice of the running task, or disables the timeslice if
+	/// `None` is given.
+	pub fn set_timeslice_end(&mut self, timeslice_end: Option<u64>) {
+		self.timeslice_end = timeslice_end;
+
+		let wakeup_time = self.list.front().and_then(|node| node.wakeup_time);
+		#[cfg(feature = "tcp")]
+		let wakeup_time = match (wakeup_time, self.network_wakeup_time) {
+			(Some(wt), Some(network_wakeup_time)) => Some(cmp::min(wt, network_wakeup_time)),
+			(wt, network_wakeup_time) => wt.or(network_wakeup_time),
+		};
+		Self::set_oneshot_timer(wakeup_time, timeslice_end);
+	}
+
+	/// Returns the end of the timeslice of the running task.
+	pub fn get_timeslice_end(&self) -> Option<u64> {
+		self.timeslice_end
+	}
+
 	fn wakeup_task(task: Rc<RefCell<Task>>) {
 		{
 			let mut borrowed = task.borrow_mut();
@@ -560,10 +758,10 @@ impl BlockedTaskQueue {
 		let mut cursor = self.list.cursor_front_mut();
 		if let Some(node) = cursor.current() {
 			if node.wakeup_time.is_none() || wakeup_time < node.wakeup_time.unwrap() {
-				arch::set_oneshot_timer(Some(wakeup_time));
+				Self::set_oneshot_timer(Some(wakeup_time), self.timeslice_end);
 			}
 		} else {
-			arch::set_oneshot_timer(Some(wakeup_time));
+			Self::set_oneshot_timer(Some(wakeup_time), self.timeslice_end);
 		}
 	}
 
@@ -583,6 +781,11 @@ impl BlockedTaskQueue {
 			borrowed.status = TaskStatus::Blocked;
 		}
 
+		self.insert(task, wakeup_time);
+	}
+
+	/// Adds a task, which is already blocked, e.g., because it migrated from another core.
+	pub fn insert(&mut self, task: Rc<RefCell<Task>>, wakeup_time: Option<u64>) {
 		let new_node = BlockedTask::new(task, wakeup_time);
 
 		// Shall the task automatically be woken up after a certain time?
@@ -594,17 +797,17 @@ impl BlockedTaskQueue {
 				// to fire when this task shall be woken up.
 				#[cfg(not(feature = "tcp"))]
 				if first_task {
-					arch::set_oneshot_timer(wakeup_time);
+					Self::set_oneshot_timer(wakeup_time, self.timeslice_end);
 				}
 				#[cfg(feature = "tcp")]
 				if first_task {
 					match self.network_wakeup_time {
 						Some(time) => {
 							if time > wt {
-								arch::set_oneshot_timer(wakeup_time);
+								Self::set_oneshot_timer(wakeup_time, self.timeslice_end);
 							}
 						}
-						_ => arch::set_oneshot_timer(wakeup_time),
+						_ => Self::set_oneshot_timer(wakeup_time, self.timeslice_end),
 					}
 				}
 			});
@@ -624,6 +827,46 @@ impl BlockedTaskQueue {
 		self.list.push_back(new_node);
 	}
 
+	/// Change the priority of the blocked task with the identifier `id`.
+	/// Returns `false` if the task isn't blocked on this core.
+	pub fn set_priority(&mut self, id: TaskId, prio: Priority) -> bool {
+		if let Some(node) = self.list.iter().find(|node| node.task.borrow().id == id) {
+			node.task.borrow_mut().prio = prio;
+			true
+		} else {
+			false
+		}
+	}
+
+	/// Returns the blocked task with the identifier `id`.
+	#[cfg(feature = "smp")]
+	pub fn get(&self, id: TaskId) -> Option<Rc<RefCell<Task>>> {
+		self.list
+			.iter()
+			.find(|node| node.task.borrow().id == id)
+			.map(|node| node.task.clone())
+	}
+
+	/// Removes the blocked task with the identifier `id` and returns it with its wakeup time.
+	#[cfg(feature = "smp")]
+	pub fn remove(&mut self, id: TaskId) -> Option<(Rc<RefCell<Task>>, Option<u64>)> {
+		let mut cursor = self.list.cursor_front_mut();
+
+		while let Some(node) = cursor.current() {
+			if node.task.borrow().id == id {
+				// The One-Shot Timer may fire too early now, which is handled like any other
+				// interrupt without elapsed wakeup time.
+				return cursor
+					.remove_current()
+					.map(|node| (node.task, node.wakeup_time));
+			}
+
+			cursor.move_next();
+		}
+
+		None
+	}
+
 	/// Manually wake up a blocked task.
 	pub fn custom_wakeup(&mut self, task: TaskHandle) {
 		let mut first_task = true;
@@ -652,24 +895,27 @@ impl BlockedTaskQueue {
 							if network_wakeup_time
 								<= next_node.wakeup_time.unwrap_or(network_wakeup_time)
 							{
-								arch::set_oneshot_timer(self.network_wakeup_time);
+								Self::set_oneshot_timer(
+									self.network_wakeup_time,
+									self.timeslice_end,
+								);
 							} else {
-								arch::set_oneshot_timer(next_node.wakeup_time);
+								Self::set_oneshot_timer(next_node.wakeup_time, self.timeslice_end);
 							}
 						} else {
-							arch::set_oneshot_timer(next_node.wakeup_time);
+							Self::set_oneshot_timer(next_node.wakeup_time, self.timeslice_end);
 						}
 					} else {
-						arch::set_oneshot_timer(self.network_wakeup_time);
+						Self::set_oneshot_timer(self.network_wakeup_time, self.timeslice_end);
 					}
 				}
 				#[cfg(not(feature = "tcp"))]
 				if first_task {
 					if let Some(next_node) = cursor.current() {
-						arch::set_oneshot_timer(next_node.wakeup_time);
+						Self::set_oneshot_timer(next_node.wakeup_time, self.timeslice_end);
 					} else {
 						// if no task is available, we have to disable the timer
-						arch::set_oneshot_timer(None);
+						Self::set_oneshot_timer(None, self.timeslice_end);
 					}
 				}
 
@@ -692,18 +938,14 @@ impl BlockedTaskQueue {
 
 		#[cfg(feature = "tcp")]
 		if let Ok(mut guard) = crate::net::NIC.try_lock() {
-			if let crate::net::NetworkState::Initialized(nic) = guard.deref_mut() {
-				let time = crate::net::now();
-				nic.poll_common(time);
-				if let Some(delay) = nic.poll_delay(time).map(|d| d.total_micros()) {
-					let wakeup_time = crate::arch::processor::get_timer_ticks() + delay;
-					self.network_wakeup_time = Some(wakeup_time);
-					if cursor.current().is_none() {
-						arch::set_oneshot_timer(self.network_wakeup_time);
-					}
-				} else {
-					self.network_wakeup_time = None;
+			if let Some(delay) = guard.poll(crate::net::now()).map(|d| d.total_micros()) {
+				let wakeup_time = crate::arch::processor::get_timer_ticks() + delay;
+				self.network_wakeup_time = Some(wakeup_time);
+				if cursor.current().is_none() {
+					Self::set_oneshot_timer(self.network_wakeup_time, self.timeslice_end);
 				}
+			} else {
+				self.network_wakeup_time = None;
 			}
 		}
 
@@ -718,15 +960,15 @@ impl BlockedTaskQueue {
 				#[cfg(feature = "tcp")]
 				if let Some(network_wakeup_time) = self.network_wakeup_time {
 					if network_wakeup_time <= node_wakeup_time.unwrap_or(network_wakeup_time) {
-						arch::set_oneshot_timer(self.network_wakeup_time);
+						Self::set_oneshot_timer(self.network_wakeup_time, self.timeslice_end);
 					} else {
-						arch::set_oneshot_timer(node_wakeup_time);
+						Self::set_oneshot_timer(node_wakeup_time, self.timeslice_end);
 					}
 				} else {
-					arch::set_oneshot_timer(node_wakeup_time);
+					Self::set_oneshot_timer(node_wakeup_time, self.timeslice_end);
 				}
 				#[cfg(not(feature = "tcp"))]
-				arch::set_oneshot_timer(node_wakeup_time);
+				Self::set_oneshot_timer(node_wakeup_time, self.timeslice_end);
 
 				break;
 			}
@@ -1,11 +1,13 @@
 use crate::arch::percore::*;
-use crate::scheduler::task::{TaskHandlePriorityQueue, TaskId};
+use crate::scheduler::task::{Priority, TaskHandlePriorityQueue, TaskId};
 use crate::synch::spinlock::Spinlock;
 
 struct RecursiveMutexState {
 	current_tid: Option<TaskId>,
 	count: usize,
 	queue: TaskHandlePriorityQueue,
+	/// Priority, which the holder has inherited from the waiters
+	boost: Option<Priority>,
 }
 
 pub struct RecursiveMutex {
@@ -19,16 +21,18 @@ impl RecursiveMutex {
 				current_tid: None,
 				count: 0,
 				queue: TaskHandlePriorityQueue::new(),
+				boost: None,
 			}),
 		}
 	}
 
 	pub fn acquire(&self) {
 		// Get information about the current task.
-		let core_scheduler = core_scheduler();
-		let tid = core_scheduler.get_current_task_id();
+		let tid = core_scheduler().get_current_task_id();
 
 		loop {
+			// The task may have migrated to another core while it was blocked.
+			let core_scheduler = core_scheduler();
 			{
 				let mut locked_state = self.state.lock();
 
@@ -40,6 +44,17 @@ impl RecursiveMutex {
 						locked_state.count += 1;
 						return;
 					}
+
+					// Priority inheritance: the holder runs at least with our priority
+					// until it releases the mutex.
+					let prio = core_scheduler.get_current_task_prio();
+					if locked_state.boost.map_or(true, |boost| boost < prio)
+						&& core_scheduler.inherit_priority(current_tid, prio).is_ok()
+					{
+						if let Some(boost) = locked_state.boost.replace(prio) {
+							let _ = core_scheduler.disinherit_priority(current_tid, boost);
+						}
+					}
 				} else {
 					// The mutex is currently not acquired, so we become its new owner.
 					locked_state.current_tid = Some(tid);
@@ -61,6 +76,9 @@ impl RecursiveMutex {
 	}
 
 	pub fn release(&self) {
+		let tid = core_scheduler().get_current_task_id();
+		let mut boost = None;
+
 		if let Some(task) = {
 			let mut locked_state = self.state.lock();
 
@@ -75,6 +93,7 @@ impl RecursiveMutex {
 			if locked_state.count == 0 {
 				// Release the entire recursive mutex.
 				locked_state.current_tid = None;
+				boost = locked_state.boost.take();
 
 				locked_state.queue.pop()
 			} else {
@@ -84,5 +103,11 @@ impl RecursiveMutex {
 			// Wake up any task that has been waiting for this mutex.
 			core_scheduler().custom_wakeup(task);
 		}
+
+		// Give back the inherited priority, so that the waiter may run. The
+		// priorities, which we have inherited by other mutexes, remain.
+		if let Some(prio) = boost {
+			let _ = core_scheduler().disinherit_priority(tid, prio);
+		}
 	}
 }
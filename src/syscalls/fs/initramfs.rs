@@ -0,0 +1,514 @@
+//! A read-only filesystem, which serves the files of a tar archive in the
+//! ustar format. The archive is embedded into the kernel image at build time
+//! (see `cargo xtask build --initramfs <DIR>`) and is mounted at `/`.
+//!
+//! Only regular files and directories are supported. Other entries, e.g.
+//! symbolic links or devices, are skipped.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str;
+
+use crate::syscalls::fs::{
+	DirectoryEntry, FileAttr, FileError, FilePerms, PosixFile, PosixFileSystem, SeekWhence,
+	S_IFDIR, S_IFREG,
+};
+
+/// The archive, whose path is given at compile time by HERMIT_INITRAMFS
+pub(super) static ARCHIVE: &[u8] = include_bytes!(env!(
+	"HERMIT_INITRAMFS",
+	"the feature initramfs requires the path of the archive in HERMIT_INITRAMFS"
+));
+
+const BLOCK_SIZE: usize = 512;
+const TYPE_FILE: u8 = b'0';
+/// Regular file of archives, which predate ustar
+const TYPE_OLD_FILE: u8 = b'\0';
+const TYPE_DIRECTORY: u8 = b'5';
+
+/// Mode of directories, which are not part of the archive, but contain its entries
+const DEFAULT_DIR_MODE: u32 = 0o755;
+
+#[derive(Copy, Clone)]
+struct Metadata {
+	inode: u64,
+	mode: u32,
+	mtime: i64,
+}
+
+impl Metadata {
+	fn attr(&self, size: usize) -> FileAttr {
+		FileAttr {
+			st_ino: self.inode,
+			st_nlink: 1,
+			st_mode: self.mode,
+			st_size: size as i64,
+			st_blksize: BLOCK_SIZE as i64,
+			st_blocks: ((size + BLOCK_SIZE - 1) / BLOCK_SIZE) as i64,
+			st_atime: self.mtime,
+			st_mtime: self.mtime,
+			st_ctime: self.mtime,
+			..Default::default()
+		}
+	}
+}
+
+enum Node {
+	File(&'static [u8], Metadata),
+	Directory(Directory),
+}
+
+impl Node {
+	fn attr(&self) -> FileAttr {
+		match self {
+			Node::File(data, metadata) => metadata.attr(data.len()),
+			Node::Directory(dir) => dir.metadata.attr(0),
+		}
+	}
+}
+
+struct Directory {
+	entries: BTreeMap<String, Node>,
+	metadata: Metadata,
+}
+
+fn components(path: &str) -> Result<Vec<&str>, FileError> {
+	let components: Vec<&str> = path
+		.split('/')
+		.filter(|component| !component.is_empty() && *component != ".")
+		.collect();
+	if components.contains(&"..") {
+		return Err(FileError::EINVAL);
+	}
+
+	Ok(components)
+}
+
+/// Returns the content of a text field, which is terminated by NUL or by the end of the field.
+fn text_field(field: &[u8]) -> Result<&str, FileError> {
+	let len = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+	str::from_utf8(&field[..len]).map_err(|_| FileError::EINVAL)
+}
+
+/// Parses a numeric field, which is encoded as octal number and padded with spaces or NULs.
+fn octal_field(field: &[u8]) -> Result<u64, FileError> {
+	let digits = text_field(field)?.trim_matches(' ');
+	if digits.is_empty() {
+		return Ok(0);
+	}
+	u64::from_str_radix(digits, 8).map_err(|_| FileError::EINVAL)
+}
+
+/// The checksum is the sum of all header bytes, where the checksum field counts as spaces.
+fn verify_checksum(header: &[u8]) -> Result<(), FileError> {
+	let expected = octal_field(&header[148..156])?;
+	let sum: u64 = header
+		.iter()
+		.enumerate()
+		.map(|(i, b)| if (148..156).contains(&i) { b' ' } else { *b })
+		.map(u64::from)
+		.sum();
+
+	if sum == expected {
+		Ok(())
+	} else {
+		Err(FileError::EINVAL)
+	}
+}
+
+pub struct TarFs {
+	root: Directory,
+	next_inode: u64,
+}
+
+impl TarFs {
+	/// Builds the directory tree of `archive`, whose files refer to their data in the archive.
+	pub fn new(archive: &'static [u8]) -> Result<Self, FileError> {
+		let mut fs = Self {
+			root: Directory {
+				entries: BTreeMap::new(),
+				metadata: Metadata {
+					inode: 1,
+					mode: S_IFDIR | DEFAULT_DIR_MODE,
+					mtime: 0,
+				},
+			},
+			next_inode: 2,
+		};
+
+		let mut pos = 0;
+		while let Some(header) = archive.get(pos..pos + BLOCK_SIZE) {
+			// the archive ends with blocks of zeros
+			if header.iter().all(|b| *b == 0) {
+				break;
+			}
+			verify_checksum(header)?;
+
+			let size =
+				usize::try_from(octal_field(&header[124..136])?).map_err(|_| FileError::EINVAL)?;
+			let start = pos + BLOCK_SIZE;
+			let data = start
+				.checked_add(size)
+				.and_then(|end| archive.get(start..end))
+				.ok_or(FileError::EINVAL)?;
+			pos = start + (size + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE;
+
+			let name = text_field(&header[0..100])?;
+			// the GNU format stores other fields at the position of the prefix
+			let prefix = if &header[257..263] == b"ustar\0" {
+				text_field(&header[345..500])?
+			} else {
+				""
+			};
+			let path = if prefix.is_empty() {
+				name.to_string()
+			} else {
+				format!("{}/{}", prefix, name)
+			};
+			let metadata = Metadata {
+				inode: 0,
+				mode: (octal_field(&header[100..108])? & 0o7777) as u32,
+				mtime: octal_field(&header[136..148])? as i64,
+			};
+
+			match header[156] {
+				TYPE_FILE | TYPE_OLD_FILE => fs.insert(&path, Some(data), metadata)?,
+				TYPE_DIRECTORY => fs.insert(&path, None, metadata)?,
+				typeflag => debug!(
+					"Skipping {} in the initramfs, whose type {:?} is not supported",
+					path, typeflag as char
+				),
+			}
+		}
+
+		Ok(fs)
+	}
+
+	/// Inserts a file with the content `data` or a directory at `path`.
+	/// Missing parent directories are created.
+	fn insert(
+		&mut self,
+		path: &str,
+		data: Option<&'static [u8]>,
+		mut metadata: Metadata,
+	) -> Result<(), FileError> {
+		let components = components(path)?;
+		let (name, parent) = match components.split_last() {
+			Some(split) => split,
+			// the archive may contain the root directory itself
+			None => return Ok(()),
+		};
+
+		let mut dir = &mut self.root;
+		for component in parent {
+			let next_inode = &mut self.next_inode;
+			let node = dir.entries.entry(component.to_string()).or_insert_with(|| {
+				*next_inode += 1;
+				Node::Directory(Directory {
+					entries: BTreeMap::new(),
+					metadata: Metadata {
+						inode: *next_inode - 1,
+						mode: S_IFDIR | DEFAULT_DIR_MODE,
+						mtime: metadata.mtime,
+					},
+				})
+			});
+			dir = match node {
+				Node::Directory(dir) => dir,
+				Node::File(..) => return Err(FileError::ENOTDIR),
+			};
+		}
+
+		if let Some(node) = dir.entries.get_mut(*name) {
+			return match (node, data) {
+				// a directory may be listed after its content
+				(Node::Directory(dir), None) => {
+					dir.metadata.mode = S_IFDIR | metadata.mode;
+					dir.metadata.mtime = metadata.mtime;
+					Ok(())
+				}
+				_ => Err(FileError::EEXIST),
+			};
+		}
+
+		metadata.inode = self.next_inode;
+		self.next_inode += 1;
+		let node = match data {
+			Some(data) => {
+				metadata.mode |= S_IFREG;
+				Node::File(data, metadata)
+			}
+			None => {
+				metadata.mode |= S_IFDIR;
+				Node::Directory(Directory {
+					entries: BTreeMap::new(),
+					metadata,
+				})
+			}
+		};
+		dir.entries.insert(name.to_string(), node);
+
+		Ok(())
+	}
+
+	/// Returns the directory, which is reached by following `components`.
+	fn lookup_dir(&self, components: &[&str]) -> Result<&Directory, FileError> {
+		let mut dir = &self.root;
+		for component in components {
+			dir = match dir.entries.get(*component) {
+				Some(Node::Directory(dir)) => dir,
+				Some(Node::File(..)) => return Err(FileError::ENOTDIR),
+				None => return Err(FileError::ENOENT),
+			};
+		}
+
+		Ok(dir)
+	}
+}
+
+impl PosixFileSystem for TarFs {
+	fn open(&self, path: &str, perms: FilePerms) -> Result<Box<dyn PosixFile + Send>, FileError> {
+		let components = components(path)?;
+		let (name, parent) = components.split_last().ok_or(FileError::EISDIR)?;
+		let (data, metadata) = match self.lookup_dir(parent)?.entries.get(*name) {
+			Some(Node::File(..)) if perms.creat && perms.excl => return Err(FileError::EEXIST),
+			Some(Node::File(data, metadata)) => (*data, *metadata),
+			Some(Node::Directory(_)) => return Err(FileError::EISDIR),
+			None if perms.creat => return Err(FileError::EROFS),
+			None => return Err(FileError::ENOENT),
+		};
+		if perms.write {
+			return Err(FileError::EROFS);
+		}
+
+		Ok(Box::new(TarFile {
+			data,
+			attr: metadata.attr(data.len()),
+			offset: 0,
+		}))
+	}
+
+	fn unlink(&self, _path: &str) -> Result<(), FileError> {
+		Err(FileError::EROFS)
+	}
+
+	fn mkdir(&self, _path: &str, _mode: u32) -> Result<(), FileError> {
+		Err(FileError::EROFS)
+	}
+
+	fn rmdir(&self, _path: &str) -> Result<(), FileError> {
+		Err(FileError::EROFS)
+	}
+
+	fn rename(&self, _old_path: &str, _new_path: &str) -> Result<(), FileError> {
+		Err(FileError::EROFS)
+	}
+
+	fn opendir(&self, path: &str) -> Result<Box<dyn PosixFile + Send>, FileError> {
+		let components = components(path)?;
+		let parent_inode = match components.split_last() {
+			Some((_, parent)) => self.lookup_dir(parent)?.metadata.inode,
+			None => self.root.metadata.inode,
+		};
+		let dir = self.lookup_dir(&components)?;
+		let attr = dir.metadata.attr(0);
+
+		let mut entries = Vec::with_capacity(dir.entries.len() + 2);
+		for (name, inode) in [(".", attr.st_ino), ("..", parent_inode)] {
+			entries.push(DirectoryEntry {
+				inode,
+				file_type: attr.file_type(),
+				name: name.to_string(),
+			});
+		}
+		for (name, node) in dir.entries.iter() {
+			let attr = node.attr();
+			entries.push(DirectoryEntry {
+				inode: attr.st_ino,
+				file_type: attr.file_type(),
+				name: name.clone(),
+			});
+		}
+
+		Ok(Box::new(TarDir {
+			attr,
+			entries: entries.into_iter(),
+		}))
+	}
+
+	fn lstat(&self, path: &str) -> Result<FileAttr, FileError> {
+		let components = components(path)?;
+
+		match components.split_last() {
+			Some((name, parent)) => self
+				.lookup_dir(parent)?
+				.entries
+				.get(*name)
+				.map(Node::attr)
+				.ok_or(FileError::ENOENT),
+			None => Ok(self.root.metadata.attr(0)),
+		}
+	}
+}
+
+/// An opened directory of the archive
+struct TarDir {
+	attr: FileAttr,
+	entries: alloc::vec::IntoIter<DirectoryEntry>,
+}
+
+impl PosixFile for TarDir {
+	fn close(&mut self) -> Result<(), FileError> {
+		Ok(())
+	}
+
+	fn read(&mut self, _len: u32) -> Result<Vec<u8>, FileError> {
+		Err(FileError::EISDIR)
+	}
+
+	fn write(&mut self, _buf: &[u8]) -> Result<u64, FileError> {
+		Err(FileError::EBADF)
+	}
+
+	fn lseek(&mut self, _offset: isize, _whence: SeekWhence) -> Result<usize, FileError> {
+		Err(FileError::EINVAL)
+	}
+
+	fn readdir(&mut self) -> Result<Option<DirectoryEntry>, FileError> {
+		Ok(self.entries.next())
+	}
+
+	fn fstat(&mut self) -> Result<FileAttr, FileError> {
+		Ok(self.attr)
+	}
+
+	fn fsync(&mut self, _datasync: bool) -> Result<(), FileError> {
+		Ok(())
+	}
+}
+
+/// An opened file, which reads its content directly from the archive
+struct TarFile {
+	data: &'static [u8],
+	attr: FileAttr,
+	offset: usize,
+}
+
+impl PosixFile for TarFile {
+	fn close(&mut self) -> Result<(), FileError> {
+		Ok(())
+	}
+
+	fn read(&mut self, len: u32) -> Result<Vec<u8>, FileError> {
+		let start = self.offset.min(self.data.len());
+		let end = start.saturating_add(len as usize).min(self.data.len());
+		self.offset = end;
+
+		Ok(self.data[start..end].to_vec())
+	}
+
+	fn write(&mut self, _buf: &[u8]) -> Result<u64, FileError> {
+		Err(FileError::EBADF)
+	}
+
+	fn pread(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, FileError> {
+		let start = usize::try_from(offset)
+			.unwrap_or(usize::MAX)
+			.min(self.data.len());
+		let len = buf.len().min(self.data.len() - start);
+		buf[..len].copy_from_slice(&self.data[start..start + len]);
+
+		Ok(len)
+	}
+
+	fn lseek(&mut self, offset: isize, whence: SeekWhence) -> Result<usize, FileError> {
+		let base = match whence {
+			SeekWhence::Set => 0,
+			SeekWhence::Cur => self.offset,
+			SeekWhence::End => self.data.len(),
+		};
+		let offset = if offset < 0 {
+			base.checked_sub(offset.unsigned_abs())
+		} else {
+			base.checked_add(offset as usize)
+		}
+		.ok_or(FileError::EINVAL)?;
+		self.offset = offset;
+
+		Ok(offset)
+	}
+
+	fn fstat(&mut self) -> Result<FileAttr, FileError> {
+		Ok(self.attr)
+	}
+
+	fn fsync(&mut self, _datasync: bool) -> Result<(), FileError> {
+		Ok(())
+	}
+}
+
+#[cfg(not(target_os = "none"))]
+#[test]
+fn tar_archive() {
+	fn entry(archive: &mut Vec<u8>, path: &str, typeflag: u8, data: &[u8]) {
+		let mut header = [0u8; BLOCK_SIZE];
+		header[..path.len()].copy_from_slice(path.as_bytes());
+		header[100..108].copy_from_slice(b"0000644\0");
+		header[124..136].copy_from_slice(format!("{:011o}\0", data.len()).as_bytes());
+		header[136..148].copy_from_slice(b"14417235450\0");
+		header[148..156].fill(b' ');
+		header[156] = typeflag;
+		header[257..263].copy_from_slice(b"ustar\0");
+		header[263..265].copy_from_slice(b"00");
+		let sum: u32 = header.iter().map(|b| u32::from(*b)).sum();
+		header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+
+		archive.extend_from_slice(&header);
+		archive.extend_from_slice(data);
+		archive.resize(
+			(archive.len() + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE,
+			0,
+		);
+	}
+
+	let mut archive = Vec::new();
+	entry(
+		&mut archive,
+		"etc/hermit/config.toml",
+		TYPE_FILE,
+		b"answer = 42\n",
+	);
+	entry(&mut archive, "etc/", TYPE_DIRECTORY, b"");
+	entry(&mut archive, "etc/link", b'2', b"");
+	archive.resize(archive.len() + 2 * BLOCK_SIZE, 0);
+	let fs = TarFs::new(Vec::leak(archive)).unwrap();
+
+	let mut file = fs
+		.open("etc/hermit/config.toml", FilePerms::default())
+		.unwrap();
+	assert_eq!(file.read(6).unwrap(), b"answer");
+	let mut buf = [0; 16];
+	assert_eq!(file.pread(&mut buf, 9).unwrap(), 3);
+	assert_eq!(&buf[..3], b"42\n");
+	assert_eq!(file.fstat().unwrap().st_mode, S_IFREG | 0o644);
+
+	let write = FilePerms {
+		write: true,
+		..Default::default()
+	};
+	assert_eq!(
+		fs.open("etc/hermit/config.toml", write).err(),
+		Some(FileError::EROFS)
+	);
+	assert_eq!(fs.lstat("etc").unwrap().st_mode, S_IFDIR | 0o644);
+	assert_eq!(fs.lstat("etc/link").err(), Some(FileError::ENOENT));
+
+	let mut dir = fs.opendir("etc").unwrap();
+	let mut names = Vec::new();
+	while let Some(entry) = dir.readdir().unwrap() {
+		names.push(entry.name);
+	}
+	assert_eq!(names, [".", "..", "hermit"]);
+}
@@ -0,0 +1,903 @@
+/// Design:
+/// - want to support different backends. One of them virtiofs.
+/// - want to support multiple mounted filesystems at once.
+/// - for simplicity: no overlays. A path belongs to the mount point, which is its longest prefix.
+///   Mount points can be nested, e.g. /data/cache next to /data.
+/// - manage all files, the standard streams and sockets in one global file-descriptor table.
+///   Duplicated fds share the same object, which is closed, when the last fd referring to it is closed.
+///
+/// - we internally treat all file systems as posix filesystems.
+/// - Have two traits. One representing a filesystem, another a file: PosixFileSystem and PosixFile
+/// - filesystem.open creates new file
+/// - trait methods like open return Result<....>, so we can catch errors on eg open() and NOT permanently assign an fd to it!
+///
+/// - have a FUSE filesystem, which implements both PosixFileSystem and PosixFile
+/// - fuse can have various FuseInterface backends. These only have to provide fuse command send/receive capabilities.
+/// - virtiofs implements FuseInterface and sends commands via virtio queues.
+///   Every virtio-fs device has its own FUSE session and is mounted at /<tag>.
+///
+/// - fd management is only relevant for "user" facing code. We don't care how fuse etc. manages nodes internally.
+/// - But we still want to have a list of open files and mounted filesystems (here in fs.rs).
+///
+/// Open Questions:
+/// - what is the maximum number of open files I want to support? if small, could have static allocation, no need for hashmap?
+/// - optimize callchain? how does LTO work here?:
+///     - app calls rust.open (which is stdlib hermit/fs.rs) [https://github.com/rust-lang/rust/blob/master/src/libstd/sys/hermit/fs.rs#L267]
+///     - abi::open() (hermit-sys crate)
+///     - [KERNEL BORDER] (uses C-interface. needed? Could just be alternative to native rust?)
+///     - hermit-lib/....rs/sys_open()
+///     - SyscallInterface.open (via &'static dyn ref)
+///     - Filesystem::open()
+///     - Fuse::open()
+///     - VirtiofsDriver::send_command(...)
+///     - [HYPERVISOR BORDER] (via virtio)
+///     - virtiofsd receives fuse command and sends reply
+///
+/// TODO:
+/// - FileDescriptor newtype
+use crate::errno;
+use crate::synch::spinlock::{Spinlock, SpinlockGuard};
+#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+use crate::syscalls::net::Socket;
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+#[cfg(feature = "initramfs")]
+mod initramfs;
+mod stdio;
+mod tmpfs;
+
+// TODO: lazy static could be replaced with explicit init on OS boot.
+pub static FILESYSTEM: Spinlock<Filesystem> = Spinlock::new(Filesystem::new());
+
+pub struct Filesystem {
+	// Keep track of mount-points
+	mounts: BTreeMap<String, Box<dyn PosixFileSystem + Send>>,
+
+	// Keep track of open files, the standard streams and sockets
+	files: BTreeMap<u64, Object>,
+
+	// Absolute and normalized current working directory
+	cwd: String,
+}
+
+impl Filesystem {
+	pub const fn new() -> Self {
+		Self {
+			mounts: BTreeMap::new(),
+			files: BTreeMap::new(),
+			cwd: String::new(),
+		}
+	}
+
+	/// Returns the lowest free file-descriptor. We map index in files BTreeMap as fd's.
+	/// The keys are sorted, so the first gap in the keys is the lowest free fd.
+	fn assign_new_fd(&self) -> u64 {
+		let mut new_fd = 0;
+		for fd in self.files.keys() {
+			if *fd != new_fd {
+				break;
+			}
+			new_fd += 1;
+		}
+
+		new_fd
+	}
+
+	/// Gets a new fd for an object and inserts it into the fd table.
+	/// Returns file descriptor
+	pub(crate) fn add_object(&mut self, object: Object) -> u64 {
+		let fd = self.assign_new_fd();
+		self.files.insert(fd, object);
+		fd
+	}
+
+	/// Gets a new fd for a file and inserts it into open files.
+	/// Returns file descriptor
+	pub(crate) fn add_file(&mut self, file: Box<dyn PosixFile + Send>) -> u64 {
+		self.add_object(Object::File(Arc::new(OpenFile::new(file))))
+	}
+
+	/// Returns the object, which is referenced by `fd`. The object stays valid,
+	/// even if the fd is closed concurrently.
+	pub(crate) fn get(&self, fd: u64) -> Result<Object, FileError> {
+		self.files.get(&fd).cloned().ok_or(FileError::EBADF)
+	}
+
+	/// Removes `fd` from the fd table. The returned object is closed, when it is dropped
+	/// and no other fd refers to it. Therefore, it should be dropped after releasing the lock
+	/// of the filesystem, because closing a socket may block.
+	pub(crate) fn remove(&mut self, fd: u64) -> Result<Object, FileError> {
+		debug!("Closing fd {}", fd);
+		self.files.remove(&fd).ok_or(FileError::EBADF)
+	}
+
+	/// Returns a new fd, which refers to the same object as `fd`.
+	pub fn dup(&mut self, fd: u64) -> Result<u64, FileError> {
+		let object = self.get(fd)?;
+		Ok(self.add_object(object))
+	}
+
+	/// Lets `new_fd` refer to the same object as `fd`. Returns the object,
+	/// which has been referenced by `new_fd` before.
+	pub(crate) fn dup2(&mut self, fd: u64, new_fd: u64) -> Result<Option<Object>, FileError> {
+		let object = self.get(fd)?;
+		if fd == new_fd {
+			return Ok(None);
+		}
+
+		Ok(self.files.insert(new_fd, object))
+	}
+
+	/// Returns the absolute path of `path`, which is relative to the current working directory
+	/// or absolute. The path is normalized, i.e. it contains neither `.` nor `..`.
+	fn absolute_path(&self, path: &str) -> String {
+		let path = if path.starts_with('/') {
+			path.to_owned()
+		} else {
+			format!("{}/{}", self.cwd, path)
+		};
+
+		normalize(&path)
+	}
+
+	/// Returns true, if a mount point other than the root covers `path`.
+	///
+	/// A filesystem mounted at `/` (e.g. the initramfs) covers every path.
+	/// Therefore, it doesn't count, so that uhyve is still able to forward
+	/// the other paths to the host.
+	pub(crate) fn is_mounted(&self, path: &str) -> bool {
+		let path = self.absolute_path(path);
+		self.mounts
+			.keys()
+			.any(|mount| mount != "/" && is_prefix(mount, &path))
+	}
+
+	/// Finds the mount point, which is the longest prefix of `path`.
+	/// Returns (PosixFileSystem, internal_path) or Error on failure.
+	fn parse_path(&self, path: &str) -> Result<(&(dyn PosixFileSystem + Send), String), FileError> {
+		let path = self.absolute_path(path);
+
+		let (mount, fs) = self
+			.mounts
+			.iter()
+			.filter(|(mount, _)| is_prefix(mount, &path))
+			.max_by_key(|(mount, _)| mount.len())
+			.ok_or_else(|| {
+				info!("Trying to open file {} without a mount point!", path);
+				FileError::ENOENT
+			})?;
+		let internal_path = path[mount.len()..].trim_start_matches('/').to_owned();
+
+		Ok((fs.deref(), internal_path))
+	}
+
+	/// Tries to open file at given path (/MOUNTPOINT/internal-path).
+	/// Looks up MOUNTPOINT in mounted dirs, passes internal-path to filesystem backend
+	/// Returns the file descriptor of the newly opened file, or an error on failure
+	pub fn open(&mut self, path: &str, perms: FilePerms) -> Result<u64, FileError> {
+		debug!("Opening file {} {:?}", path, perms);
+		let (fs, internal_path) = self.parse_path(path)?;
+		let file = fs.open(&internal_path, perms)?;
+		Ok(self.add_file(file))
+	}
+
+	/// Unlinks a file given by path
+	pub fn unlink(&mut self, path: &str) -> Result<(), FileError> {
+		info!("Unlinking file {}", path);
+		let (fs, internal_path) = self.parse_path(path)?;
+		fs.unlink(&internal_path)?;
+		Ok(())
+	}
+
+	/// Creates a directory at the given path (/MOUNTPOINT/internal-path)
+	pub fn mkdir(&mut self, path: &str, mode: u32) -> Result<(), FileError> {
+		debug!("Creating directory {}", path);
+		let (fs, internal_path) = self.parse_path(path)?;
+		fs.mkdir(&internal_path, mode)
+	}
+
+	/// Removes the empty directory at the given path
+	pub fn rmdir(&mut self, path: &str) -> Result<(), FileError> {
+		debug!("Removing directory {}", path);
+		let (fs, internal_path) = self.parse_path(path)?;
+		fs.rmdir(&internal_path)
+	}
+
+	/// Opens the directory at the given path for reading its entries.
+	/// Directories share the file descriptors with the files.
+	pub fn opendir(&mut self, path: &str) -> Result<u64, FileError> {
+		debug!("Opening directory {}", path);
+		let (fs, internal_path) = self.parse_path(path)?;
+		let dir = fs.opendir(&internal_path)?;
+		Ok(self.add_file(dir))
+	}
+
+	/// Returns the next entry of an opened directory or `None`, if all entries are read.
+	pub fn readdir(&mut self, fd: u64) -> Result<Option<DirectoryEntry>, FileError> {
+		match self.get(fd)? {
+			Object::File(file) => file.lock().readdir(),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => Err(FileError::ENOTDIR),
+		}
+	}
+
+	/// Removes the fd of a directory, which is opened by `opendir`, from the fd table.
+	/// Other objects are rejected with `EBADF` and stay open.
+	pub(crate) fn closedir(&mut self, fd: u64) -> Result<Object, FileError> {
+		let is_directory = match self.get(fd)? {
+			Object::File(file) => file
+				.lock()
+				.fstat()
+				.map_or(false, |attr| attr.file_type() == FileType::Directory),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => false,
+		};
+
+		if !is_directory {
+			return Err(FileError::EBADF);
+		}
+		self.remove(fd)
+	}
+
+	/// Renames a file or directory. Both paths have to be on the same mount point.
+	pub fn rename(&mut self, old_path: &str, new_path: &str) -> Result<(), FileError> {
+		debug!("Renaming {} to {}", old_path, new_path);
+		let (old_fs, old_internal_path) = self.parse_path(old_path)?;
+		let (new_fs, new_internal_path) = self.parse_path(new_path)?;
+		if old_fs as *const _ as *const () != new_fs as *const _ as *const () {
+			return Err(FileError::EXDEV);
+		}
+		old_fs.rename(&old_internal_path, &new_internal_path)
+	}
+
+	/// Returns the metadata of a file. Symbolic links are followed.
+	pub fn stat(&self, path: &str) -> Result<FileAttr, FileError> {
+		let (fs, internal_path) = self.parse_path(path)?;
+		fs.stat(&internal_path)
+	}
+
+	/// Returns the metadata of a file. Symbolic links are not followed.
+	pub fn lstat(&self, path: &str) -> Result<FileAttr, FileError> {
+		let (fs, internal_path) = self.parse_path(path)?;
+		fs.lstat(&internal_path)
+	}
+
+	/// Returns the current working directory.
+	pub fn getcwd(&self) -> &str {
+		if self.cwd.is_empty() {
+			"/"
+		} else {
+			&self.cwd
+		}
+	}
+
+	/// Changes the current working directory. Besides the directories of the mounted
+	/// filesystems, the parents of mount points like `/` are valid working directories.
+	pub fn chdir(&mut self, path: &str) -> Result<(), FileError> {
+		debug!("Changing working directory to {}", path);
+		let path = self.absolute_path(path);
+
+		if !self.mounts.keys().any(|mount| is_prefix(&path, mount)) {
+			let (fs, internal_path) = self.parse_path(&path)?;
+			if fs.stat(&internal_path)?.file_type() != FileType::Directory {
+				return Err(FileError::ENOTDIR);
+			}
+		}
+
+		// the root is represented by the empty string to join paths easily
+		self.cwd = if path == "/" { String::new() } else { path };
+		Ok(())
+	}
+
+	/// Create new backing-fs at mountpoint mntpath. Mount points without a leading
+	/// slash are interpreted as absolute paths, e.g. `tmp` is mounted at `/tmp`.
+	pub fn mount(
+		&mut self,
+		mntpath: &str,
+		mntobj: Box<dyn PosixFileSystem + Send>,
+	) -> Result<(), ()> {
+		let mntpath = normalize(mntpath);
+		info!("Mounting {}", mntpath);
+
+		// if mounts contains path already abort
+		if self.mounts.contains_key(&mntpath) {
+			warn!("Mountpoint already exists!");
+			return Err(());
+		}
+
+		// insert filesystem into mounts, done
+		self.mounts.insert(mntpath, mntobj);
+		Ok(())
+	}
+}
+
+/// An opened file, which is closed, when the last fd referring to it is closed
+pub(crate) struct OpenFile(Spinlock<Box<dyn PosixFile + Send>>);
+
+impl OpenFile {
+	fn new(file: Box<dyn PosixFile + Send>) -> Self {
+		Self(Spinlock::new(file))
+	}
+
+	pub(crate) fn lock(&self) -> SpinlockGuard<'_, Box<dyn PosixFile + Send>> {
+		self.0.lock()
+	}
+}
+
+impl Drop for OpenFile {
+	fn drop(&mut self) {
+		if let Err(err) = self.0.lock().close() {
+			debug!("Unable to close file: {:?}", err);
+		}
+	}
+}
+
+/// An entry of the fd table. Errors are reported as positive `errno` values,
+/// because the objects are not limited to files.
+#[derive(Clone)]
+pub(crate) enum Object {
+	File(Arc<OpenFile>),
+	#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+	Socket(Arc<Socket>),
+}
+
+impl Object {
+	/// Reads up to `buf.len()` bytes and returns the number of read bytes.
+	pub(crate) fn read(&self, buf: &mut [u8]) -> Result<usize, i32> {
+		match self {
+			Object::File(file) => file.lock().read_into(buf).map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(socket) => socket.read(buf),
+		}
+	}
+
+	/// Writes `buf` and returns the number of written bytes.
+	pub(crate) fn write(&self, buf: &[u8]) -> Result<usize, i32> {
+		match self {
+			Object::File(file) => file
+				.lock()
+				.write(buf)
+				.map(|len| len as usize)
+				.map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(socket) => socket.write(buf),
+		}
+	}
+
+	pub(crate) fn pread(&self, buf: &mut [u8], offset: u64) -> Result<usize, i32> {
+		match self {
+			Object::File(file) => file.lock().pread(buf, offset).map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => Err(errno::ESPIPE),
+		}
+	}
+
+	pub(crate) fn pwrite(&self, buf: &[u8], offset: u64) -> Result<usize, i32> {
+		match self {
+			Object::File(file) => file.lock().pwrite(buf, offset).map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => Err(errno::ESPIPE),
+		}
+	}
+
+	/// Reads into the buffers in order. A socket fills only the first non-empty
+	/// buffer, so that the call does not block, after data has been received.
+	pub(crate) fn readv(&self, bufs: &mut [&mut [u8]]) -> Result<usize, i32> {
+		match self {
+			Object::File(file) => file.lock().readv(bufs).map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(socket) => match bufs.iter_mut().find(|buf| !buf.is_empty()) {
+				Some(buf) => socket.read(buf),
+				None => Ok(0),
+			},
+		}
+	}
+
+	pub(crate) fn writev(&self, bufs: &[&[u8]]) -> Result<usize, i32> {
+		match self {
+			Object::File(file) => file.lock().writev(bufs).map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(socket) => {
+				let mut total = 0;
+				for buf in bufs {
+					let len = socket.write(buf)?;
+					total += len;
+					if len < buf.len() {
+						break;
+					}
+				}
+				Ok(total)
+			}
+		}
+	}
+
+	pub(crate) fn flush(&self) -> Result<(), i32> {
+		match self {
+			Object::File(file) => file.lock().flush().map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => Ok(()),
+		}
+	}
+
+	pub(crate) fn fsync(&self, datasync: bool) -> Result<(), i32> {
+		match self {
+			Object::File(file) => file.lock().fsync(datasync).map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => Err(errno::EINVAL),
+		}
+	}
+
+	pub(crate) fn ftruncate(&self, size: u64) -> Result<(), i32> {
+		match self {
+			Object::File(file) => file.lock().ftruncate(size).map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => Err(errno::EINVAL),
+		}
+	}
+
+	pub(crate) fn fallocate(&self, mode: u32, offset: u64, len: u64) -> Result<(), i32> {
+		match self {
+			Object::File(file) => file
+				.lock()
+				.fallocate(mode, offset, len)
+				.map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => Err(errno::ENODEV),
+		}
+	}
+
+	pub(crate) fn lseek(&self, offset: isize, whence: SeekWhence) -> Result<usize, i32> {
+		match self {
+			Object::File(file) => file.lock().lseek(offset, whence).map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => Err(errno::ESPIPE),
+		}
+	}
+
+	pub(crate) fn fstat(&self) -> Result<FileAttr, i32> {
+		match self {
+			Object::File(file) => file.lock().fstat().map_err(|err| err.errno()),
+			#[cfg(all(feature = "tcp", not(feature = "newlib")))]
+			Object::Socket(_) => Ok(FileAttr {
+				st_mode: S_IFSOCK | 0o777,
+				st_nlink: 1,
+				..Default::default()
+			}),
+		}
+	}
+}
+
+/// Installs the standard streams and mounts the filesystems, which do not depend on a device,
+/// i.e. the tmpfs at `/tmp` and, with the feature `initramfs`, the embedded archive at `/`.
+/// Relative paths are initially resolved against the directory, which is given
+/// at compile time by HERMIT_WD.
+pub(crate) fn init() {
+	let mut fs = FILESYSTEM.lock();
+	for fd in 0..3 {
+		let file = stdio::open(fd);
+		fs.add_file(file);
+	}
+	let _ = fs.mount("tmp", Box::new(tmpfs::TmpFs::default()));
+	// the other mount points take precedence over the initramfs at the root
+	#[cfg(feature = "initramfs")]
+	match initramfs::TarFs::new(initramfs::ARCHIVE) {
+		Ok(initramfs) => {
+			let _ = fs.mount("/", Box::new(initramfs));
+		}
+		Err(err) => warn!("Unable to mount the initramfs: {:?}", err),
+	}
+	fs.cwd = normalize(option_env!("HERMIT_WD").unwrap_or("root"));
+	if fs.cwd == "/" {
+		fs.cwd = String::new();
+	}
+}
+
+/// Normalizes a path, which is interpreted as absolute path. Empty components and `.`
+/// are removed and `..` removes the previous component, i.e. symbolic links are not taken
+/// into account. The parent of the root is the root.
+fn normalize(path: &str) -> String {
+	let mut components: Vec<&str> = Vec::new();
+	for component in path.split('/') {
+		match component {
+			"" | "." => {}
+			".." => {
+				components.pop();
+			}
+			component => components.push(component),
+		}
+	}
+
+	format!("/{}", components.join("/"))
+}
+
+/// Returns true, if the normalized path `prefix` is equal to `path` or one of its parents.
+fn is_prefix(prefix: &str, path: &str) -> bool {
+	prefix == "/"
+		|| path == prefix
+		|| (path.starts_with(prefix) && path[prefix.len()..].starts_with('/'))
+}
+
+/// Defines `FileError` with one variant per `errno` value. The aliases
+/// `EWOULDBLOCK` and `EDEADLOCK` are represented by `EAGAIN` and `EDEADLK`.
+macro_rules! file_errors {
+	($($name:ident),* $(,)?) => {
+		#[allow(clippy::upper_case_acronyms)]
+		#[repr(i32)]
+		#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+		pub enum FileError {
+			$($name = errno::$name,)*
+		}
+
+		impl FileError {
+			/// Converts a (positive) `errno` value. Unknown values are reported as `EIO`.
+			pub fn from_errno(value: i32) -> Self {
+				match value {
+					$(errno::$name => FileError::$name,)*
+					_ => FileError::EIO,
+				}
+			}
+		}
+	};
+}
+
+file_errors! {
+	EPERM, ENOENT, ESRCH, EINTR, EIO, ENXIO, E2BIG, ENOEXEC, EBADF, ECHILD, EAGAIN, ENOMEM, EACCES,
+	EFAULT, ENOTBLK, EBUSY, EEXIST, EXDEV, ENODEV, ENOTDIR, EISDIR, EINVAL, ENFILE, EMFILE, ENOTTY,
+	ETXTBSY, EFBIG, ENOSPC, ESPIPE, EROFS, EMLINK, EPIPE, EDOM, ERANGE, EDEADLK, ENAMETOOLONG,
+	ENOLCK, ENOSYS, ENOTEMPTY, ELOOP, ENOMSG, EIDRM, ECHRNG, EL2NSYNC, EL3HLT, EL3RST, ELNRNG,
+	EUNATCH, ENOCSI, EL2HLT, EBADE, EBADR, EXFULL, ENOANO, EBADRQC, EBADSLT, EBFONT, ENOSTR,
+	ENODATA, ETIME, ENOSR, ENONET, ENOPKG, EREMOTE, ENOLINK, EADV, ESRMNT, ECOMM, EPROTO,
+	EMULTIHOP, EDOTDOT, EBADMSG, EOVERFLOW, ENOTUNIQ, EBADFD, EREMCHG, ELIBACC, ELIBBAD, ELIBSCN,
+	ELIBMAX, ELIBEXEC, EILSEQ, ERESTART, ESTRPIPE, EUSERS, ENOTSOCK, EDESTADDRREQ, EMSGSIZE,
+	EPROTOTYPE, ENOPROTOOPT, EPROTONOSUPPORT, ESOCKTNOSUPPORT, EOPNOTSUPP, EPFNOSUPPORT,
+	EAFNOSUPPORT, EADDRINUSE, EADDRNOTAVAIL, ENETDOWN, ENETUNREACH, ENETRESET, ECONNABORTED,
+	ECONNRESET, ENOBUFS, EISCONN, ENOTCONN, ESHUTDOWN, ETOOMANYREFS, ETIMEDOUT, ECONNREFUSED,
+	EHOSTDOWN, EHOSTUNREACH, EALREADY, EINPROGRESS, ESTALE, EUCLEAN, ENOTNAM, ENAVAIL, EISNAM,
+	EREMOTEIO, EDQUOT, ENOMEDIUM, EMEDIUMTYPE, ECANCELED, ENOKEY, EKEYEXPIRED, EKEYREVOKED,
+	EKEYREJECTED, EOWNERDEAD, ENOTRECOVERABLE, ERFKILL, EHWPOISON,
+}
+
+impl FileError {
+	/// Returns the corresponding (positive) `errno` value.
+	pub fn errno(&self) -> i32 {
+		*self as i32
+	}
+}
+
+pub trait PosixFileSystem {
+	fn open(&self, _path: &str, _perms: FilePerms) -> Result<Box<dyn PosixFile + Send>, FileError>;
+	fn unlink(&self, _path: &str) -> Result<(), FileError>;
+
+	fn mkdir(&self, _path: &str, _mode: u32) -> Result<(), FileError> {
+		Err(FileError::ENOSYS)
+	}
+
+	fn rmdir(&self, _path: &str) -> Result<(), FileError> {
+		Err(FileError::ENOSYS)
+	}
+
+	/// Opens a directory. Its entries are returned by [`PosixFile::readdir`].
+	fn opendir(&self, _path: &str) -> Result<Box<dyn PosixFile + Send>, FileError> {
+		Err(FileError::ENOSYS)
+	}
+
+	fn rename(&self, _old_path: &str, _new_path: &str) -> Result<(), FileError> {
+		Err(FileError::ENOSYS)
+	}
+
+	fn lstat(&self, _path: &str) -> Result<FileAttr, FileError> {
+		Err(FileError::ENOSYS)
+	}
+
+	/// Filesystems without symbolic links are able to use [`PosixFileSystem::lstat`].
+	fn stat(&self, path: &str) -> Result<FileAttr, FileError> {
+		self.lstat(path)
+	}
+}
+
+pub trait PosixFile {
+	fn close(&mut self) -> Result<(), FileError>;
+	fn read(&mut self, len: u32) -> Result<Vec<u8>, FileError>;
+	fn write(&mut self, buf: &[u8]) -> Result<u64, FileError>;
+	fn lseek(&mut self, offset: isize, whence: SeekWhence) -> Result<usize, FileError>;
+
+	/// Reads up to `buf.len()` bytes into `buf` and returns the number of read bytes.
+	/// Files, which are able to fill `buf` directly, avoid the copy of [`PosixFile::read`].
+	fn read_into(&mut self, buf: &mut [u8]) -> Result<usize, FileError> {
+		let data = self.read(buf.len().try_into().unwrap_or(u32::MAX))?;
+		buf[..data.len()].copy_from_slice(&data);
+		Ok(data.len())
+	}
+
+	/// Reads up to `buf.len()` bytes at `offset` without changing the file offset.
+	/// Files, which are not seekable, return `ESPIPE`.
+	fn pread(&mut self, _buf: &mut [u8], _offset: u64) -> Result<usize, FileError> {
+		Err(FileError::ESPIPE)
+	}
+
+	/// Writes `buf` at `offset` without changing the file offset.
+	/// Files, which are not seekable, return `ESPIPE`.
+	fn pwrite(&mut self, _buf: &[u8], _offset: u64) -> Result<usize, FileError> {
+		Err(FileError::ESPIPE)
+	}
+
+	/// Reads into the buffers in order and stops at the first short read.
+	fn readv(&mut self, bufs: &mut [&mut [u8]]) -> Result<usize, FileError> {
+		let mut total = 0;
+		for buf in bufs.iter_mut() {
+			let len = self.read_into(buf)?;
+			total += len;
+			if len < buf.len() {
+				break;
+			}
+		}
+		Ok(total)
+	}
+
+	/// Writes the buffers in order and stops at the first short write.
+	fn writev(&mut self, bufs: &[&[u8]]) -> Result<usize, FileError> {
+		let mut total = 0;
+		for buf in bufs {
+			let len = self.write(buf)? as usize;
+			total += len;
+			if len < buf.len() {
+				break;
+			}
+		}
+		Ok(total)
+	}
+
+	/// Returns the next entry of a directory, which is opened by
+	/// [`PosixFileSystem::opendir`], or `None` after the last entry.
+	fn readdir(&mut self) -> Result<Option<DirectoryEntry>, FileError> {
+		Err(FileError::ENOTDIR)
+	}
+
+	fn fstat(&mut self) -> Result<FileAttr, FileError> {
+		Err(FileError::ENOSYS)
+	}
+
+	/// Is called on every close of a file descriptor, which refers to the file,
+	/// so that errors of delayed writes can be reported.
+	fn flush(&mut self) -> Result<(), FileError> {
+		Ok(())
+	}
+
+	/// Transfers the modified data of the file to the storage device. The metadata
+	/// is only transferred, if it is required to read the data, when `datasync` is set.
+	fn fsync(&mut self, _datasync: bool) -> Result<(), FileError> {
+		Err(FileError::EINVAL)
+	}
+
+	/// Truncates or extends the file to `size` bytes.
+	fn ftruncate(&mut self, _size: u64) -> Result<(), FileError> {
+		Err(FileError::EINVAL)
+	}
+
+	/// Allocates the range of `len` bytes at `offset`. With `FALLOC_FL_KEEP_SIZE`
+	/// in `mode`, the size of the file is not changed.
+	fn fallocate(&mut self, _mode: u32, _offset: u64, _len: u64) -> Result<(), FileError> {
+		Err(FileError::ENODEV)
+	}
+}
+
+/// Flag of `sys_fallocate`, which keeps the file size unchanged
+pub const FALLOC_FL_KEEP_SIZE: u32 = 1;
+
+/// Bit mask of the file type in `st_mode`
+pub const S_IFMT: u32 = 0o170_000;
+pub const S_IFLNK: u32 = 0o120_000;
+pub const S_IFREG: u32 = 0o100_000;
+pub const S_IFDIR: u32 = 0o040_000;
+pub const S_IFCHR: u32 = 0o020_000;
+pub const S_IFSOCK: u32 = 0o140_000;
+
+/// Buffer of `sys_readv` and `sys_writev`, which has the layout of `struct iovec`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct IoVec {
+	pub iov_base: *mut u8,
+	pub iov_len: usize,
+}
+
+/// Metadata of a file, which is returned by `sys_stat`, `sys_lstat` and `sys_fstat`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FileAttr {
+	pub st_dev: u64,
+	pub st_ino: u64,
+	pub st_nlink: u64,
+	/// File type and access permissions
+	pub st_mode: u32,
+	pub st_uid: u32,
+	pub st_gid: u32,
+	pub st_rdev: u64,
+	pub st_size: i64,
+	pub st_blksize: i64,
+	pub st_blocks: i64,
+	pub st_atime: i64,
+	pub st_atime_nsec: i64,
+	pub st_mtime: i64,
+	pub st_mtime_nsec: i64,
+	pub st_ctime: i64,
+	pub st_ctime_nsec: i64,
+}
+
+impl FileAttr {
+	pub fn file_type(&self) -> FileType {
+		match self.st_mode & S_IFMT {
+			S_IFREG => FileType::File,
+			S_IFDIR => FileType::Directory,
+			S_IFLNK => FileType::Symlink,
+			_ => FileType::Unknown,
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FileType {
+	Unknown,
+	File,
+	Directory,
+	Symlink,
+}
+
+#[derive(Clone, Debug)]
+pub struct DirectoryEntry {
+	pub inode: u64,
+	pub file_type: FileType,
+	pub name: String,
+}
+
+/// Maximum length of a file name without the terminating NUL
+pub const MAX_NAME_LEN: usize = 255;
+
+const DT_UNKNOWN: u8 = 0;
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+const DT_LNK: u8 = 10;
+
+/// Directory entry, which is returned by `sys_readdir`
+#[repr(C)]
+pub struct Dirent {
+	pub d_ino: u64,
+	/// File type as `DT_*` value
+	pub d_type: u8,
+	/// NUL-terminated file name
+	pub d_name: [u8; MAX_NAME_LEN + 1],
+}
+
+impl TryFrom<&DirectoryEntry> for Dirent {
+	type Error = FileError;
+
+	fn try_from(entry: &DirectoryEntry) -> Result<Self, Self::Error> {
+		let name = entry.name.as_bytes();
+		if name.len() > MAX_NAME_LEN {
+			return Err(FileError::ENAMETOOLONG);
+		}
+
+		let mut dirent = Dirent {
+			d_ino: entry.inode,
+			d_type: match entry.file_type {
+				FileType::Unknown => DT_UNKNOWN,
+				FileType::File => DT_REG,
+				FileType::Directory => DT_DIR,
+				FileType::Symlink => DT_LNK,
+			},
+			d_name: [0; MAX_NAME_LEN + 1],
+		};
+		dirent.d_name[..name.len()].copy_from_slice(name);
+
+		Ok(dirent)
+	}
+}
+
+// TODO: raw is partially redundant, create nicer interface
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FilePerms {
+	pub write: bool,
+	pub creat: bool,
+	pub excl: bool,
+	pub trunc: bool,
+	pub append: bool,
+	pub directio: bool,
+	pub raw: u32,
+	pub mode: u32,
+}
+
+pub enum SeekWhence {
+	Set,
+	Cur,
+	End,
+}
+
+#[cfg(not(target_os = "none"))]
+#[test]
+fn nested_mounts() {
+	let mut fs = Filesystem::new();
+	fs.mount("data", Box::new(tmpfs::TmpFs::default())).unwrap();
+	fs.mount("/data/./cache/", Box::new(tmpfs::TmpFs::default()))
+		.unwrap();
+	assert!(fs
+		.mount("/data/cache", Box::new(tmpfs::TmpFs::default()))
+		.is_err());
+
+	assert_eq!(normalize("/a/./b/../../../c//d/"), "/c/d");
+	assert_eq!(fs.parse_path("/data/cache/x/../y").unwrap().1, "y");
+	assert_eq!(fs.parse_path("/data/cachefile").unwrap().1, "cachefile");
+	assert_eq!(fs.parse_path("/data").unwrap().1, "");
+	assert!(fs.parse_path("/tmp/x").is_err());
+	assert!(fs.is_mounted("/data/file"));
+	assert!(!fs.is_mounted("/tmp/x"));
+
+	// the parent of a mount point is a valid working directory
+	fs.chdir("/").unwrap();
+	assert_eq!(fs.getcwd(), "/");
+	fs.mkdir("/data/cache/dir", 0o777).unwrap();
+	fs.chdir("data/cache/dir").unwrap();
+	assert_eq!(fs.getcwd(), "/data/cache/dir");
+	assert_eq!(fs.parse_path("../../file").unwrap().1, "file");
+	assert!(matches!(fs.chdir("missing"), Err(FileError::ENOENT)));
+}
+
+#[cfg(not(target_os = "none"))]
+#[test]
+fn root_mount() {
+	let mut fs = Filesystem::new();
+	fs.mount("/", Box::new(tmpfs::TmpFs::default())).unwrap();
+	fs.mount("/tmp", Box::new(tmpfs::TmpFs::default())).unwrap();
+
+	assert!(fs.parse_path("/home/user/file").is_ok());
+	assert!(!fs.is_mounted("/home/user/file"));
+	assert!(!fs.is_mounted("/"));
+	assert!(fs.is_mounted("/tmp/file"));
+}
+
+#[cfg(not(target_os = "none"))]
+#[test]
+fn fd_table() {
+	let mut fs = Filesystem::new();
+	fs.mount("tmp", Box::new(tmpfs::TmpFs::default())).unwrap();
+	let perms = FilePerms {
+		write: true,
+		creat: true,
+		..Default::default()
+	};
+
+	let first = fs.open("/tmp/first", perms).unwrap();
+	let second = fs.open("/tmp/second", perms).unwrap();
+	assert_eq!((first, second), (0, 1));
+
+	// the lowest free fd is reused
+	fs.remove(first).unwrap();
+	assert_eq!(fs.dup(second).unwrap(), 0);
+
+	// both fds share the same offset
+	fs.get(0).unwrap().write(b"hello").unwrap();
+	assert_eq!(fs.get(1).unwrap().lseek(0, SeekWhence::Cur).unwrap(), 5);
+
+	assert!(fs.dup2(1, 7).unwrap().is_none());
+	assert!(fs.dup2(7, 0).unwrap().is_some());
+	assert!(matches!(fs.get(2), Err(FileError::EBADF)));
+
+	// closedir accepts only directories
+	let dir = fs.opendir("/tmp").unwrap();
+	assert!(matches!(fs.closedir(1), Err(FileError::EBADF)));
+	assert!(fs.get(1).is_ok());
+	fs.closedir(dir).unwrap();
+}
+
+#[cfg(not(target_os = "none"))]
+#[test]
+fn errno_conversion() {
+	assert_eq!(FileError::from_errno(errno::ENOSPC), FileError::ENOSPC);
+	assert_eq!(FileError::from_errno(errno::EWOULDBLOCK), FileError::EAGAIN);
+	assert_eq!(FileError::ECONNRESET.errno(), errno::ECONNRESET);
+	assert_eq!(FileError::from_errno(4096), FileError::EIO);
+}
@@ -0,0 +1,84 @@
+//! The standard streams, which occupy the file descriptors 0, 1 and 2.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::console::CONSOLE;
+use crate::env::is_uhyve;
+use crate::syscalls::fs::{FileAttr, FileError, PosixFile, SeekWhence, S_IFCHR};
+use crate::syscalls::interfaces::UhyveFile;
+
+const STDIN_FILENO: u64 = 0;
+
+/// Returns the standard stream `fd`. On uhyve, the streams of the host are used.
+/// Otherwise, stdout and stderr are written to the console and stdin is empty.
+pub(super) fn open(fd: u64) -> Box<dyn PosixFile + Send> {
+	if is_uhyve() {
+		Box::new(UhyveFile::new(fd as i32))
+	} else if fd == STDIN_FILENO {
+		Box::new(Stdin)
+	} else {
+		Box::new(Stdout)
+	}
+}
+
+fn console_attr() -> FileAttr {
+	FileAttr {
+		st_mode: S_IFCHR | 0o620,
+		st_nlink: 1,
+		..Default::default()
+	}
+}
+
+/// The console does not provide any input, so reading always returns end-of-file.
+#[derive(Debug)]
+struct Stdin;
+
+impl PosixFile for Stdin {
+	fn close(&mut self) -> Result<(), FileError> {
+		Ok(())
+	}
+
+	fn read(&mut self, _len: u32) -> Result<Vec<u8>, FileError> {
+		Ok(Vec::new())
+	}
+
+	fn write(&mut self, _buf: &[u8]) -> Result<u64, FileError> {
+		Err(FileError::EBADF)
+	}
+
+	fn lseek(&mut self, _offset: isize, _whence: SeekWhence) -> Result<usize, FileError> {
+		Err(FileError::ESPIPE)
+	}
+
+	fn fstat(&mut self) -> Result<FileAttr, FileError> {
+		Ok(console_attr())
+	}
+}
+
+/// Stdout and stderr, which are both written to the console
+#[derive(Debug)]
+struct Stdout;
+
+impl PosixFile for Stdout {
+	fn close(&mut self) -> Result<(), FileError> {
+		Ok(())
+	}
+
+	fn read(&mut self, _len: u32) -> Result<Vec<u8>, FileError> {
+		Err(FileError::EBADF)
+	}
+
+	fn write(&mut self, buf: &[u8]) -> Result<u64, FileError> {
+		CONSOLE.lock().write_all(buf);
+		Ok(buf.len() as u64)
+	}
+
+	fn lseek(&mut self, _offset: isize, _whence: SeekWhence) -> Result<usize, FileError> {
+		Err(FileError::ESPIPE)
+	}
+
+	fn fstat(&mut self) -> Result<FileAttr, FileError> {
+		Ok(console_attr())
+	}
+}
@@ -0,0 +1,616 @@
+//! A filesystem, which keeps its files and directories on the heap.
+//! The content is lost, when the unikernel terminates.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::arch;
+use crate::synch::spinlock::Spinlock;
+use crate::syscalls::fs::{
+	DirectoryEntry, FileAttr, FileError, FilePerms, PosixFile, PosixFileSystem, SeekWhence,
+	FALLOC_FL_KEEP_SIZE, S_IFDIR, S_IFREG,
+};
+
+/// Block size, which is reported by `stat`
+const BLOCK_SIZE: i64 = 512;
+/// Maximum size of a file in bytes
+const MAX_FILE_SIZE: usize = 1 << 30;
+
+static NEXT_INODE: AtomicU64 = AtomicU64::new(1);
+
+/// Returns the current time as seconds and nanoseconds since the epoch.
+fn now() -> (i64, i64) {
+	let microseconds = arch::processor::get_timer_ticks() + arch::get_boot_time();
+	(
+		(microseconds / 1_000_000) as i64,
+		(microseconds % 1_000_000 * 1000) as i64,
+	)
+}
+
+#[derive(Copy, Clone)]
+struct Metadata {
+	inode: u64,
+	mode: u32,
+	atime: (i64, i64),
+	mtime: (i64, i64),
+	ctime: (i64, i64),
+}
+
+impl Metadata {
+	fn new(mode: u32) -> Self {
+		let time = now();
+		Self {
+			inode: NEXT_INODE.fetch_add(1, Ordering::Relaxed),
+			mode,
+			atime: time,
+			mtime: time,
+			ctime: time,
+		}
+	}
+
+	/// Updates the modification time after a change of the content.
+	fn modified(&mut self) {
+		let time = now();
+		self.mtime = time;
+		self.ctime = time;
+	}
+
+	fn attr(&self, size: usize) -> FileAttr {
+		FileAttr {
+			st_ino: self.inode,
+			st_nlink: 1,
+			st_mode: self.mode,
+			st_size: size as i64,
+			st_blksize: BLOCK_SIZE,
+			st_blocks: (size as i64 + BLOCK_SIZE - 1) / BLOCK_SIZE,
+			st_atime: self.atime.0,
+			st_atime_nsec: self.atime.1,
+			st_mtime: self.mtime.0,
+			st_mtime_nsec: self.mtime.1,
+			st_ctime: self.ctime.0,
+			st_ctime_nsec: self.ctime.1,
+			..Default::default()
+		}
+	}
+}
+
+struct File {
+	data: Vec<u8>,
+	metadata: Metadata,
+}
+
+impl File {
+	/// Copies the data at `offset` into `buf` and returns the number of copied bytes.
+	fn read_at(&mut self, buf: &mut [u8], offset: usize) -> usize {
+		let start = offset.min(self.data.len());
+		let len = buf.len().min(self.data.len() - start);
+		buf[..len].copy_from_slice(&self.data[start..start + len]);
+		self.metadata.atime = now();
+		len
+	}
+
+	/// Changes the size of the file and fills new space with zeros. Sizes beyond
+	/// `MAX_FILE_SIZE` fail with `EFBIG` and an exhausted heap with `ENOSPC`.
+	fn resize(&mut self, size: usize) -> Result<(), FileError> {
+		if size > MAX_FILE_SIZE {
+			return Err(FileError::EFBIG);
+		}
+		if let Some(additional) = size.checked_sub(self.data.len()) {
+			self.data
+				.try_reserve(additional)
+				.map_err(|_| FileError::ENOSPC)?;
+		}
+		self.data.resize(size, 0);
+		Ok(())
+	}
+
+	/// Writes `buf` at `offset` and returns the offset behind the written data.
+	fn write_at(&mut self, buf: &[u8], offset: usize) -> Result<usize, FileError> {
+		let end = offset.checked_add(buf.len()).ok_or(FileError::EFBIG)?;
+		// a write behind the end of the file fills the gap with zeros
+		if self.data.len() < end {
+			self.resize(end)?;
+		}
+		self.data[offset..end].copy_from_slice(buf);
+		self.metadata.modified();
+		Ok(end)
+	}
+}
+
+/// A file, which is shared by all open handles. Therefore, an
+/// unlinked file stays accessible, until the last handle is closed.
+type SharedFile = Arc<Spinlock<File>>;
+
+enum Node {
+	File(SharedFile),
+	Directory(Directory),
+}
+
+impl Node {
+	fn attr(&self) -> FileAttr {
+		match self {
+			Node::File(file) => {
+				let file = file.lock();
+				file.metadata.attr(file.data.len())
+			}
+			Node::Directory(dir) => dir.metadata.attr(0),
+		}
+	}
+}
+
+struct Directory {
+	entries: BTreeMap<String, Node>,
+	metadata: Metadata,
+}
+
+impl Directory {
+	fn new(mode: u32) -> Self {
+		Self {
+			entries: BTreeMap::new(),
+			metadata: Metadata::new(S_IFDIR | (mode & 0o7777)),
+		}
+	}
+
+	/// Returns the directory, which is reached by following `components`.
+	fn lookup_dir(&mut self, components: &[&str]) -> Result<&mut Directory, FileError> {
+		let mut dir = self;
+		for component in components {
+			dir = match dir.entries.get_mut(*component) {
+				Some(Node::Directory(dir)) => dir,
+				Some(Node::File(_)) => return Err(FileError::ENOTDIR),
+				None => return Err(FileError::ENOENT),
+			};
+		}
+
+		Ok(dir)
+	}
+
+	fn insert(&mut self, name: &str, node: Node) {
+		self.entries.insert(name.to_string(), node);
+		self.metadata.modified();
+	}
+
+	fn remove(&mut self, name: &str) -> Option<Node> {
+		let node = self.entries.remove(name);
+		self.metadata.modified();
+		node
+	}
+}
+
+fn components(path: &str) -> Result<Vec<&str>, FileError> {
+	let components: Vec<&str> = path
+		.split('/')
+		.filter(|component| !component.is_empty() && *component != ".")
+		.collect();
+	if components.contains(&"..") {
+		return Err(FileError::EINVAL);
+	}
+
+	Ok(components)
+}
+
+/// Splits `path` into the components of its parent directory and its name.
+fn split_path(path: &str) -> Result<(Vec<&str>, &str), FileError> {
+	let mut components = components(path)?;
+	// the root directory itself has no name
+	let name = components.pop().ok_or(FileError::EINVAL)?;
+
+	Ok((components, name))
+}
+
+pub struct TmpFs {
+	root: Spinlock<Directory>,
+}
+
+impl Default for TmpFs {
+	fn default() -> Self {
+		Self {
+			root: Spinlock::new(Directory::new(0o777)),
+		}
+	}
+}
+
+impl PosixFileSystem for TmpFs {
+	fn open(&self, path: &str, perms: FilePerms) -> Result<Box<dyn PosixFile + Send>, FileError> {
+		let (parent, name) = split_path(path)?;
+		let mut root = self.root.lock();
+		let dir = root.lookup_dir(&parent)?;
+
+		let file = match dir.entries.get(name) {
+			Some(Node::File(_)) if perms.creat && perms.excl => return Err(FileError::EEXIST),
+			Some(Node::File(file)) => file.clone(),
+			Some(Node::Directory(_)) => return Err(FileError::EISDIR),
+			None if perms.creat => {
+				let file = Arc::new(Spinlock::new(File {
+					data: Vec::new(),
+					metadata: Metadata::new(S_IFREG | (perms.mode & 0o7777)),
+				}));
+				dir.insert(name, Node::File(file.clone()));
+				file
+			}
+			None => return Err(FileError::ENOENT),
+		};
+
+		if perms.trunc && perms.write {
+			let mut file = file.lock();
+			file.data.clear();
+			file.metadata.modified();
+		}
+
+		Ok(Box::new(TmpFile {
+			file,
+			offset: 0,
+			write: perms.write,
+			append: perms.append,
+		}))
+	}
+
+	fn unlink(&self, path: &str) -> Result<(), FileError> {
+		let (parent, name) = split_path(path)?;
+		let mut root = self.root.lock();
+		let dir = root.lookup_dir(&parent)?;
+
+		match dir.entries.get(name) {
+			Some(Node::File(_)) => {
+				dir.remove(name);
+				Ok(())
+			}
+			Some(Node::Directory(_)) => Err(FileError::EISDIR),
+			None => Err(FileError::ENOENT),
+		}
+	}
+
+	fn mkdir(&self, path: &str, mode: u32) -> Result<(), FileError> {
+		let (parent, name) = split_path(path)?;
+		let mut root = self.root.lock();
+		let dir = root.lookup_dir(&parent)?;
+
+		if dir.entries.contains_key(name) {
+			return Err(FileError::EEXIST);
+		}
+		dir.insert(name, Node::Directory(Directory::new(mode)));
+
+		Ok(())
+	}
+
+	fn rmdir(&self, path: &str) -> Result<(), FileError> {
+		let (parent, name) = split_path(path)?;
+		let mut root = self.root.lock();
+		let dir = root.lookup_dir(&parent)?;
+
+		match dir.entries.get(name) {
+			Some(Node::Directory(child)) if !child.entries.is_empty() => Err(FileError::ENOTEMPTY),
+			Some(Node::Directory(_)) => {
+				dir.remove(name);
+				Ok(())
+			}
+			Some(Node::File(_)) => Err(FileError::ENOTDIR),
+			None => Err(FileError::ENOENT),
+		}
+	}
+
+	fn opendir(&self, path: &str) -> Result<Box<dyn PosixFile + Send>, FileError> {
+		let components = components(path)?;
+		let mut root = self.root.lock();
+		let parent_inode = match components.split_last() {
+			Some((_, parent)) => root.lookup_dir(parent)?.metadata.inode,
+			None => root.metadata.inode,
+		};
+		let dir = root.lookup_dir(&components)?;
+		let attr = dir.metadata.attr(0);
+
+		let mut entries = Vec::with_capacity(dir.entries.len() + 2);
+		for (name, inode) in [(".", attr.st_ino), ("..", parent_inode)] {
+			entries.push(DirectoryEntry {
+				inode,
+				file_type: attr.file_type(),
+				name: name.to_string(),
+			});
+		}
+		for (name, node) in dir.entries.iter() {
+			let attr = node.attr();
+			entries.push(DirectoryEntry {
+				inode: attr.st_ino,
+				file_type: attr.file_type(),
+				name: name.clone(),
+			});
+		}
+
+		Ok(Box::new(TmpDir {
+			attr,
+			entries: entries.into_iter(),
+		}))
+	}
+
+	fn rename(&self, old_path: &str, new_path: &str) -> Result<(), FileError> {
+		let (old_parent, old_name) = split_path(old_path)?;
+		let (new_parent, new_name) = split_path(new_path)?;
+		// a directory cannot become a subdirectory of itself
+		if new_parent.len() > old_parent.len()
+			&& new_parent.starts_with(&old_parent)
+			&& new_parent[old_parent.len()] == old_name
+		{
+			return Err(FileError::EINVAL);
+		}
+
+		let mut root = self.root.lock();
+		let is_dir = match root.lookup_dir(&old_parent)?.entries.get(old_name) {
+			Some(node) => matches!(node, Node::Directory(_)),
+			None => return Err(FileError::ENOENT),
+		};
+		// an existing destination is replaced, if it has the same type
+		match root.lookup_dir(&new_parent)?.entries.get(new_name) {
+			Some(Node::Directory(_)) if !is_dir => return Err(FileError::EISDIR),
+			Some(Node::Directory(dir)) if !dir.entries.is_empty() => {
+				return Err(FileError::ENOTEMPTY)
+			}
+			Some(Node::File(_)) if is_dir => return Err(FileError::ENOTDIR),
+			_ => {}
+		}
+		if old_parent == new_parent && old_name == new_name {
+			return Ok(());
+		}
+
+		let node = root.lookup_dir(&old_parent)?.remove(old_name).unwrap();
+		root.lookup_dir(&new_parent)?.insert(new_name, node);
+
+		Ok(())
+	}
+
+	fn lstat(&self, path: &str) -> Result<FileAttr, FileError> {
+		let components = components(path)?;
+		let mut root = self.root.lock();
+
+		match components.split_last() {
+			Some((name, parent)) => root
+				.lookup_dir(parent)?
+				.entries
+				.get(*name)
+				.map(Node::attr)
+				.ok_or(FileError::ENOENT),
+			None => Ok(root.metadata.attr(0)),
+		}
+	}
+}
+
+/// An opened directory, whose entries are taken at the time of opening
+struct TmpDir {
+	attr: FileAttr,
+	entries: alloc::vec::IntoIter<DirectoryEntry>,
+}
+
+impl PosixFile for TmpDir {
+	fn close(&mut self) -> Result<(), FileError> {
+		Ok(())
+	}
+
+	fn read(&mut self, _len: u32) -> Result<Vec<u8>, FileError> {
+		Err(FileError::EISDIR)
+	}
+
+	fn write(&mut self, _buf: &[u8]) -> Result<u64, FileError> {
+		Err(FileError::EBADF)
+	}
+
+	fn lseek(&mut self, _offset: isize, _whence: SeekWhence) -> Result<usize, FileError> {
+		Err(FileError::EINVAL)
+	}
+
+	fn readdir(&mut self) -> Result<Option<DirectoryEntry>, FileError> {
+		Ok(self.entries.next())
+	}
+
+	fn fstat(&mut self) -> Result<FileAttr, FileError> {
+		Ok(self.attr)
+	}
+
+	fn fsync(&mut self, _datasync: bool) -> Result<(), FileError> {
+		Ok(())
+	}
+}
+
+struct TmpFile {
+	file: SharedFile,
+	offset: usize,
+	write: bool,
+	append: bool,
+}
+
+impl PosixFile for TmpFile {
+	fn close(&mut self) -> Result<(), FileError> {
+		Ok(())
+	}
+
+	fn read(&mut self, len: u32) -> Result<Vec<u8>, FileError> {
+		let mut file = self.file.lock();
+		let start = self.offset.min(file.data.len());
+		let mut buf = vec![0; (len as usize).min(file.data.len() - start)];
+		let len = file.read_at(&mut buf, self.offset);
+		self.offset += len;
+
+		Ok(buf)
+	}
+
+	fn write(&mut self, buf: &[u8]) -> Result<u64, FileError> {
+		if !self.write {
+			return Err(FileError::EBADF);
+		}
+
+		let mut file = self.file.lock();
+		if self.append {
+			self.offset = file.data.len();
+		}
+		self.offset = file.write_at(buf, self.offset)?;
+
+		Ok(buf.len() as u64)
+	}
+
+	fn pread(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, FileError> {
+		let offset = usize::try_from(offset).unwrap_or(usize::MAX);
+		Ok(self.file.lock().read_at(buf, offset))
+	}
+
+	/// Writes at `offset` even if the file is opened with `O_APPEND`, as required by POSIX.
+	fn pwrite(&mut self, buf: &[u8], offset: u64) -> Result<usize, FileError> {
+		if !self.write {
+			return Err(FileError::EBADF);
+		}
+
+		let offset = usize::try_from(offset)
+			.ok()
+			.filter(|offset| *offset <= MAX_FILE_SIZE)
+			.ok_or(FileError::EFBIG)?;
+		self.file.lock().write_at(buf, offset)?;
+		Ok(buf.len())
+	}
+
+	fn lseek(&mut self, offset: isize, whence: SeekWhence) -> Result<usize, FileError> {
+		let base = match whence {
+			SeekWhence::Set => 0,
+			SeekWhence::Cur => self.offset,
+			SeekWhence::End => self.file.lock().data.len(),
+		};
+		let offset = if offset < 0 {
+			base.checked_sub(offset.unsigned_abs())
+		} else {
+			base.checked_add(offset as usize)
+		}
+		.ok_or(FileError::EINVAL)?;
+		self.offset = offset;
+
+		Ok(offset)
+	}
+
+	fn fstat(&mut self) -> Result<FileAttr, FileError> {
+		let file = self.file.lock();
+		Ok(file.metadata.attr(file.data.len()))
+	}
+
+	/// The data is kept in memory, so there is nothing to synchronize.
+	fn fsync(&mut self, _datasync: bool) -> Result<(), FileError> {
+		Ok(())
+	}
+
+	fn ftruncate(&mut self, size: u64) -> Result<(), FileError> {
+		if !self.write {
+			return Err(FileError::EBADF);
+		}
+
+		let size = usize::try_from(size).map_err(|_| FileError::EFBIG)?;
+		let mut file = self.file.lock();
+		file.resize(size)?;
+		file.metadata.modified();
+		Ok(())
+	}
+
+	fn fallocate(&mut self, mode: u32, offset: u64, len: u64) -> Result<(), FileError> {
+		if !self.write {
+			return Err(FileError::EBADF);
+		} else if mode & !FALLOC_FL_KEEP_SIZE != 0 {
+			return Err(FileError::EOPNOTSUPP);
+		}
+
+		let end = offset
+			.checked_add(len)
+			.and_then(|end| usize::try_from(end).ok())
+			.ok_or(FileError::EFBIG)?;
+		let mut file = self.file.lock();
+		if mode & FALLOC_FL_KEEP_SIZE == 0 && file.data.len() < end {
+			file.resize(end)?;
+			file.metadata.modified();
+		}
+		Ok(())
+	}
+}
+
+#[cfg(not(target_os = "none"))]
+#[test]
+fn tmpfs_files() {
+	let fs = TmpFs::default();
+	let create = FilePerms {
+		write: true,
+		creat: true,
+		mode: 0o644,
+		..Default::default()
+	};
+
+	fs.mkdir("dir", 0o777).unwrap();
+	assert!(fs.open("missing/file", create).is_err());
+	let mut file = fs.open("dir/file", create).unwrap();
+	assert_eq!(file.write(b"hello world").unwrap(), 11);
+	assert_eq!(file.lseek(-5, SeekWhence::End).unwrap(), 6);
+	assert_eq!(file.read(100).unwrap(), b"world");
+	assert!(fs.rmdir("dir").is_err());
+
+	// a write far behind the end of the file must not exhaust the heap
+	file.lseek(isize::MAX, SeekWhence::Set).unwrap();
+	assert_eq!(file.write(b"!").unwrap_err(), FileError::EFBIG);
+
+	let attr = fs.stat("dir/file").unwrap();
+	assert_eq!(attr.st_mode, S_IFREG | 0o644);
+	assert_eq!(attr.st_size, 11);
+	assert_eq!(file.fstat().unwrap().st_ino, attr.st_ino);
+	assert_eq!(fs.stat("dir").unwrap().st_mode & S_IFDIR, S_IFDIR);
+
+	let append = FilePerms {
+		write: true,
+		append: true,
+		..Default::default()
+	};
+	let mut file = fs.open("dir/file", append).unwrap();
+	file.write(b"!").unwrap();
+	let mut file = fs.open("dir/file", FilePerms::default()).unwrap();
+	assert_eq!(file.read(100).unwrap(), b"hello world!");
+	assert!(file.write(b"read-only").is_err());
+
+	// positional and vectored I/O
+	let mut file = fs.open("dir/file", append).unwrap();
+	assert_eq!(file.pwrite(b"W", 6).unwrap(), 1);
+	let mut buf = [0; 5];
+	assert_eq!(file.pread(&mut buf, 6).unwrap(), 5);
+	assert_eq!(&buf, b"World");
+	assert_eq!(file.pread(&mut buf, 100).unwrap(), 0);
+	assert_eq!(file.pwrite(b"!", 1 << 62).unwrap_err(), FileError::EFBIG);
+	assert_eq!(fs.stat("dir/file").unwrap().st_size, 12);
+	let (mut first, mut second) = ([0; 6], [0; 10]);
+	assert_eq!(file.readv(&mut [&mut first, &mut second]).unwrap(), 12);
+	assert_eq!(&first, b"hello ");
+	assert_eq!(&second[..6], b"World!");
+	assert_eq!(file.writev(&[b"a", b"bc"]).unwrap(), 3);
+	assert_eq!(fs.stat("dir/file").unwrap().st_size, 15);
+	file.fallocate(FALLOC_FL_KEEP_SIZE, 0, 100).unwrap();
+	assert_eq!(fs.stat("dir/file").unwrap().st_size, 15);
+	file.fallocate(0, 10, 10).unwrap();
+	assert_eq!(fs.stat("dir/file").unwrap().st_size, 20);
+	file.ftruncate(5).unwrap();
+	assert_eq!(fs.stat("dir/file").unwrap().st_size, 5);
+	assert_eq!(file.ftruncate(u64::MAX).unwrap_err(), FileError::EFBIG);
+	assert_eq!(file.fallocate(0, 5, 1 << 40).unwrap_err(), FileError::EFBIG);
+	assert_eq!(fs.stat("dir/file").unwrap().st_size, 5);
+
+	let trunc = FilePerms {
+		write: true,
+		trunc: true,
+		..Default::default()
+	};
+	let mut file = fs.open("dir/file", trunc).unwrap();
+	assert!(file.read(100).unwrap().is_empty());
+
+	fs.mkdir("other", 0o777).unwrap();
+	fs.rename("dir/file", "other/renamed").unwrap();
+	assert!(fs.rename("other", "other/nested").is_err());
+	let mut dir = fs.opendir("other").unwrap();
+	let mut names = Vec::new();
+	while let Some(entry) = dir.readdir().unwrap() {
+		names.push(entry.name);
+	}
+	assert_eq!(names, [".", "..", "renamed"]);
+
+	fs.unlink("other/renamed").unwrap();
+	fs.rmdir("dir").unwrap();
+	assert!(fs.open("dir/file", FilePerms::default()).is_err());
+}
@@ -4,15 +4,18 @@ use core::ffi::CStr;
 use core::{isize, slice, str};
 
 use crate::arch;
-use crate::console::CONSOLE;
 use crate::env;
 use crate::errno::*;
-use crate::syscalls::fs::{self, FilePerms, PosixFile, SeekWhence};
+use crate::syscalls::fs::{self, Dirent, FileAttr, FilePerms, IoVec, SeekWhence};
+#[cfg(feature = "newlib")]
+use crate::syscalls::lwip::sys_lwip_get_errno;
+#[cfg(feature = "newlib")]
+use crate::syscalls::{LWIP_FD_BIT, LWIP_LOCK};
 
 #[cfg(all(not(feature = "pci"), not(target_arch = "aarch64")))]
-use arch::kernel::mmio::get_network_driver;
+use arch::kernel::mmio::{get_network_driver, get_network_drivers};
 #[cfg(all(feature = "pci", not(target_arch = "aarch64")))]
-use arch::kernel::pci::get_network_driver;
+use arch::kernel::pci::{get_network_driver, get_network_drivers};
 
 pub use self::generic::*;
 pub use self::uhyve::*;
@@ -23,6 +26,14 @@ mod uhyve;
 const SEEK_SET: i32 = 0;
 const SEEK_CUR: i32 = 1;
 const SEEK_END: i32 = 2;
+/// Maximum number of buffers of `readv` and `writev`
+const IOV_MAX: i32 = 1024;
+
+#[cfg(feature = "newlib")]
+extern "C" {
+	fn lwip_write(fd: i32, buf: *const u8, len: usize) -> i32;
+	fn lwip_read(fd: i32, buf: *mut u8, len: usize) -> i32;
+}
 
 impl TryFrom<i32> for SeekWhence {
 	type Error = &'static str;
@@ -78,6 +89,32 @@ fn open_flags_to_perm(flags: i32, mode: u32) -> FilePerms {
 	perms
 }
 
+/// Converts a null-terminated path of the application. Paths, which are
+/// not valid UTF-8, are rejected with `EINVAL`.
+fn path_str<'a>(name: *const u8) -> Result<&'a str, i32> {
+	unsafe { CStr::from_ptr(name as _) }
+		.to_str()
+		.map_err(|_| EINVAL)
+}
+
+/// Converts the buffer array of `readv` and `writev`. The number of buffers
+/// and their total length are checked as by POSIX.
+fn iovec_slice<'a>(iov: *const IoVec, iovcnt: i32) -> Result<&'a [IoVec], i32> {
+	if !(0..=IOV_MAX).contains(&iovcnt) {
+		return Err(EINVAL);
+	} else if iovcnt == 0 {
+		return Ok(&[]);
+	}
+
+	let iov = unsafe { slice::from_raw_parts(iov, iovcnt as usize) };
+	iov.iter()
+		.try_fold(0usize, |total, iov| total.checked_add(iov.iov_len))
+		.filter(|total| *total <= isize::MAX as usize)
+		.ok_or(EINVAL)?;
+
+	Ok(iov)
+}
+
 pub trait SyscallInterface: Send + Sync {
 	fn init(&self) {
 		// Interface-specific initialization steps.
@@ -117,9 +154,17 @@ pub trait SyscallInterface: Send + Sync {
 		arch::processor::shutdown()
 	}
 
-	fn get_mac_address(&self) -> Result<[u8; 6], ()> {
+	/// Returns the number of network interfaces, which are found by the drivers.
+	fn get_network_interface_count(&self) -> usize {
+		#[cfg(not(target_arch = "aarch64"))]
+		return get_network_drivers().count();
+		#[cfg(target_arch = "aarch64")]
+		0
+	}
+
+	fn get_mac_address(&self, index: usize) -> Result<[u8; 6], ()> {
 		#[cfg(not(target_arch = "aarch64"))]
-		match get_network_driver() {
+		match get_network_driver(index) {
 			Some(driver) => Ok(driver.lock().get_mac_address()),
 			_ => Err(()),
 		}
@@ -127,9 +172,9 @@ pub trait SyscallInterface: Send + Sync {
 		Err(())
 	}
 
-	fn get_mtu(&self) -> Result<u16, ()> {
+	fn get_mtu(&self, index: usize) -> Result<u16, ()> {
 		#[cfg(not(target_arch = "aarch64"))]
-		match get_network_driver() {
+		match get_network_driver(index) {
 			Some(driver) => Ok(driver.lock().get_mtu()),
 			_ => Err(()),
 		}
@@ -137,9 +182,9 @@ pub trait SyscallInterface: Send + Sync {
 		Err(())
 	}
 
-	fn has_packet(&self) -> bool {
+	fn has_packet(&self, index: usize) -> bool {
 		#[cfg(not(target_arch = "aarch64"))]
-		match get_network_driver() {
+		match get_network_driver(index) {
 			Some(driver) => driver.lock().has_packet(),
 			_ => false,
 		}
@@ -147,9 +192,9 @@ pub trait SyscallInterface: Send + Sync {
 		false
 	}
 
-	fn get_tx_buffer(&self, len: usize) -> Result<(*mut u8, usize), ()> {
+	fn get_tx_buffer(&self, index: usize, len: usize) -> Result<(*mut u8, usize), ()> {
 		#[cfg(not(target_arch = "aarch64"))]
-		match get_network_driver() {
+		match get_network_driver(index) {
 			Some(driver) => driver.lock().get_tx_buffer(len),
 			_ => Err(()),
 		}
@@ -157,9 +202,9 @@ pub trait SyscallInterface: Send + Sync {
 		Err(())
 	}
 
-	fn free_tx_buffer(&self, handle: usize) -> Result<(), ()> {
+	fn free_tx_buffer(&self, index: usize, handle: usize) -> Result<(), ()> {
 		#[cfg(not(target_arch = "aarch64"))]
-		match get_network_driver() {
+		match get_network_driver(index) {
 			Some(driver) => {
 				driver.lock().free_tx_buffer(handle);
 				Ok(())
@@ -170,9 +215,9 @@ pub trait SyscallInterface: Send + Sync {
 		Err(())
 	}
 
-	fn send_tx_buffer(&self, handle: usize, len: usize) -> Result<(), ()> {
+	fn send_tx_buffer(&self, index: usize, handle: usize, len: usize) -> Result<(), ()> {
 		#[cfg(not(target_arch = "aarch64"))]
-		match get_network_driver() {
+		match get_network_driver(index) {
 			Some(driver) => driver.lock().send_tx_buffer(handle, len),
 			_ => Err(()),
 		}
@@ -180,9 +225,9 @@ pub trait SyscallInterface: Send + Sync {
 		Err(())
 	}
 
-	fn receive_rx_buffer(&self) -> Result<(&'static mut [u8], usize), ()> {
+	fn receive_rx_buffer(&self, index: usize) -> Result<(&'static mut [u8], usize), ()> {
 		#[cfg(not(target_arch = "aarch64"))]
-		match get_network_driver() {
+		match get_network_driver(index) {
 			Some(driver) => driver.lock().receive_rx_buffer(),
 			_ => Err(()),
 		}
@@ -190,9 +235,9 @@ pub trait SyscallInterface: Send + Sync {
 		Err(())
 	}
 
-	fn rx_buffer_consumed(&self, handle: usize) -> Result<(), ()> {
+	fn rx_buffer_consumed(&self, index: usize, handle: usize) -> Result<(), ()> {
 		#[cfg(not(target_arch = "aarch64"))]
-		match get_network_driver() {
+		match get_network_driver(index) {
 			Some(driver) => {
 				driver.lock().rx_buffer_consumed(handle);
 				Ok(())
@@ -211,14 +256,16 @@ pub trait SyscallInterface: Send + Sync {
 
 	#[cfg(target_arch = "x86_64")]
 	fn unlink(&self, name: *const u8) -> i32 {
-		let name = unsafe { CStr::from_ptr(name as _) }.to_str().unwrap();
+		let name = match path_str(name) {
+			Ok(name) => name,
+			Err(errno) => return -errno,
+		};
 		debug!("unlink {}", name);
 
-		fs::FILESYSTEM
-			.lock()
-			.unlink(name)
-			.expect("Unlinking failed!"); // TODO: error handling
-		0
+		match fs::FILESYSTEM.lock().unlink(name) {
+			Ok(()) => 0,
+			Err(err) => -err.errno(),
+		}
 	}
 
 	#[cfg(not(target_arch = "x86_64"))]
@@ -233,89 +280,444 @@ pub trait SyscallInterface: Send + Sync {
 		//! flags is bitmask of O_DEC_* defined above.
 		//! (taken from rust stdlib/sys hermit target )
 
-		let name = unsafe { CStr::from_ptr(name as _) }.to_str().unwrap();
+		let name = match path_str(name) {
+			Ok(name) => name,
+			Err(errno) => return -errno,
+		};
 		debug!("Open {}, {}, {}", name, flags, mode);
 
 		let mut fs = fs::FILESYSTEM.lock();
-		let fd = fs.open(name, open_flags_to_perm(flags, mode as u32));
-
-		if let Ok(fd) = fd {
-			fd as i32
-		} else {
-			-1
+		match fs.open(name, open_flags_to_perm(flags, mode as u32)) {
+			Ok(fd) => fd as i32,
+			Err(err) => -err.errno(),
 		}
 	}
 
 	fn close(&self, fd: i32) -> i32 {
-		// we don't have to close standard descriptors
-		if fd < 3 {
-			return 0;
+		// the object is closed after releasing the lock, because closing a socket may block
+		let object = fs::FILESYSTEM.lock().remove(fd as u64);
+
+		match object
+			.map_err(|err| err.errno())
+			.and_then(|object| object.flush())
+		{
+			Ok(()) => 0,
+			Err(errno) => -errno,
 		}
+	}
 
-		let mut fs = fs::FILESYSTEM.lock();
-		fs.close(fd as u64);
-		0
+	/// Returns a new file descriptor, which refers to the same object as `fd`.
+	fn dup(&self, fd: i32) -> i32 {
+		match fs::FILESYSTEM.lock().dup(fd as u64) {
+			Ok(new_fd) => new_fd as i32,
+			Err(err) => -err.errno(),
+		}
 	}
 
-	#[cfg(not(target_arch = "x86_64"))]
-	fn read(&self, _fd: i32, _buf: *mut u8, _len: usize) -> isize {
-		debug!("read is unimplemented, returning -ENOSYS");
-		-ENOSYS as isize
+	/// Lets `new_fd` refer to the same object as `fd`. A previously opened
+	/// `new_fd` is closed.
+	fn dup2(&self, fd: i32, new_fd: i32) -> i32 {
+		if new_fd < 0 {
+			return -EBADF;
+		}
+
+		let replaced = fs::FILESYSTEM.lock().dup2(fd as u64, new_fd as u64);
+
+		match replaced {
+			Ok(replaced) => {
+				// as by POSIX, errors of closing the replaced object are ignored
+				if let Some(object) = replaced {
+					let _ = object.flush();
+				}
+				new_fd
+			}
+			Err(err) => -err.errno(),
+		}
 	}
 
-	#[cfg(target_arch = "x86_64")]
 	fn read(&self, fd: i32, buf: *mut u8, len: usize) -> isize {
 		debug!("Read! {}, {}", fd, len);
 
-		let mut fs = fs::FILESYSTEM.lock();
-		let mut read_bytes = 0;
-		fs.fd_op(fd as u64, |file: &mut Box<dyn PosixFile + Send>| {
-			let dat = file.read(len as u32).unwrap(); // TODO: might fail
-
-			read_bytes = dat.len();
-			unsafe {
-				core::slice::from_raw_parts_mut(buf, read_bytes).copy_from_slice(&dat);
+		// do we have an LwIP file descriptor?
+		#[cfg(feature = "newlib")]
+		{
+			if (fd & LWIP_FD_BIT) != 0 {
+				// take lock to protect LwIP
+				let _guard = LWIP_LOCK.lock();
+				let ret = unsafe { lwip_read(fd & !LWIP_FD_BIT, buf, len) };
+				if ret < 0 {
+					return -sys_lwip_get_errno() as isize;
+				}
+
+				return ret as isize;
 			}
-		});
+		}
+
+		assert!(len <= isize::MAX as usize);
+		let buf = unsafe { slice::from_raw_parts_mut(buf, len) };
 
-		read_bytes as isize
+		// do not hold the lock of the fd table, while a socket waits for data
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno() as isize,
+		};
+
+		match object.read(buf) {
+			Ok(read_bytes) => read_bytes as isize,
+			Err(errno) => -errno as isize,
+		}
 	}
 
 	fn write(&self, fd: i32, buf: *const u8, len: usize) -> isize {
+		// do we have an LwIP file descriptor?
+		#[cfg(feature = "newlib")]
+		{
+			if (fd & LWIP_FD_BIT) != 0 {
+				// take lock to protect LwIP
+				let _guard = LWIP_LOCK.lock();
+				let ret = unsafe { lwip_write(fd & !LWIP_FD_BIT, buf, len) };
+				if ret < 0 {
+					return -sys_lwip_get_errno() as isize;
+				}
+
+				return ret as isize;
+			}
+		}
+
 		assert!(len <= isize::MAX as usize);
 		let buf = unsafe { slice::from_raw_parts(buf, len) };
 
-		if fd > 2 {
-			// Normal file
-			let mut written_bytes = 0;
-			let mut fs = fs::FILESYSTEM.lock();
-			fs.fd_op(fd as u64, |file: &mut Box<dyn PosixFile + Send>| {
-				written_bytes = file.write(buf).unwrap(); // TODO: might fail
-			});
-			debug!("Write done! {}", written_bytes);
-			written_bytes as isize
-		} else {
-			// stdin/err/out all go to console
-			CONSOLE.lock().write_all(buf);
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno() as isize,
+		};
+
+		match object.write(buf) {
+			Ok(written_bytes) => written_bytes as isize,
+			Err(errno) => -errno as isize,
+		}
+	}
+
+	fn pread(&self, fd: i32, buf: *mut u8, len: usize, offset: i64) -> isize {
+		debug!("pread! {}, {}, {}", fd, len, offset);
+
+		if offset < 0 {
+			return -EINVAL as isize;
+		}
+		assert!(len <= isize::MAX as usize);
+		let buf = unsafe { slice::from_raw_parts_mut(buf, len) };
+
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno() as isize,
+		};
 
-			len as isize
+		match object.pread(buf, offset as u64) {
+			Ok(read_bytes) => read_bytes as isize,
+			Err(errno) => -errno as isize,
+		}
+	}
+
+	fn pwrite(&self, fd: i32, buf: *const u8, len: usize, offset: i64) -> isize {
+		debug!("pwrite! {}, {}, {}", fd, len, offset);
+
+		if offset < 0 {
+			return -EINVAL as isize;
+		}
+		assert!(len <= isize::MAX as usize);
+		let buf = unsafe { slice::from_raw_parts(buf, len) };
+
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno() as isize,
+		};
+
+		match object.pwrite(buf, offset as u64) {
+			Ok(written_bytes) => written_bytes as isize,
+			Err(errno) => -errno as isize,
+		}
+	}
+
+	fn readv(&self, fd: i32, iov: *const IoVec, iovcnt: i32) -> isize {
+		debug!("readv! {}, {}", fd, iovcnt);
+
+		let iov = match iovec_slice(iov, iovcnt) {
+			Ok(iov) => iov,
+			Err(errno) => return -errno as isize,
+		};
+		let mut bufs: Vec<&mut [u8]> = iov
+			.iter()
+			.map(|iov| unsafe { slice::from_raw_parts_mut(iov.iov_base, iov.iov_len) })
+			.collect();
+
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno() as isize,
+		};
+
+		match object.readv(&mut bufs) {
+			Ok(read_bytes) => read_bytes as isize,
+			Err(errno) => -errno as isize,
+		}
+	}
+
+	fn writev(&self, fd: i32, iov: *const IoVec, iovcnt: i32) -> isize {
+		debug!("writev! {}, {}", fd, iovcnt);
+
+		let iov = match iovec_slice(iov, iovcnt) {
+			Ok(iov) => iov,
+			Err(errno) => return -errno as isize,
+		};
+		let bufs: Vec<&[u8]> = iov
+			.iter()
+			.map(|iov| unsafe { slice::from_raw_parts(iov.iov_base as *const u8, iov.iov_len) })
+			.collect();
+
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno() as isize,
+		};
+
+		match object.writev(&bufs) {
+			Ok(written_bytes) => written_bytes as isize,
+			Err(errno) => -errno as isize,
+		}
+	}
+
+	fn fsync(&self, fd: i32, datasync: bool) -> i32 {
+		debug!("fsync! {}, {}", fd, datasync);
+
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno(),
+		};
+
+		match object.fsync(datasync) {
+			Ok(()) => 0,
+			Err(errno) => -errno,
+		}
+	}
+
+	fn ftruncate(&self, fd: i32, length: i64) -> i32 {
+		debug!("ftruncate! {}, {}", fd, length);
+
+		if length < 0 {
+			return -EINVAL;
+		}
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno(),
+		};
+
+		match object.ftruncate(length as u64) {
+			Ok(()) => 0,
+			Err(errno) => -errno,
+		}
+	}
+
+	fn fallocate(&self, fd: i32, mode: i32, offset: i64, len: i64) -> i32 {
+		debug!("fallocate! {}, {:#x}, {}, {}", fd, mode, offset, len);
+
+		if offset < 0 || len <= 0 {
+			return -EINVAL;
+		}
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno(),
+		};
+
+		match object.fallocate(mode as u32, offset as u64, len as u64) {
+			Ok(()) => 0,
+			Err(errno) => -errno,
 		}
 	}
 
 	fn lseek(&self, fd: i32, offset: isize, whence: i32) -> isize {
 		debug!("lseek! {}, {}, {}", fd, offset, whence);
 
-		let mut fs = fs::FILESYSTEM.lock();
-		let mut ret = 0;
-		fs.fd_op(fd as u64, |file: &mut Box<dyn PosixFile + Send>| {
-			ret = file.lseek(offset, whence.try_into().unwrap()).unwrap(); // TODO: might fail
-		});
+		let whence = match SeekWhence::try_from(whence) {
+			Ok(whence) => whence,
+			Err(_) => return -EINVAL as isize,
+		};
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno() as isize,
+		};
 
-		ret as isize
+		match object.lseek(offset, whence) {
+			Ok(offset) => offset as isize,
+			Err(errno) => -errno as isize,
+		}
 	}
 
-	fn stat(&self, _file: *const u8, _st: usize) -> i32 {
-		info!("stat is unimplemented");
-		-ENOSYS
+	fn mkdir(&self, name: *const u8, mode: u32) -> i32 {
+		let name = match path_str(name) {
+			Ok(name) => name,
+			Err(errno) => return -errno,
+		};
+		debug!("mkdir {}, {:#o}", name, mode);
+
+		match fs::FILESYSTEM.lock().mkdir(name, mode) {
+			Ok(()) => 0,
+			Err(err) => -err.errno(),
+		}
+	}
+
+	fn rmdir(&self, name: *const u8) -> i32 {
+		let name = match path_str(name) {
+			Ok(name) => name,
+			Err(errno) => return -errno,
+		};
+		debug!("rmdir {}", name);
+
+		match fs::FILESYSTEM.lock().rmdir(name) {
+			Ok(()) => 0,
+			Err(err) => -err.errno(),
+		}
+	}
+
+	fn opendir(&self, name: *const u8) -> i32 {
+		let name = match path_str(name) {
+			Ok(name) => name,
+			Err(errno) => return -errno,
+		};
+		debug!("opendir {}", name);
+
+		match fs::FILESYSTEM.lock().opendir(name) {
+			Ok(fd) => fd as i32,
+			Err(err) => -err.errno(),
+		}
+	}
+
+	/// Stores the next directory entry in `dirent` and returns 1 or
+	/// returns 0, if the end of the directory is reached.
+	fn readdir(&self, fd: i32, dirent: *mut Dirent) -> i32 {
+		let entry = match fs::FILESYSTEM.lock().readdir(fd as u64) {
+			Ok(Some(entry)) => entry,
+			Ok(None) => return 0,
+			Err(err) => return -err.errno(),
+		};
+
+		match Dirent::try_from(&entry) {
+			Ok(entry) => {
+				unsafe {
+					dirent.write(entry);
+				}
+				1
+			}
+			Err(err) => -err.errno(),
+		}
+	}
+
+	fn closedir(&self, fd: i32) -> i32 {
+		let dir = fs::FILESYSTEM.lock().closedir(fd as u64);
+
+		match dir {
+			Ok(_) => 0,
+			Err(err) => -err.errno(),
+		}
+	}
+
+	fn rename(&self, old_name: *const u8, new_name: *const u8) -> i32 {
+		let old_name = match path_str(old_name) {
+			Ok(old_name) => old_name,
+			Err(errno) => return -errno,
+		};
+		let new_name = match path_str(new_name) {
+			Ok(new_name) => new_name,
+			Err(errno) => return -errno,
+		};
+		debug!("rename {} to {}", old_name, new_name);
+
+		match fs::FILESYSTEM.lock().rename(old_name, new_name) {
+			Ok(()) => 0,
+			Err(err) => -err.errno(),
+		}
+	}
+
+	/// Stores the metadata of a file in `stat`. Symbolic links are followed.
+	fn stat(&self, name: *const u8, stat: *mut FileAttr) -> i32 {
+		let name = match path_str(name) {
+			Ok(name) => name,
+			Err(errno) => return -errno,
+		};
+		debug!("stat {}", name);
+
+		match fs::FILESYSTEM.lock().stat(name) {
+			Ok(attr) => {
+				unsafe {
+					stat.write(attr);
+				}
+				0
+			}
+			Err(err) => -err.errno(),
+		}
+	}
+
+	/// Stores the metadata of a file in `stat` without following symbolic links.
+	fn lstat(&self, name: *const u8, stat: *mut FileAttr) -> i32 {
+		let name = match path_str(name) {
+			Ok(name) => name,
+			Err(errno) => return -errno,
+		};
+		debug!("lstat {}", name);
+
+		match fs::FILESYSTEM.lock().lstat(name) {
+			Ok(attr) => {
+				unsafe {
+					stat.write(attr);
+				}
+				0
+			}
+			Err(err) => -err.errno(),
+		}
+	}
+
+	fn fstat(&self, fd: i32, stat: *mut FileAttr) -> i32 {
+		debug!("fstat {}", fd);
+
+		let object = match fs::FILESYSTEM.lock().get(fd as u64) {
+			Ok(object) => object,
+			Err(err) => return -err.errno(),
+		};
+
+		match object.fstat() {
+			Ok(attr) => {
+				unsafe {
+					stat.write(attr);
+				}
+				0
+			}
+			Err(errno) => -errno,
+		}
+	}
+
+	/// Copies the current working directory including the terminating null byte
+	/// into `buf`. Returns `-ERANGE`, if `size` is too small.
+	fn getcwd(&self, buf: *mut u8, size: usize) -> i32 {
+		let fs = fs::FILESYSTEM.lock();
+		let cwd = fs.getcwd().as_bytes();
+		if cwd.len() >= size {
+			return -ERANGE;
+		}
+
+		unsafe {
+			core::ptr::copy_nonoverlapping(cwd.as_ptr(), buf, cwd.len());
+			buf.add(cwd.len()).write(0);
+		}
+		0
+	}
+
+	fn chdir(&self, name: *const u8) -> i32 {
+		let name = match path_str(name) {
+			Ok(name) => name,
+			Err(errno) => return -errno,
+		};
+		debug!("chdir {}", name);
+
+		match fs::FILESYSTEM.lock().chdir(name) {
+			Ok(()) => 0,
+			Err(err) => -err.errno(),
+		}
 	}
 }
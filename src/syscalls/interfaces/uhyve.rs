@@ -7,11 +7,11 @@ use x86::io::*;
 use crate::arch;
 use crate::arch::mm::paging;
 use crate::arch::mm::{PhysAddr, VirtAddr};
-use crate::syscalls::interfaces::SyscallInterface;
-#[cfg(feature = "newlib")]
-use crate::syscalls::lwip::sys_lwip_get_errno;
-#[cfg(feature = "newlib")]
-use crate::syscalls::{LWIP_FD_BIT, LWIP_LOCK};
+use crate::errno::ENOSYS;
+use crate::syscalls::fs::{self, FileAttr, FileError, PosixFile, SeekWhence};
+use crate::syscalls::interfaces::{
+	open_flags_to_perm, path_str, SyscallInterface, SEEK_CUR, SEEK_END, SEEK_SET,
+};
 
 const UHYVE_PORT_WRITE: u16 = 0x400;
 const UHYVE_PORT_OPEN: u16 = 0x440;
@@ -23,12 +23,6 @@ const UHYVE_PORT_CMDSIZE: u16 = 0x740;
 const UHYVE_PORT_CMDVAL: u16 = 0x780;
 const UHYVE_PORT_UNLINK: u16 = 0x840;
 
-#[cfg(feature = "newlib")]
-extern "C" {
-	fn lwip_write(fd: i32, buf: *const u8, len: usize) -> i32;
-	fn lwip_read(fd: i32, buf: *mut u8, len: usize) -> i32;
-}
-
 /// forward a request to the hypervisor uhyve
 #[inline]
 #[cfg(target_arch = "x86_64")]
@@ -62,6 +56,9 @@ fn uhyve_send<T>(port: u16, data: &mut T) {
 
 const MAX_ARGC_ENVC: usize = 128;
 
+/// Maximum number of bytes, which are read into an intermediate buffer
+const MAX_READ_LEN: usize = 0x10_0000;
+
 #[repr(C, packed)]
 struct SysCmdsize {
 	argc: i32,
@@ -201,27 +198,51 @@ impl SysLseek {
 pub struct Uhyve;
 
 impl SyscallInterface for Uhyve {
+	/// Opens a file of the mounted filesystems. Paths, which are not covered by
+	/// a mount point, are opened on the host and inserted into the fd table of the kernel.
 	fn open(&self, name: *const u8, flags: i32, mode: i32) -> i32 {
+		let path = match path_str(name) {
+			Ok(path) => path,
+			Err(errno) => return -errno,
+		};
+		if fs::FILESYSTEM.lock().is_mounted(path) {
+			let perms = open_flags_to_perm(flags, mode as u32);
+			return match fs::FILESYSTEM.lock().open(path, perms) {
+				Ok(fd) => fd as i32,
+				Err(err) => -err.errno(),
+			};
+		}
+
 		let mut sysopen = SysOpen::new(VirtAddr(name as u64), flags, mode);
 		uhyve_send(UHYVE_PORT_OPEN, &mut sysopen);
 
-		sysopen.ret
+		if sysopen.ret < 0 {
+			return sysopen.ret;
+		}
+
+		let file = Box::new(UhyveFile::new(sysopen.ret));
+		fs::FILESYSTEM.lock().add_file(file) as i32
 	}
 
+	/// Files of the mounted filesystems are removed by the kernel, other paths by the host.
 	fn unlink(&self, name: *const u8) -> i32 {
+		let path = match path_str(name) {
+			Ok(path) => path,
+			Err(errno) => return -errno,
+		};
+		if fs::FILESYSTEM.lock().is_mounted(path) {
+			return match fs::FILESYSTEM.lock().unlink(path) {
+				Ok(()) => 0,
+				Err(err) => -err.errno(),
+			};
+		}
+
 		let mut sysunlink = SysUnlink::new(VirtAddr(name as u64));
 		uhyve_send(UHYVE_PORT_UNLINK, &mut sysunlink);
 
 		sysunlink.ret
 	}
 
-	fn close(&self, fd: i32) -> i32 {
-		let mut sysclose = SysClose::new(fd);
-		uhyve_send(UHYVE_PORT_CLOSE, &mut sysclose);
-
-		sysclose.ret
-	}
-
 	/// ToDo: This function needs a description - also applies to trait in src/syscalls/interfaces/mod.rs
 	///
 	/// ToDo: Add Safety section under which circumctances this is safe/unsafe to use
@@ -281,62 +302,153 @@ impl SyscallInterface for Uhyve {
 		}
 	}
 
-	fn read(&self, fd: i32, buf: *mut u8, len: usize) -> isize {
-		// do we have an LwIP file descriptor?
-		#[cfg(feature = "newlib")]
-		{
-			if (fd & LWIP_FD_BIT) != 0 {
-				// take lock to protect LwIP
-				let _guard = LWIP_LOCK.lock();
-				let ret;
-
-				unsafe {
-					ret = lwip_read(fd & !LWIP_FD_BIT, buf as *mut u8, len);
-				}
-				if ret < 0 {
-					return -sys_lwip_get_errno() as isize;
-				}
-
-				return ret as isize;
-			}
+	/// Files of the mounted filesystems are resolved by the kernel. uhyve
+	/// offers no hypercall for the metadata of host files, so that other
+	/// paths aren't supported.
+	fn stat(&self, name: *const u8, stat: *mut FileAttr) -> i32 {
+		let path = match path_str(name) {
+			Ok(path) => path,
+			Err(errno) => return -errno,
+		};
+		if fs::FILESYSTEM.lock().is_mounted(path) {
+			return write_attr(fs::FILESYSTEM.lock().stat(path), stat);
 		}
 
-		let mut sysread = SysRead::new(fd, buf, len);
-		uhyve_send(UHYVE_PORT_READ, &mut sysread);
+		host_stat(path)
+	}
 
-		sysread.ret
+	/// Files of the mounted filesystems are resolved by the kernel. Like
+	/// `stat`, other paths aren't supported.
+	fn lstat(&self, name: *const u8, stat: *mut FileAttr) -> i32 {
+		let path = match path_str(name) {
+			Ok(path) => path,
+			Err(errno) => return -errno,
+		};
+		if fs::FILESYSTEM.lock().is_mounted(path) {
+			return write_attr(fs::FILESYSTEM.lock().lstat(path), stat);
+		}
+
+		host_stat(path)
 	}
+}
 
-	fn write(&self, fd: i32, buf: *const u8, len: usize) -> isize {
-		// do we have an LwIP file descriptor?
-		#[cfg(feature = "newlib")]
-		{
-			if (fd & LWIP_FD_BIT) != 0 {
-				// take lock to protect LwIP
-				let _guard = LWIP_LOCK.lock();
-				let ret;
-
-				unsafe {
-					ret = lwip_write(fd & !LWIP_FD_BIT, buf as *const u8, len);
-				}
-				if ret < 0 {
-					return -sys_lwip_get_errno() as isize;
-				}
-
-				return ret as isize;
+/// Stores the metadata of a file of the mounted filesystems in `stat`.
+fn write_attr(attr: Result<FileAttr, FileError>, stat: *mut FileAttr) -> i32 {
+	match attr {
+		Ok(attr) => {
+			unsafe {
+				stat.write(attr);
 			}
+			0
 		}
+		Err(err) => -err.errno(),
+	}
+}
+
+/// The metadata of host files is unavailable, because uhyve has no hypercall for it.
+fn host_stat(path: &str) -> i32 {
+	debug!(
+		"stat of host file {} is unsupported, returning -ENOSYS",
+		path
+	);
+	-ENOSYS
+}
+
+/// A file descriptor of the host, whose operations are forwarded to uhyve
+/// (except for `fstat`, for which uhyve has no hypercall)
+#[derive(Debug)]
+pub(crate) struct UhyveFile {
+	fd: i32,
+}
 
-		let mut syswrite = SysWrite::new(fd, buf, len);
+impl UhyveFile {
+	pub(crate) fn new(fd: i32) -> Self {
+		Self { fd }
+	}
+
+	/// uhyve has no positional hypercalls, so the offset of the host file is moved
+	/// to `offset` for the operation `f` and restored afterwards. This is not
+	/// observable, because the open file is locked during the call.
+	fn at_offset<T>(
+		&mut self,
+		offset: u64,
+		f: impl FnOnce(&mut Self) -> Result<T, FileError>,
+	) -> Result<T, FileError> {
+		let offset = isize::try_from(offset).map_err(|_| FileError::EINVAL)?;
+		let current = self
+			.lseek(0, SeekWhence::Cur)
+			.map_err(|_| FileError::ESPIPE)?;
+		self.lseek(offset, SeekWhence::Set)?;
+		let result = f(self);
+		self.lseek(current as isize, SeekWhence::Set)?;
+		result
+	}
+}
+
+impl PosixFile for UhyveFile {
+	fn close(&mut self) -> Result<(), FileError> {
+		// the standard streams of the host stay open
+		if self.fd < 3 {
+			return Ok(());
+		}
+
+		let mut sysclose = SysClose::new(self.fd);
+		uhyve_send(UHYVE_PORT_CLOSE, &mut sysclose);
+
+		if sysclose.ret < 0 {
+			Err(FileError::EIO)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Reads at most `MAX_READ_LEN` bytes, so that a large `len` does not exhaust the heap.
+	fn read(&mut self, len: u32) -> Result<Vec<u8>, FileError> {
+		let mut buf = vec![0; (len as usize).min(MAX_READ_LEN)];
+		let len = self.read_into(&mut buf)?;
+		buf.truncate(len);
+
+		Ok(buf)
+	}
+
+	/// The host writes straight into `buf`.
+	fn read_into(&mut self, buf: &mut [u8]) -> Result<usize, FileError> {
+		let mut sysread = SysRead::new(self.fd, buf.as_mut_ptr(), buf.len());
+		uhyve_send(UHYVE_PORT_READ, &mut sysread);
+
+		let ret = sysread.ret;
+		if ret < 0 {
+			return Err(FileError::EIO);
+		}
+
+		Ok(ret as usize)
+	}
+
+	fn write(&mut self, buf: &[u8]) -> Result<u64, FileError> {
+		let mut syswrite = SysWrite::new(self.fd, buf.as_ptr(), buf.len());
 		uhyve_send(UHYVE_PORT_WRITE, &mut syswrite);
 
-		syswrite.len as isize
+		Ok(syswrite.len as u64)
+	}
+
+	fn pread(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, FileError> {
+		self.at_offset(offset, |file| file.read_into(buf))
+	}
+
+	fn pwrite(&mut self, buf: &[u8], offset: u64) -> Result<usize, FileError> {
+		self.at_offset(offset, |file| file.write(buf).map(|len| len as usize))
 	}
 
-	fn lseek(&self, fd: i32, offset: isize, whence: i32) -> isize {
-		let mut syslseek = SysLseek::new(fd, offset, whence);
+	fn lseek(&mut self, offset: isize, whence: SeekWhence) -> Result<usize, FileError> {
+		let whence = match whence {
+			SeekWhence::Set => SEEK_SET,
+			SeekWhence::Cur => SEEK_CUR,
+			SeekWhence::End => SEEK_END,
+		};
+		let mut syslseek = SysLseek::new(self.fd, offset, whence);
 		uhyve_send(UHYVE_PORT_LSEEK, &mut syslseek);
 
-		syslseek.offset
+		let offset = syslseek.offset;
+		offset.try_into().map_err(|_| FileError::EINVAL)
 	}
 }
@@ -3,6 +3,7 @@
 use crate::env;
 #[cfg(feature = "newlib")]
 use crate::synch::spinlock::SpinlockIrqSave;
+use crate::syscalls::fs::{Dirent, FileAttr, IoVec};
 use crate::syscalls::interfaces::SyscallInterface;
 #[cfg(target_os = "none")]
 use crate::{__sys_free, __sys_malloc, __sys_realloc};
@@ -64,6 +65,8 @@ pub(crate) fn init() {
 		SYS.init();
 	}
 
+	fs::init();
+
 	random_init();
 	#[cfg(feature = "newlib")]
 	sbrk_init();
@@ -130,6 +133,27 @@ pub extern "C" fn sys_close(fd: i32) -> i32 {
 	kernel_function!(__sys_close(fd))
 }
 
+extern "C" fn __sys_dup(fd: i32) -> i32 {
+	unsafe { SYS.dup(fd) }
+}
+
+/// Returns the lowest free file descriptor, which refers to the same
+/// file, stream or socket as `fd`.
+#[no_mangle]
+pub extern "C" fn sys_dup(fd: i32) -> i32 {
+	kernel_function!(__sys_dup(fd))
+}
+
+extern "C" fn __sys_dup2(fd: i32, new_fd: i32) -> i32 {
+	unsafe { SYS.dup2(fd, new_fd) }
+}
+
+/// Lets `new_fd` refer to the same object as `fd`, e.g. to redirect stdout.
+#[no_mangle]
+pub extern "C" fn sys_dup2(fd: i32, new_fd: i32) -> i32 {
+	kernel_function!(__sys_dup2(fd, new_fd))
+}
+
 extern "C" fn __sys_read(fd: i32, buf: *mut u8, len: usize) -> isize {
 	unsafe { SYS.read(fd, buf, len) }
 }
@@ -147,6 +171,78 @@ pub extern "C" fn sys_write(fd: i32, buf: *const u8, len: usize) -> isize {
 	kernel_function!(__sys_write(fd, buf, len))
 }
 
+extern "C" fn __sys_pread(fd: i32, buf: *mut u8, len: usize, offset: i64) -> isize {
+	unsafe { SYS.pread(fd, buf, len, offset) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_pread(fd: i32, buf: *mut u8, len: usize, offset: i64) -> isize {
+	kernel_function!(__sys_pread(fd, buf, len, offset))
+}
+
+extern "C" fn __sys_pwrite(fd: i32, buf: *const u8, len: usize, offset: i64) -> isize {
+	unsafe { SYS.pwrite(fd, buf, len, offset) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_pwrite(fd: i32, buf: *const u8, len: usize, offset: i64) -> isize {
+	kernel_function!(__sys_pwrite(fd, buf, len, offset))
+}
+
+extern "C" fn __sys_readv(fd: i32, iov: *const IoVec, iovcnt: i32) -> isize {
+	unsafe { SYS.readv(fd, iov, iovcnt) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_readv(fd: i32, iov: *const IoVec, iovcnt: i32) -> isize {
+	kernel_function!(__sys_readv(fd, iov, iovcnt))
+}
+
+extern "C" fn __sys_writev(fd: i32, iov: *const IoVec, iovcnt: i32) -> isize {
+	unsafe { SYS.writev(fd, iov, iovcnt) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_writev(fd: i32, iov: *const IoVec, iovcnt: i32) -> isize {
+	kernel_function!(__sys_writev(fd, iov, iovcnt))
+}
+
+extern "C" fn __sys_fsync(fd: i32) -> i32 {
+	unsafe { SYS.fsync(fd, false) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_fsync(fd: i32) -> i32 {
+	kernel_function!(__sys_fsync(fd))
+}
+
+extern "C" fn __sys_fdatasync(fd: i32) -> i32 {
+	unsafe { SYS.fsync(fd, true) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_fdatasync(fd: i32) -> i32 {
+	kernel_function!(__sys_fdatasync(fd))
+}
+
+extern "C" fn __sys_ftruncate(fd: i32, length: i64) -> i32 {
+	unsafe { SYS.ftruncate(fd, length) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_ftruncate(fd: i32, length: i64) -> i32 {
+	kernel_function!(__sys_ftruncate(fd, length))
+}
+
+extern "C" fn __sys_fallocate(fd: i32, mode: i32, offset: i64, len: i64) -> i32 {
+	unsafe { SYS.fallocate(fd, mode, offset, len) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_fallocate(fd: i32, mode: i32, offset: i64, len: i64) -> i32 {
+	kernel_function!(__sys_fallocate(fd, mode, offset, len))
+}
+
 extern "C" fn __sys_lseek(fd: i32, offset: isize, whence: i32) -> isize {
 	unsafe { SYS.lseek(fd, offset, whence) }
 }
@@ -156,11 +252,108 @@ pub extern "C" fn sys_lseek(fd: i32, offset: isize, whence: i32) -> isize {
 	kernel_function!(__sys_lseek(fd, offset, whence))
 }
 
-extern "C" fn __sys_stat(file: *const u8, st: usize) -> i32 {
+extern "C" fn __sys_mkdir(name: *const u8, mode: u32) -> i32 {
+	unsafe { SYS.mkdir(name, mode) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_mkdir(name: *const u8, mode: u32) -> i32 {
+	kernel_function!(__sys_mkdir(name, mode))
+}
+
+extern "C" fn __sys_rmdir(name: *const u8) -> i32 {
+	unsafe { SYS.rmdir(name) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_rmdir(name: *const u8) -> i32 {
+	kernel_function!(__sys_rmdir(name))
+}
+
+extern "C" fn __sys_opendir(name: *const u8) -> i32 {
+	unsafe { SYS.opendir(name) }
+}
+
+/// Opens a directory and returns its file descriptor or a negative `errno` value.
+#[no_mangle]
+pub extern "C" fn sys_opendir(name: *const u8) -> i32 {
+	kernel_function!(__sys_opendir(name))
+}
+
+extern "C" fn __sys_readdir(fd: i32, dirent: *mut Dirent) -> i32 {
+	unsafe { SYS.readdir(fd, dirent) }
+}
+
+/// Reads the next entry of a directory. Returns 1, if `dirent` is filled,
+/// 0 at the end of the directory or a negative `errno` value.
+#[no_mangle]
+pub extern "C" fn sys_readdir(fd: i32, dirent: *mut Dirent) -> i32 {
+	kernel_function!(__sys_readdir(fd, dirent))
+}
+
+extern "C" fn __sys_closedir(fd: i32) -> i32 {
+	unsafe { SYS.closedir(fd) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_closedir(fd: i32) -> i32 {
+	kernel_function!(__sys_closedir(fd))
+}
+
+extern "C" fn __sys_rename(old_name: *const u8, new_name: *const u8) -> i32 {
+	unsafe { SYS.rename(old_name, new_name) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_rename(old_name: *const u8, new_name: *const u8) -> i32 {
+	kernel_function!(__sys_rename(old_name, new_name))
+}
+
+extern "C" fn __sys_stat(file: *const u8, st: *mut FileAttr) -> i32 {
 	unsafe { SYS.stat(file, st) }
 }
 
+/// Stores the metadata of a file in `st`. Symbolic links are followed.
 #[no_mangle]
-pub extern "C" fn sys_stat(file: *const u8, st: usize) -> i32 {
+pub extern "C" fn sys_stat(file: *const u8, st: *mut FileAttr) -> i32 {
 	kernel_function!(__sys_stat(file, st))
 }
+
+extern "C" fn __sys_lstat(file: *const u8, st: *mut FileAttr) -> i32 {
+	unsafe { SYS.lstat(file, st) }
+}
+
+/// Stores the metadata of a file in `st` without following symbolic links.
+#[no_mangle]
+pub extern "C" fn sys_lstat(file: *const u8, st: *mut FileAttr) -> i32 {
+	kernel_function!(__sys_lstat(file, st))
+}
+
+extern "C" fn __sys_fstat(fd: i32, st: *mut FileAttr) -> i32 {
+	unsafe { SYS.fstat(fd, st) }
+}
+
+/// Stores the metadata of an opened file in `st`.
+#[no_mangle]
+pub extern "C" fn sys_fstat(fd: i32, st: *mut FileAttr) -> i32 {
+	kernel_function!(__sys_fstat(fd, st))
+}
+
+extern "C" fn __sys_getcwd(buf: *mut u8, size: usize) -> i32 {
+	unsafe { SYS.getcwd(buf, size) }
+}
+
+/// Copies the absolute path of the current working directory into `buf`.
+#[no_mangle]
+pub extern "C" fn sys_getcwd(buf: *mut u8, size: usize) -> i32 {
+	kernel_function!(__sys_getcwd(buf, size))
+}
+
+extern "C" fn __sys_chdir(name: *const u8) -> i32 {
+	unsafe { SYS.chdir(name) }
+}
+
+#[no_mangle]
+pub extern "C" fn sys_chdir(name: *const u8) -> i32 {
+	kernel_function!(__sys_chdir(name))
+}
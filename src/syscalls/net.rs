@@ -1,84 +1,260 @@
+//! Network syscalls of the smoltcp-based network stack.
+//!
+//! Errors are reported as positive `errno` values, so that the standard
+//! library is able to derive the corresponding `io::ErrorKind`.
+//!
+//! Sockets are referenced by file descriptors, which share the fd table
+//! with the files. Hence, `sys_read`, `sys_write`, `sys_close` and
+//! `sys_dup` are applicable to sockets as well.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::errno::*;
 use crate::net::executor::block_on;
-use crate::net::{AsyncSocket, Handle};
-use crate::DEFAULT_KEEP_ALIVE_INTERVAL;
+use crate::net::{
+	AsyncListener, AsyncSocket, AsyncUdpSocket, InterfaceHandle, Ipv4Config, ListenerHandle,
+	TcpConfig,
+};
+use crate::syscalls::fs::{self, Object};
 
 use smoltcp::socket::TcpSocket;
 use smoltcp::time::Duration;
-use smoltcp::wire::IpAddress;
+use smoltcp::wire::{IpAddress, IpCidr};
+
+/// Hop limit of outgoing packets, if the socket does not override it
+const DEFAULT_HOP_LIMIT: u8 = 64;
+
+/// A socket in the fd table. The network stack releases the socket,
+/// when the last file descriptor referring to it is closed.
+#[derive(Debug)]
+pub(crate) enum Socket {
+	Tcp(InterfaceHandle),
+	Udp(InterfaceHandle),
+	Listener(ListenerHandle),
+}
+
+impl Socket {
+	pub(crate) fn read(&self, buffer: &mut [u8]) -> Result<usize, i32> {
+		match self {
+			Socket::Tcp(handle) => tcp_read(*handle, buffer),
+			Socket::Udp(handle) => udp_recv(*handle, buffer),
+			Socket::Listener(_) => Err(ENOTCONN),
+		}
+	}
+
+	pub(crate) fn write(&self, buffer: &[u8]) -> Result<usize, i32> {
+		match self {
+			Socket::Tcp(handle) => tcp_write(*handle, buffer),
+			Socket::Udp(handle) => udp_send(*handle, buffer),
+			Socket::Listener(_) => Err(ENOTCONN),
+		}
+	}
+}
+
+impl Drop for Socket {
+	fn drop(&mut self) {
+		let result = match self {
+			Socket::Tcp(handle) => AsyncSocket::from(*handle).close(),
+			Socket::Udp(handle) => AsyncUdpSocket::from(*handle).close(),
+			Socket::Listener(handle) => AsyncListener::from(*handle).close(),
+		};
 
+		if let Err(errno) = result {
+			debug!("Unable to close socket {:?}: {}", self, errno);
+		}
+	}
+}
+
+/// Inserts a socket into the fd table and returns its file descriptor.
+fn add_socket(socket: Socket) -> i32 {
+	let object = Object::Socket(Arc::new(socket));
+	fs::FILESYSTEM.lock().add_object(object) as i32
+}
+
+/// Returns the socket, which is referenced by `fd`.
+fn socket(fd: i32) -> Result<Arc<Socket>, i32> {
+	match fs::FILESYSTEM.lock().get(fd as u64) {
+		Ok(Object::Socket(socket)) => Ok(socket),
+		Ok(_) => Err(ENOTSOCK),
+		Err(err) => Err(err.errno()),
+	}
+}
+
+fn tcp_handle(fd: i32) -> Result<InterfaceHandle, i32> {
+	match *socket(fd)? {
+		Socket::Tcp(handle) => Ok(handle),
+		_ => Err(EOPNOTSUPP),
+	}
+}
+
+fn udp_handle(fd: i32) -> Result<InterfaceHandle, i32> {
+	match *socket(fd)? {
+		Socket::Udp(handle) => Ok(handle),
+		_ => Err(EOPNOTSUPP),
+	}
+}
+
+fn listener_handle(fd: i32) -> Result<ListenerHandle, i32> {
+	match *socket(fd)? {
+		Socket::Listener(handle) => Ok(handle),
+		_ => Err(EOPNOTSUPP),
+	}
+}
+
+/// Removes a socket from the fd table. The socket is closed, if no other
+/// file descriptor refers to it.
+fn close(fd: i32) -> Result<(), i32> {
+	socket(fd)?;
+	let socket = fs::FILESYSTEM.lock().remove(fd as u64);
+	socket.map(drop).map_err(|err| err.errno())
+}
+
+/// Connects to the given address through the interface, which is selected
+/// by the routing table.
 #[no_mangle]
-pub fn sys_tcp_stream_connect(ip: &[u8], port: u16, timeout: Option<u64>) -> Result<Handle, ()> {
-	let socket = AsyncSocket::new();
-	block_on(socket.connect(ip, port), timeout.map(Duration::from_millis))?.map_err(|_| ())
+pub fn sys_tcp_stream_connect(ip: &[u8], port: u16, timeout: Option<u64>) -> Result<i32, i32> {
+	sys_tcp_stream_connect_with(ip, port, timeout, TcpConfig::default())
 }
 
+/// Connects to the given address with a socket, whose buffer sizes and
+/// options are given by `config`.
 #[no_mangle]
-pub fn sys_tcp_stream_read(handle: Handle, buffer: &mut [u8]) -> Result<usize, ()> {
-	let socket = AsyncSocket::from(handle);
-	block_on(socket.read(buffer), None)?.map_err(|_| ())
+pub fn sys_tcp_stream_connect_with(
+	ip: &[u8],
+	port: u16,
+	timeout: Option<u64>,
+	config: TcpConfig,
+) -> Result<i32, i32> {
+	let interface = crate::net::route(ip)?;
+	connect(interface, ip, port, timeout, &config)
 }
 
+/// Connects to the given address through the interface with the index `interface`.
 #[no_mangle]
-pub fn sys_tcp_stream_write(handle: Handle, buffer: &[u8]) -> Result<usize, ()> {
+pub fn sys_tcp_stream_connect_on(
+	interface: usize,
+	ip: &[u8],
+	port: u16,
+	timeout: Option<u64>,
+) -> Result<i32, i32> {
+	connect(interface, ip, port, timeout, &TcpConfig::default())
+}
+
+fn connect(
+	interface: usize,
+	ip: &[u8],
+	port: u16,
+	timeout: Option<u64>,
+	config: &TcpConfig,
+) -> Result<i32, i32> {
+	let socket = AsyncSocket::new(interface, config)?;
+	let result = block_on(socket.connect(ip, port), timeout.map(Duration::from_millis))
+		.map_err(|_| ETIMEDOUT)
+		.and_then(|result| result);
+
+	match result {
+		Ok(handle) => Ok(add_socket(Socket::Tcp(handle))),
+		Err(errno) => {
+			// Otherwise, the socket occupies a slot of the interface forever.
+			socket.destroy();
+			Err(errno)
+		}
+	}
+}
+
+fn tcp_read(handle: InterfaceHandle, buffer: &mut [u8]) -> Result<usize, i32> {
 	let socket = AsyncSocket::from(handle);
-	block_on(socket.write(buffer), None)?.map_err(|_| ())
+	let timeout = socket.options().read_timeout.map(Duration::from_millis);
+	block_on(socket.read(buffer), timeout).map_err(|_| EAGAIN)?
 }
 
-#[no_mangle]
-pub fn sys_tcp_stream_close(handle: Handle) -> Result<(), ()> {
+fn tcp_write(handle: InterfaceHandle, buffer: &[u8]) -> Result<usize, i32> {
 	let socket = AsyncSocket::from(handle);
-	block_on(socket.close(), None)?.map_err(|_| ())
+	let timeout = socket.options().write_timeout.map(Duration::from_millis);
+	block_on(socket.write(buffer), timeout).map_err(|_| EAGAIN)?
+}
+
+#[no_mangle]
+pub fn sys_tcp_stream_read(fd: i32, buffer: &mut [u8]) -> Result<usize, i32> {
+	tcp_read(tcp_handle(fd)?, buffer)
 }
 
-//ToDo: an enum, or at least constants would be better
 #[no_mangle]
-pub fn sys_tcp_stream_shutdown(handle: Handle, how: i32) -> Result<(), ()> {
+pub fn sys_tcp_stream_write(fd: i32, buffer: &[u8]) -> Result<usize, i32> {
+	tcp_write(tcp_handle(fd)?, buffer)
+}
+
+#[no_mangle]
+pub fn sys_tcp_stream_close(fd: i32) -> Result<(), i32> {
+	close(fd)
+}
+
+/// Shuts down the receiving half of the connection
+const SHUT_RD: i32 = 0;
+/// Shuts down the sending half of the connection
+const SHUT_WR: i32 = 1;
+/// Shuts down both halves of the connection
+const SHUT_RDWR: i32 = 2;
+
+#[no_mangle]
+pub fn sys_tcp_stream_shutdown(fd: i32, how: i32) -> Result<(), i32> {
+	let socket = AsyncSocket::from(tcp_handle(fd)?);
+
 	match how {
-		0 /* Read */ => {
-			trace!("Shutdown::Read is not implemented");
-			Ok(())
-		},
-		1 /* Write */ => {
-			sys_tcp_stream_close(handle)
-		},
-		2 /* Both */ => {
-			sys_tcp_stream_close(handle)
-		},
+		SHUT_RD => socket.shutdown_read(),
+		SHUT_WR => socket.shutdown_write(),
+		SHUT_RDWR => {
+			socket.shutdown_read()?;
+			socket.shutdown_write()
+		}
 		_ => {
-			panic!("Invalid shutdown argument {}", how);
-		},
+			debug!("Invalid shutdown argument {}", how);
+			Err(EINVAL)
+		}
 	}
 }
 
+/// Sets the read timeout in milliseconds. `None` blocks indefinitely.
 #[no_mangle]
-pub fn sys_tcp_stream_set_read_timeout(_handle: Handle, _timeout: Option<u64>) -> Result<(), ()> {
-	Err(())
+pub fn sys_tcp_stream_set_read_timeout(fd: i32, timeout: Option<u64>) -> Result<(), i32> {
+	let socket = AsyncSocket::from(tcp_handle(fd)?);
+	socket.set_options(|options| options.read_timeout = timeout)
 }
 
 #[no_mangle]
-pub fn sys_tcp_stream_get_read_timeout(_handle: Handle) -> Result<Option<u64>, ()> {
-	Err(())
+pub fn sys_tcp_stream_get_read_timeout(fd: i32) -> Result<Option<u64>, i32> {
+	let socket = AsyncSocket::from(tcp_handle(fd)?);
+	Ok(socket.options().read_timeout)
 }
 
+/// Sets the write timeout in milliseconds. `None` blocks indefinitely.
 #[no_mangle]
-pub fn sys_tcp_stream_set_write_timeout(_handle: Handle, _timeout: Option<u64>) -> Result<(), ()> {
-	Err(())
+pub fn sys_tcp_stream_set_write_timeout(fd: i32, timeout: Option<u64>) -> Result<(), i32> {
+	let socket = AsyncSocket::from(tcp_handle(fd)?);
+	socket.set_options(|options| options.write_timeout = timeout)
 }
 
 #[no_mangle]
-pub fn sys_tcp_stream_get_write_timeout(_handle: Handle) -> Result<Option<u64>, ()> {
-	Err(())
+pub fn sys_tcp_stream_get_write_timeout(fd: i32) -> Result<Option<u64>, i32> {
+	let socket = AsyncSocket::from(tcp_handle(fd)?);
+	Ok(socket.options().write_timeout)
 }
 
+/// Returns a new file descriptor for the connection, like `sys_dup`.
 #[deprecated(since = "0.1.14", note = "Please don't use this function")]
 #[no_mangle]
-pub fn sys_tcp_stream_duplicate(_handle: Handle) -> Result<Handle, ()> {
-	Err(())
+pub fn sys_tcp_stream_duplicate(fd: i32) -> Result<i32, i32> {
+	tcp_handle(fd)?;
+	let new_fd = fs::FILESYSTEM.lock().dup(fd as u64);
+	new_fd.map(|fd| fd as i32).map_err(|err| err.errno())
 }
 
 #[no_mangle]
-pub fn sys_tcp_stream_peek(_handle: Handle, _buf: &mut [u8]) -> Result<usize, ()> {
-	Err(())
+pub fn sys_tcp_stream_peek(fd: i32, buf: &mut [u8]) -> Result<usize, i32> {
+	let socket = AsyncSocket::from(tcp_handle(fd)?);
+	let timeout = socket.options().read_timeout.map(Duration::from_millis);
+	block_on(socket.peek(buf), timeout).map_err(|_| EAGAIN)?
 }
 
 /// If set, this option disables the Nagle algorithm. This means that segments are
@@ -86,53 +262,321 @@ pub fn sys_tcp_stream_peek(_handle: Handle, _buf: &mut [u8]) -> Result<usize, ()
 /// When not set, data is buffered until there is a sufficient amount to send out,
 /// thereby avoiding the frequent sending of small packets.
 #[no_mangle]
-pub fn sys_tcp_set_no_delay(handle: Handle, mode: bool) -> Result<(), ()> {
+pub fn sys_tcp_set_no_delay(fd: i32, mode: bool) -> Result<(), i32> {
+	let handle = tcp_handle(fd)?;
 	let mut guard = crate::net::NIC.lock();
-	let nic = guard.as_nic_mut().map_err(drop)?;
-	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle);
+	let nic = guard.nic_mut(handle.interface)?;
+	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle.socket);
 	socket.set_nagle_enabled(!mode);
 
 	Ok(())
 }
 
 #[no_mangle]
-pub fn sys_tcp_stream_set_nonblocking(_handle: Handle, mode: bool) -> Result<(), ()> {
-	// non-blocking mode is currently not support
-	// => return only an error, if `mode` is defined as `true`
-	if mode {
-		Err(())
-	} else {
-		Ok(())
+pub fn sys_tcp_get_no_delay(fd: i32) -> Result<bool, i32> {
+	let handle = tcp_handle(fd)?;
+	let mut guard = crate::net::NIC.lock();
+	let nic = guard.nic_mut(handle.interface)?;
+	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle.socket);
+
+	Ok(!socket.nagle_enabled())
+}
+
+/// Sets the interval in milliseconds between keep-alive segments, which
+/// are sent while the connection is idle. `None` disables keep-alive.
+#[no_mangle]
+pub fn sys_tcp_stream_set_keep_alive(fd: i32, interval: Option<u64>) -> Result<(), i32> {
+	if interval == Some(0) {
+		return Err(EINVAL);
 	}
+
+	let handle = tcp_handle(fd)?;
+	let mut guard = crate::net::NIC.lock();
+	let nic = guard.nic_mut(handle.interface)?;
+	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle.socket);
+	socket.set_keep_alive(interval.map(Duration::from_millis));
+
+	Ok(())
+}
+
+#[no_mangle]
+pub fn sys_tcp_stream_get_keep_alive(fd: i32) -> Result<Option<u64>, i32> {
+	let handle = tcp_handle(fd)?;
+	let mut guard = crate::net::NIC.lock();
+	let nic = guard.nic_mut(handle.interface)?;
+	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle.socket);
+
+	Ok(socket.keep_alive().map(|interval| interval.total_millis()))
+}
+
+/// Returns the size of the receive buffer in bytes.
+#[no_mangle]
+pub fn sys_tcp_stream_get_recv_buffer_size(fd: i32) -> Result<usize, i32> {
+	let handle = tcp_handle(fd)?;
+	let mut guard = crate::net::NIC.lock();
+	let nic = guard.nic_mut(handle.interface)?;
+	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle.socket);
+
+	Ok(socket.recv_capacity())
+}
+
+/// Returns the size of the send buffer in bytes.
+#[no_mangle]
+pub fn sys_tcp_stream_get_send_buffer_size(fd: i32) -> Result<usize, i32> {
+	let handle = tcp_handle(fd)?;
+	let mut guard = crate::net::NIC.lock();
+	let nic = guard.nic_mut(handle.interface)?;
+	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle.socket);
+
+	Ok(socket.send_capacity())
 }
 
+/// In non-blocking mode, reads and writes, which are not able to make
+/// progress, return immediately with an error instead of parking the task.
 #[no_mangle]
-pub fn sys_tcp_stream_set_tll(_handle: Handle, _ttl: u32) -> Result<(), ()> {
-	Err(())
+pub fn sys_tcp_stream_set_nonblocking(fd: i32, mode: bool) -> Result<(), i32> {
+	let socket = AsyncSocket::from(tcp_handle(fd)?);
+	socket.set_options(|options| options.nonblocking = mode)
 }
 
 #[no_mangle]
-pub fn sys_tcp_stream_get_tll(_handle: Handle) -> Result<u32, ()> {
-	Err(())
+pub fn sys_tcp_stream_set_tll(fd: i32, ttl: u32) -> Result<(), i32> {
+	// smoltcp panics on a hop limit of zero
+	let ttl: u8 = match ttl.try_into() {
+		Ok(0) | Err(_) => return Err(EINVAL),
+		Ok(ttl) => ttl,
+	};
+
+	let handle = tcp_handle(fd)?;
+	let mut guard = crate::net::NIC.lock();
+	let nic = guard.nic_mut(handle.interface)?;
+	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle.socket);
+	socket.set_hop_limit(Some(ttl));
+
+	Ok(())
+}
+
+#[no_mangle]
+pub fn sys_tcp_stream_get_tll(fd: i32) -> Result<u32, i32> {
+	let handle = tcp_handle(fd)?;
+	let mut guard = crate::net::NIC.lock();
+	let nic = guard.nic_mut(handle.interface)?;
+	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle.socket);
+
+	Ok(socket.hop_limit().unwrap_or(DEFAULT_HOP_LIMIT).into())
 }
 
 #[cfg(feature = "tcp")]
 #[no_mangle]
-pub fn sys_tcp_stream_peer_addr(handle: Handle) -> Result<(IpAddress, u16), ()> {
+pub fn sys_tcp_stream_peer_addr(fd: i32) -> Result<(IpAddress, u16), i32> {
+	let handle = tcp_handle(fd)?;
 	let mut guard = crate::net::NIC.lock();
-	let nic = guard.as_nic_mut().map_err(drop)?;
-	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle);
-	socket.set_keep_alive(Some(Duration::from_millis(DEFAULT_KEEP_ALIVE_INTERVAL)));
+	let nic = guard.nic_mut(handle.interface)?;
+	let socket = nic.iface.get_socket::<TcpSocket<'_>>(handle.socket);
 	let endpoint = socket.remote_endpoint();
 
 	Ok((endpoint.addr, endpoint.port))
 }
 
+/// Creates a TCP listener on the given local address. Up to `backlog`
+/// connections are accepted by the network stack before the application
+/// calls [`sys_tcp_listener_accept`].
+#[cfg(feature = "tcp")]
+#[no_mangle]
+pub fn sys_tcp_listener_bind(ip: &[u8], port: u16, backlog: usize) -> Result<i32, i32> {
+	sys_tcp_listener_bind_with(ip, port, backlog, TcpConfig::default())
+}
+
+/// Creates a TCP listener, whose accepted connections use the buffer
+/// sizes and options given by `config`.
+#[cfg(feature = "tcp")]
+#[no_mangle]
+pub fn sys_tcp_listener_bind_with(
+	ip: &[u8],
+	port: u16,
+	backlog: usize,
+	config: TcpConfig,
+) -> Result<i32, i32> {
+	let listener = AsyncListener::bind(None, ip, port, backlog, &config)?;
+
+	Ok(add_socket(Socket::Listener(listener.inner())))
+}
+
+/// Creates a TCP listener, which accepts only connections arriving
+/// at the interface with the index `interface`.
+#[cfg(feature = "tcp")]
+#[no_mangle]
+pub fn sys_tcp_listener_bind_on(
+	interface: usize,
+	ip: &[u8],
+	port: u16,
+	backlog: usize,
+) -> Result<i32, i32> {
+	let listener = AsyncListener::bind(Some(interface), ip, port, backlog, &TcpConfig::default())?;
+
+	Ok(add_socket(Socket::Listener(listener.inner())))
+}
+
+#[cfg(feature = "tcp")]
+#[no_mangle]
+pub fn sys_tcp_listener_accept(fd: i32) -> Result<(i32, IpAddress, u16), i32> {
+	let listener = AsyncListener::from(listener_handle(fd)?);
+	let (handle, endpoint) = block_on(listener.accept(), None).map_err(|_| ETIMEDOUT)??;
+	let fd = add_socket(Socket::Tcp(handle));
+
+	Ok((fd, endpoint.addr, endpoint.port))
+}
+
 #[cfg(feature = "tcp")]
 #[no_mangle]
-pub fn sys_tcp_listener_accept(port: u16) -> Result<(Handle, IpAddress, u16), ()> {
-	let socket = AsyncSocket::new();
-	let (addr, port) = block_on(socket.accept(port), None)?.map_err(|_| ())?;
+pub fn sys_tcp_listener_close(fd: i32) -> Result<(), i32> {
+	close(fd)
+}
+
+/// Binds a UDP socket to the given local address. The socket uses the
+/// interface, which owns the address, or the first interface for an
+/// empty address.
+#[no_mangle]
+pub fn sys_udp_bind(ip: &[u8], port: u16) -> Result<i32, i32> {
+	let interface = crate::net::local_interface(ip)?;
+	sys_udp_bind_on(interface, ip, port)
+}
+
+/// Binds a UDP socket on the interface with the index `interface`.
+#[no_mangle]
+pub fn sys_udp_bind_on(interface: usize, ip: &[u8], port: u16) -> Result<i32, i32> {
+	let socket = AsyncUdpSocket::new(interface)?;
+	if let Err(errno) = socket.bind(ip, port) {
+		let _ = socket.close();
+		return Err(errno);
+	}
+
+	Ok(add_socket(Socket::Udp(socket.inner())))
+}
+
+#[no_mangle]
+pub fn sys_udp_connect(fd: i32, ip: &[u8], port: u16) -> Result<(), i32> {
+	let socket = AsyncUdpSocket::from(udp_handle(fd)?);
+	socket.connect(ip, port)
+}
+
+#[no_mangle]
+pub fn sys_udp_send_to(fd: i32, buffer: &[u8], ip: &[u8], port: u16) -> Result<usize, i32> {
+	let socket = AsyncUdpSocket::from(udp_handle(fd)?);
+	block_on(socket.send_to(buffer, ip, port), None).map_err(|_| EAGAIN)?
+}
+
+fn udp_send(handle: InterfaceHandle, buffer: &[u8]) -> Result<usize, i32> {
+	let socket = AsyncUdpSocket::from(handle);
+	block_on(socket.send(buffer), None).map_err(|_| EAGAIN)?
+}
+
+fn udp_recv(handle: InterfaceHandle, buffer: &mut [u8]) -> Result<usize, i32> {
+	let socket = AsyncUdpSocket::from(handle);
+	block_on(socket.recv(buffer), None).map_err(|_| EAGAIN)?
+}
+
+#[no_mangle]
+pub fn sys_udp_send(fd: i32, buffer: &[u8]) -> Result<usize, i32> {
+	udp_send(udp_handle(fd)?, buffer)
+}
+
+#[no_mangle]
+pub fn sys_udp_recv_from(fd: i32, buffer: &mut [u8]) -> Result<(usize, IpAddress, u16), i32> {
+	let socket = AsyncUdpSocket::from(udp_handle(fd)?);
+	let (len, endpoint) = block_on(socket.recv_from(buffer), None).map_err(|_| EAGAIN)??;
+
+	Ok((len, endpoint.addr, endpoint.port))
+}
+
+#[no_mangle]
+pub fn sys_udp_recv(fd: i32, buffer: &mut [u8]) -> Result<usize, i32> {
+	udp_recv(udp_handle(fd)?, buffer)
+}
+
+#[no_mangle]
+pub fn sys_udp_close(fd: i32) -> Result<(), i32> {
+	close(fd)
+}
+
+/// Resolves a host name into its IPv4 and IPv6 addresses by asking the
+/// nameservers of the `-dns` option or of DHCP. Answers are cached.
+#[no_mangle]
+pub fn sys_getaddrinfo(name: &[u8]) -> Result<Vec<IpAddress>, i32> {
+	let name = core::str::from_utf8(name).map_err(|_| EINVAL)?;
+	crate::net::dns::resolve(name)
+}
+
+/// Description of a network interface, which is returned by [`sys_net_interfaces`]
+#[derive(Debug, Clone)]
+pub struct NetworkInterfaceInfo {
+	pub mac: [u8; 6],
+	pub mtu: usize,
+	pub addresses: Vec<IpCidr>,
+}
+
+/// Lists all network interfaces. The position in the list is the index,
+/// which selects an interface in the other syscalls.
+#[no_mangle]
+pub fn sys_net_interfaces() -> Result<Vec<NetworkInterfaceInfo>, i32> {
+	let mut guard = crate::net::NIC.lock();
+
+	Ok(guard
+		.nics_mut()
+		.iter()
+		.map(|nic| NetworkInterfaceInfo {
+			mac: nic.mac_address().0,
+			mtu: nic.mtu(),
+			addresses: nic.addresses(),
+		})
+		.collect())
+}
+
+/// Returns the IPv4 address, netmask and default gateway of a network interface.
+#[no_mangle]
+pub fn sys_net_get_ipv4_config(
+	interface: usize,
+) -> Result<(IpAddress, IpAddress, Option<IpAddress>), i32> {
+	let mut guard = crate::net::NIC.lock();
+	let config = guard.nic_mut(interface)?.ipv4_config();
+
+	Ok((
+		IpAddress::Ipv4(config.address.address()),
+		IpAddress::Ipv4(config.address.netmask()),
+		config.gateway.map(IpAddress::Ipv4),
+	))
+}
+
+/// Configures the IPv4 address, netmask and default gateway statically and
+/// stops the DHCP client. An empty `gateway` removes the default route.
+#[no_mangle]
+pub fn sys_net_set_ipv4_config(
+	interface: usize,
+	ip: &[u8],
+	netmask: &[u8],
+	gateway: &[u8],
+) -> Result<(), i32> {
+	let ip = core::str::from_utf8(ip).map_err(|_| EINVAL)?;
+	let netmask = core::str::from_utf8(netmask).map_err(|_| EINVAL)?;
+	let gateway = core::str::from_utf8(gateway).map_err(|_| EINVAL)?;
+	let config = Ipv4Config::parse(ip, netmask, Some(gateway).filter(|s| !s.is_empty()))?;
+
+	let mut guard = crate::net::NIC.lock();
+	guard.nic_mut(interface)?.set_ipv4_config(config);
+
+	Ok(())
+}
+
+#[no_mangle]
+pub fn sys_net_get_dhcp(interface: usize) -> Result<bool, i32> {
+	let mut guard = crate::net::NIC.lock();
+	Ok(guard.nic_mut(interface)?.dhcp_enabled())
+}
 
-	Ok((socket.inner(), addr, port))
+/// Starts or stops the DHCP client. Returns `EOPNOTSUPP`, if the
+/// kernel is built without the `dhcpv4` feature.
+#[no_mangle]
+pub fn sys_net_set_dhcp(interface: usize, enabled: bool) -> Result<(), i32> {
+	let mut guard = crate::net::NIC.lock();
+	guard.nic_mut(interface)?.set_dhcp(enabled)
 }
@@ -1,4 +1,5 @@
 use crate::arch::get_processor_count;
+use crate::scheduler;
 
 extern "C" fn __sys_get_processor_count() -> usize {
 	get_processor_count().try_into().unwrap()
@@ -19,3 +20,13 @@ extern "C" fn __sys_get_processor_frequency() -> u16 {
 pub extern "C" fn sys_get_processor_frequency() -> u16 {
 	kernel_function!(__sys_get_processor_frequency())
 }
+
+extern "C" fn __sys_get_idle_time(core_id: u32) -> u64 {
+	scheduler::get_idle_time(core_id).unwrap_or(0)
+}
+
+/// Returns the time in microseconds, which the processor `core_id` has spent idle.
+#[no_mangle]
+pub extern "C" fn sys_get_idle_time(core_id: u32) -> u64 {
+	kernel_function!(__sys_get_idle_time(core_id))
+}
@@ -13,7 +13,9 @@ use crate::errno::*;
 #[cfg(feature = "newlib")]
 use crate::mm::{task_heap_end, task_heap_start};
 use crate::scheduler;
-use crate::scheduler::task::{Priority, TaskHandle, TaskId};
+#[cfg(feature = "newlib")]
+use crate::scheduler::task::NO_PRIORITIES;
+use crate::scheduler::task::{CpuSet, Priority, TaskHandle, TaskId};
 use crate::synch::spinlock::SpinlockIrqSave;
 use crate::syscalls;
 use crate::syscalls::timer::timespec;
@@ -33,12 +35,13 @@ pub extern "C" fn sys_getpid() -> Tid {
 
 #[cfg(feature = "newlib")]
 extern "C" fn __sys_getprio(id: *const Tid) -> i32 {
-	let task = core_scheduler().get_current_task_handle();
+	if id.is_null() {
+		return i32::from(core_scheduler().get_current_task_prio().into());
+	}
 
-	if id.is_null() || unsafe { *id } == task.get_id().into() {
-		i32::from(task.get_priority().into())
-	} else {
-		-EINVAL
+	match scheduler::get_priority(TaskId::from(unsafe { *id })) {
+		Some(prio) => i32::from(prio.into()),
+		None => -ESRCH,
 	}
 }
 
@@ -48,10 +51,28 @@ pub extern "C" fn sys_getprio(id: *const Tid) -> i32 {
 	kernel_function!(__sys_getprio(id))
 }
 
+#[cfg(feature = "newlib")]
+extern "C" fn __sys_setprio(id: *const Tid, prio: i32) -> i32 {
+	if prio <= 0 || prio >= NO_PRIORITIES as i32 {
+		return -EINVAL;
+	}
+	let prio = Priority::from(prio as u8);
+
+	if id.is_null() {
+		core_scheduler().set_current_task_priority(prio);
+		return 0;
+	}
+
+	match core_scheduler().set_priority(TaskId::from(unsafe { *id }), prio) {
+		Ok(()) => 0,
+		Err(()) => -ESRCH,
+	}
+}
+
 #[cfg(feature = "newlib")]
 #[no_mangle]
-pub extern "C" fn sys_setprio(_id: *const Tid, _prio: i32) -> i32 {
-	-ENOSYS
+pub extern "C" fn sys_setprio(id: *const Tid, prio: i32) -> i32 {
+	kernel_function!(__sys_setprio(id, prio))
 }
 
 extern "C" fn __sys_exit(arg: i32) -> ! {
@@ -231,15 +252,25 @@ extern "C" fn __sys_spawn2(
 ) -> Tid {
 	static CORE_COUNTER: AtomicU32 = AtomicU32::new(1);
 
-	let core_id = if selector < 0 {
-		// use Round Robin to schedule the cores
-		CORE_COUNTER.fetch_add(1, Ordering::SeqCst) % get_processor_count()
+	let (core_id, affinity) = if selector < 0 {
+		// use Round Robin to schedule the cores, the task may migrate later on
+		(
+			CORE_COUNTER.fetch_add(1, Ordering::SeqCst) % get_processor_count(),
+			CpuSet::all(),
+		)
 	} else {
-		selector as u32
+		(selector as u32, CpuSet::single(selector as u32))
 	};
 
-	scheduler::PerCoreScheduler::spawn(func, arg, Priority::from(prio), core_id, stack_size).into()
-		as Tid
+	scheduler::PerCoreScheduler::spawn(
+		func,
+		arg,
+		Priority::from(prio),
+		core_id,
+		affinity,
+		stack_size,
+	)
+	.into() as Tid
 }
 
 #[no_mangle]
@@ -363,3 +394,36 @@ extern "C" fn __sys_set_current_task_priority(prio: u8) {
 pub extern "C" fn sys_set_current_task_priority(prio: u8) {
 	kernel_function!(__sys_set_current_task_priority(prio))
 }
+
+extern "C" fn __sys_set_affinity(id: Tid, cpuset: u64) -> i32 {
+	let task_id = TaskId::from(id);
+
+	if !scheduler::task_exists(task_id) {
+		return -ESRCH;
+	}
+
+	match core_scheduler().set_affinity(task_id, CpuSet::from_bits(cpuset)) {
+		Ok(()) => 0,
+		Err(()) => -EINVAL,
+	}
+}
+
+/// Restrict the thread with the identifier `id` to the cores in the bit mask `cpuset`.
+///
+/// Bit `n` of `cpuset` selects the core `n`. If the thread runs on another core,
+/// it migrates to one of the selected cores.
+#[no_mangle]
+pub extern "C" fn sys_set_affinity(id: Tid, cpuset: u64) -> i32 {
+	kernel_function!(__sys_set_affinity(id, cpuset))
+}
+
+extern "C" fn __sys_get_affinity(id: Tid) -> u64 {
+	scheduler::get_affinity(TaskId::from(id)).map_or(0, CpuSet::into_bits)
+}
+
+/// Determine the bit mask of cores, on which the thread with the identifier `id`
+/// is allowed to run. Returns an empty mask if the thread doesn't exist.
+#[no_mangle]
+pub extern "C" fn sys_get_affinity(id: Tid) -> u64 {
+	kernel_function!(__sys_get_affinity(id))
+}
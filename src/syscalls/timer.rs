@@ -1,5 +1,7 @@
 use crate::arch;
+use crate::arch::percore::core_scheduler;
 use crate::errno::*;
+use crate::scheduler;
 use crate::syscalls::__sys_usleep;
 
 #[derive(Copy, Clone, Debug)]
@@ -23,11 +25,22 @@ pub struct timeval {
 	pub tv_usec: i64,
 }
 
+#[derive(Copy, Clone, Debug)]
+#[repr(C)]
+pub struct rusage {
+	/// CPU time used by the tasks
+	pub ru_utime: timeval,
+	/// CPU time used by the kernel, which is included in `ru_utime`
+	pub ru_stime: timeval,
+}
+
 pub(crate) const CLOCK_REALTIME: u64 = 1;
 pub(crate) const CLOCK_PROCESS_CPUTIME_ID: u64 = 2;
 pub(crate) const CLOCK_THREAD_CPUTIME_ID: u64 = 3;
 pub(crate) const CLOCK_MONOTONIC: u64 = 4;
 pub(crate) const TIMER_ABSTIME: i32 = 4;
+pub(crate) const RUSAGE_SELF: i32 = 0;
+pub(crate) const RUSAGE_THREAD: i32 = 1;
 
 fn microseconds_to_timespec(microseconds: u64, result: &mut timespec) {
 	result.tv_sec = (microseconds / 1_000_000) as i64;
@@ -82,6 +95,14 @@ extern "C" fn __sys_clock_gettime(clock_id: u64, tp: *mut timespec) -> i32 {
 			microseconds_to_timespec(microseconds, result);
 			0
 		}
+		CLOCK_PROCESS_CPUTIME_ID => {
+			microseconds_to_timespec(scheduler::get_cpu_time(), result);
+			0
+		}
+		CLOCK_THREAD_CPUTIME_ID => {
+			microseconds_to_timespec(core_scheduler().get_current_task_cpu_time(), result);
+			0
+		}
 		_ => {
 			debug!(
 				"Called sys_clock_gettime for unsupported clock {}",
@@ -178,6 +199,32 @@ pub extern "C" fn sys_gettimeofday(tp: *mut timeval, tz: usize) -> i32 {
 	kernel_function!(__sys_gettimeofday(tp, tz))
 }
 
+extern "C" fn __sys_getrusage(who: i32, usage: *mut rusage) -> i32 {
+	let microseconds = match who {
+		RUSAGE_SELF => scheduler::get_cpu_time(),
+		RUSAGE_THREAD => core_scheduler().get_current_task_cpu_time(),
+		_ => {
+			debug!("Called sys_getrusage for unsupported target {}", who);
+			return -EINVAL;
+		}
+	};
+
+	if let Some(result) = unsafe { usage.as_mut() } {
+		// HermitCore doesn't distinguish between user and system time.
+		microseconds_to_timeval(microseconds, &mut result.ru_utime);
+		microseconds_to_timeval(0, &mut result.ru_stime);
+		0
+	} else {
+		-EINVAL
+	}
+}
+
+/// Returns the CPU time of all tasks (`RUSAGE_SELF`) or of the current task (`RUSAGE_THREAD`).
+#[no_mangle]
+pub extern "C" fn sys_getrusage(who: i32, usage: *mut rusage) -> i32 {
+	kernel_function!(__sys_getrusage(who, usage))
+}
+
 #[no_mangle]
 extern "C" fn __sys_setitimer(
 	_which: i32,
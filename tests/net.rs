@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+#![test_runner(common::test_case_runner)]
+#![feature(custom_test_frameworks)]
+#![reexport_test_harness_main = "test_main"]
+
+extern crate hermit;
+
+use common::*;
+mod common;
+
+use hermit::errno::EAGAIN;
+use hermit::sys_usleep;
+
+extern "Rust" {
+	fn sys_tcp_stream_connect(ip: &[u8], port: u16, timeout: Option<u64>) -> Result<i32, i32>;
+	fn sys_tcp_stream_read(fd: i32, buffer: &mut [u8]) -> Result<usize, i32>;
+	fn sys_tcp_stream_set_read_timeout(fd: i32, timeout: Option<u64>) -> Result<(), i32>;
+	fn sys_tcp_stream_close(fd: i32) -> Result<(), i32>;
+}
+
+/// QEMU accepts connections to this address, but never sends any data
+/// (see `hermit_test_runner.py`).
+const IDLE_PEER: &[u8] = b"10.0.2.100";
+const IDLE_PEER_PORT: u16 = 7;
+
+/// Connects to the idle peer. The interface may still wait for its
+/// address from DHCP, so the connection is tried several times.
+fn connect_idle_peer() -> i32 {
+	for _ in 0..50 {
+		match unsafe { sys_tcp_stream_connect(IDLE_PEER, IDLE_PEER_PORT, Some(1000)) } {
+			Ok(fd) => return fd,
+			Err(_) => sys_usleep(100_000),
+		}
+	}
+
+	panic!("Unable to connect to the idle peer");
+}
+
+#[test_case]
+fn read_timeout_on_idle_socket() {
+	let fd = connect_idle_peer();
+	let mut buffer = [0; 16];
+
+	unsafe {
+		sys_tcp_stream_set_read_timeout(fd, Some(200)).unwrap();
+		assert_eq!(sys_tcp_stream_read(fd, &mut buffer), Err(EAGAIN));
+		sys_tcp_stream_close(fd).unwrap();
+	}
+}
+
+#[no_mangle]
+extern "C" fn runtime_entry(_argc: i32, _argv: *const *const u8, _env: *const *const u8) -> ! {
+	test_main();
+	common::exit(false)
+}
@@ -29,6 +29,8 @@ xflags::xflags! {
 			optional --profile profile: String
 			/// Enable the `-Z instrument-mcount` flag.
 			optional --instrument-mcount
+			/// Embed the directory as read-only initramfs, which is mounted at `/`.
+			optional --initramfs initramfs: PathBuf
 		}
 
 		/// Run clippy for all targets.
@@ -65,6 +67,7 @@ pub struct Build {
 	pub release: bool,
 	pub profile: Option<String>,
 	pub instrument_mcount: bool,
+	pub initramfs: Option<PathBuf>,
 }
 
 #[derive(Debug)]
@@ -0,0 +1,139 @@
+//! Packs a directory into the ustar archive, which is embedded into the kernel as initramfs.
+
+use std::{
+	fs::{self, Metadata},
+	path::Path,
+	time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+const BLOCK_SIZE: usize = 512;
+const TYPE_FILE: u8 = b'0';
+const TYPE_DIRECTORY: u8 = b'5';
+
+/// Writes the files and directories below `dir` to the archive at `archive`.
+pub fn create(dir: &Path, archive: &Path) -> Result<()> {
+	let mut tar = Vec::new();
+	append_dir(&mut tar, dir, "")?;
+	// two blocks of zeros terminate the archive
+	tar.resize(tar.len() + 2 * BLOCK_SIZE, 0);
+
+	fs::write(archive, tar).with_context(|| format!("writing {}", archive.display()))?;
+	Ok(())
+}
+
+fn append_dir(tar: &mut Vec<u8>, dir: &Path, prefix: &str) -> Result<()> {
+	let mut entries = fs::read_dir(dir)
+		.with_context(|| format!("reading {}", dir.display()))?
+		.collect::<Result<Vec<_>, _>>()?;
+	// sort the entries, so that the archive is reproducible
+	entries.sort_by_key(|entry| entry.file_name());
+
+	for entry in entries {
+		let name = entry
+			.file_name()
+			.into_string()
+			.map_err(|name| anyhow!("{name:?} is not valid UTF-8"))?;
+		let path = format!("{prefix}{name}");
+		// symbolic links are replaced by their targets
+		let metadata = fs::metadata(entry.path())?;
+
+		if metadata.is_dir() {
+			let path = format!("{path}/");
+			append_header(tar, &path, TYPE_DIRECTORY, &metadata, 0)?;
+			append_dir(tar, &entry.path(), &path)?;
+		} else if metadata.is_file() {
+			let data = fs::read(entry.path())?;
+			append_header(tar, &path, TYPE_FILE, &metadata, data.len())?;
+			tar.extend_from_slice(&data);
+			tar.resize((tar.len() + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE, 0);
+		} else {
+			eprintln!("Skipping {path}, which is neither a file nor a directory");
+		}
+	}
+
+	Ok(())
+}
+
+fn append_header(
+	tar: &mut Vec<u8>,
+	path: &str,
+	typeflag: u8,
+	metadata: &Metadata,
+	size: usize,
+) -> Result<()> {
+	let (prefix, name) = split_path(path)?;
+	let mtime = metadata
+		.modified()
+		.ok()
+		.and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+		.map(|mtime| mtime.as_secs())
+		.unwrap_or_default();
+
+	let mut header = [0; BLOCK_SIZE];
+	header[..name.len()].copy_from_slice(name.as_bytes());
+	write_octal(&mut header[100..108], mode(metadata, typeflag).into())?;
+	// uid and gid
+	write_octal(&mut header[108..116], 0)?;
+	write_octal(&mut header[116..124], 0)?;
+	write_octal(&mut header[124..136], size as u64)?;
+	write_octal(&mut header[136..148], mtime)?;
+	header[156] = typeflag;
+	header[257..263].copy_from_slice(b"ustar\0");
+	header[263..265].copy_from_slice(b"00");
+	header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+	// the checksum is calculated with spaces in its own field
+	header[148..156].fill(b' ');
+	let checksum: u32 = header.iter().copied().map(u32::from).sum();
+	header[148..156].copy_from_slice(format!("{checksum:06o}\0 ").as_bytes());
+
+	tar.extend_from_slice(&header);
+	Ok(())
+}
+
+/// Splits a path into the prefix and name fields of a ustar header, which
+/// hold up to 155 and 100 bytes.
+fn split_path(path: &str) -> Result<(&str, &str)> {
+	if path.len() <= 100 {
+		return Ok(("", path));
+	}
+
+	path.char_indices()
+		.filter(|(_, c)| *c == '/')
+		.map(|(i, _)| (&path[..i], &path[i + 1..]))
+		.find(|(prefix, name)| prefix.len() <= 155 && !name.is_empty() && name.len() <= 100)
+		.ok_or_else(|| anyhow!("{path} is too long for a ustar archive"))
+}
+
+/// Writes `value` as octal number, which is terminated by NUL.
+fn write_octal(field: &mut [u8], value: u64) -> Result<()> {
+	let digits = format!("{value:0width$o}\0", width = field.len() - 1);
+	if digits.len() > field.len() {
+		bail!("{value} does not fit into a ustar header");
+	}
+	field.copy_from_slice(digits.as_bytes());
+	Ok(())
+}
+
+#[cfg(unix)]
+fn mode(metadata: &Metadata, _typeflag: u8) -> u32 {
+	use std::os::unix::fs::PermissionsExt;
+
+	metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(unix))]
+fn mode(metadata: &Metadata, typeflag: u8) -> u32 {
+	let mode = if typeflag == TYPE_DIRECTORY {
+		0o755
+	} else {
+		0o644
+	};
+	if metadata.permissions().readonly() {
+		mode & !0o222
+	} else {
+		mode
+	}
+}
@@ -3,6 +3,7 @@
 mod arch;
 mod archive;
 mod flags;
+mod initramfs;
 
 use std::{
 	env::{self, VarError},
@@ -36,8 +37,19 @@ impl flags::Build {
 	fn run(self) -> Result<()> {
 		let sh = sh()?;
 
+		let mut build = cmd!(sh, "cargo build");
+		if let Some(initramfs) = &self.initramfs {
+			let archive = sh.current_dir().join(self.out_dir()).join("initramfs.tar");
+			eprintln!("Packing {} into {}", initramfs.display(), archive.display());
+			sh.create_dir(archive.parent().unwrap())?;
+			initramfs::create(initramfs, &archive)?;
+			build = build
+				.env("HERMIT_INITRAMFS", &archive)
+				.args(["--features", "initramfs"]);
+		}
+
 		eprintln!("Building kernel");
-		cmd!(sh, "cargo build")
+		build
 			.env("CARGO_ENCODED_RUSTFLAGS", self.cargo_encoded_rustflags()?)
 			.args(self.arch.cargo_args())
 			.args(self.target_dir_args())